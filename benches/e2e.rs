@@ -1,6 +1,6 @@
 use std::{env, sync::LazyLock};
 
-use db_mover::{self, uri::URI};
+use db_mover::{self, databases::sqlite::FastWriteJournal, uri::URI};
 
 use criterion::{Criterion, Throughput, criterion_group, criterion_main};
 
@@ -75,6 +75,25 @@ fn sqlite_to_sqlite(c: &mut Criterion) {
     });
 }
 
+fn sqlite_to_sqlite_fast_write(c: &mut Criterion) {
+    let mut input = LazyTestDatabaseFactory::new(TestSqliteDatabase::new());
+    let mut group = c.benchmark_group("sqlite_to_sqlite_fast_write");
+    group.throughput(Throughput::Elements(NUM_ROWS as u64));
+    for journal in [FastWriteJournal::Wal, FastWriteJournal::Off] {
+        group.bench_with_input(format!("{NUM_ROWS}/{journal:?}"), &journal, |b, journal| {
+            input.init();
+            b.iter(|| {
+                let mut output = TestSqliteDatabase::new();
+                output.create_test_table("test");
+                let mut args = db_mover::args::Args::new(input.get_uri(), output.get_uri());
+                args.table.push("test".to_string());
+                args.sqlite_fast_write = Some(*journal);
+                db_mover::run(args.clone()).unwrap();
+            })
+        });
+    }
+}
+
 fn sqlite_to_postgres(c: &mut Criterion) {
     let mut input = LazyTestDatabaseFactory::new(TestSqliteDatabase::new());
     let mut group = c.benchmark_group("sqlite_to_postgres");
@@ -199,6 +218,6 @@ fn mariadb_to_sqlite(c: &mut Criterion) {
 criterion_group! {
     name = benches;
     config = Criterion::default().sample_size(10);
-    targets = sqlite_to_sqlite, sqlite_to_postgres, postgres_to_sqlite, postgres_to_postgres, sqlite_to_mysql, mysql_to_sqlite, sqlite_to_mariadb, mariadb_to_sqlite,
+    targets = sqlite_to_sqlite, sqlite_to_sqlite_fast_write, sqlite_to_postgres, postgres_to_sqlite, postgres_to_postgres, sqlite_to_mysql, mysql_to_sqlite, sqlite_to_mariadb, mariadb_to_sqlite,
 }
 criterion_main!(benches);
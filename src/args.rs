@@ -1,16 +1,22 @@
+use crate::databases::csv::CsvDB;
 use crate::databases::mysql::{MysqlDB, MysqlTypeOptions};
 use crate::databases::postgres::PostgresDB;
-use crate::databases::sqlite::SqliteDB;
+use crate::databases::sqlite::{FastWriteJournal, SqliteDB};
 use crate::databases::traits::{DBReader, DBWriter};
+use crate::databases::write_mode::WriteMode;
 use crate::uri::URI;
 use anyhow::Context;
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
 use tracing::Level;
 
 #[derive(Parser, Clone)]
 #[command(version, about, long_about = None)]
 #[command(next_line_help = true)]
 pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// Input URI of database
     #[arg(long, short)]
     pub input: URI,
@@ -19,18 +25,76 @@ pub struct Args {
     #[arg(long, short)]
     pub output: URI,
 
-    /// List of tables. By default all tables from the input database
-    #[arg(long, short)]
+    /// List of tables. By default all tables from the input database.
+    /// SOURCE:DESTINATION moves a table under another name, names can be qualified
+    /// with a schema (PostgreSQL) or a database (MySQL), e.g. src.orders:dst.orders_archive
+    #[arg(long, short, value_name = "TABLE")]
     pub table: Vec<String>,
 
+    /// SQL condition to filter rows of a table, in format TABLE=CONDITION.
+    /// Can be used multiple times.
+    #[arg(long = "where", value_name = "TABLE=CONDITION")]
+    pub filter: Vec<String>,
+
+    /// Move only rows with values of an increasing column (id or timestamp) larger than
+    /// moved by the previous run and upsert them, in format TABLE=COLUMN.
+    /// Can be used multiple times.
+    #[arg(long, value_name = "TABLE=COLUMN", requires = "state_file")]
+    pub incremental: Vec<String>,
+
+    /// File to save the largest moved values of incremental columns to
+    #[arg(long)]
+    pub state_file: Option<PathBuf>,
+
+    /// JSON file with settings of tables, options from the command line take precedence
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+
     /// Size of queue between reader and writers
     #[arg(long, default_value_t = 100_000)]
     pub queue_size: usize,
 
+    /// Number of reader workers (if supported by database). Table is split into key ranges
+    /// by a single integer or UUID primary key, each range is read by its own connection.
+    #[arg(long, default_value_t = 1)]
+    pub reader_workers: usize,
+
     /// Number of writer workers (if supported by database)
     #[arg(long, default_value_t = 1)]
     pub writer_workers: usize,
 
+    /// Read all tables from a single consistent snapshot of the input database.
+    /// Only PostgreSQL shares the snapshot between connections, other databases read
+    /// one table at a time with a single reader worker.
+    #[clap(long, action)]
+    pub snapshot: bool,
+
+    /// PostgreSQL logical replication slot to read changes from, created with the test_decoding
+    /// plugin before tables are moved. If the slot already exists, tables are not moved again.
+    #[arg(long)]
+    pub slot: Option<String>,
+
+    /// File to save the MySQL binlog position to, taken before tables are moved.
+    /// If the file has a position, tables are not moved again.
+    #[arg(long)]
+    pub binlog_state_file: Option<PathBuf>,
+
+    /// After moving tables, apply inserts, updates and deletes of the input tables
+    /// until the process is stopped. PostgreSQL changes are read from the replication slot,
+    /// MySQL changes from the row based binlog (requires replication privileges).
+    /// Filtered tables (--where) are not supported.
+    #[clap(long, action)]
+    pub follow: bool,
+
+    /// Number of tables moved at the same time
+    #[arg(long, default_value_t = 1)]
+    pub parallel_tables: usize,
+
+    /// Limit of connections opened by reader and writer workers of tables moved at the same
+    /// time. Table waits for enough free connections before starting.
+    #[arg(long)]
+    pub max_connections: Option<usize>,
+
     /// Size of batches used by writer
     #[arg(long, default_value_t = 10_000)]
     pub batch_write_size: usize,
@@ -61,16 +125,106 @@ pub struct Args {
     /// Disable assumption that binary(16) is UUID for MySQL
     #[clap(long, action)]
     pub no_mysql_binary_16_as_uuid: bool,
+
+    /// Write to MySQL with LOAD DATA LOCAL INFILE instead of INSERT. INSERT is still used
+    /// for upserts and if the server disallows local infile.
+    #[clap(long, action)]
+    pub mysql_load_data: bool,
+
+    /// Read MySQL tables in pages of this many rows, ordered by a single column primary key.
+    /// Tables without such a key are rejected. Without the option every table is streamed
+    /// in full by a single query.
+    #[arg(long)]
+    pub mysql_fetch_size: Option<usize>,
+
+    /// Write to SQLite without synchronous writes, with a large cache, multi-row inserts and
+    /// the given journal mode. The previous journal mode is restored after the run.
+    #[arg(long, value_enum, num_args = 0..=1, default_missing_value = "wal")]
+    pub sqlite_fast_write: Option<FastWriteJournal>,
+
+    /// Store decimals in SQLite as REAL instead of lossless TEXT
+    #[clap(long, action)]
+    pub sqlite_decimal_as_real: bool,
+
+    /// Write array columns to JSON columns as JSON arrays, for output databases without arrays
+    /// like MySQL and SQLite. Created tables get JSON columns instead of arrays.
+    #[clap(long, action)]
+    pub arrays_as_json: bool,
+
+    /// How rows are written. Upsert and ignore-duplicates allow non-empty output tables,
+    /// conflicts are detected by the primary key of the output table.
+    #[arg(long, value_enum, default_value_t = WriteMode::Insert)]
+    pub write_mode: WriteMode,
+
+    /// Disable triggers and foreign key checks of the output database while writing.
+    /// Disabling triggers in PostgreSQL requires a superuser.
+    #[clap(long, action)]
+    pub fast_load: bool,
+
+    /// Drop non-unique secondary indexes of output tables before writing
+    /// and recreate them afterwards
+    #[clap(long, action, requires = "fast_load")]
+    pub drop_indexes: bool,
+
+    /// Create tables missing in the output database using the schema of the input table.
+    /// MySQL timestamp columns are created for timestamps with time zone,
+    /// they only hold timestamps from 1970 to 2038.
+    #[clap(long, action)]
+    pub create_tables: bool,
+
+    /// Write into a staging table created like the output table and replace the output
+    /// table with it once the table is moved and verified. Replaces non-empty tables.
+    /// PostgreSQL and MySQL tables with foreign keys or triggers are not supported,
+    /// grants of the output table are not kept.
+    #[clap(long, action, conflicts_with = "resume")]
+    pub atomic: bool,
+
+    /// File to save progress of each table to. Table needs a single integer or uuid primary key,
+    /// it is read in key order by one reader and written by one writer.
+    #[arg(long)]
+    pub checkpoint_file: Option<PathBuf>,
+
+    /// Continue tables from the checkpoint file, skipping already moved ones.
+    /// Rows of resumed tables are upserted, as rows after the saved key may be written already.
+    #[clap(long, action, requires = "checkpoint_file")]
+    pub resume: bool,
+
+    /// Verify moved tables by comparing row counts and checksums of rows
+    #[clap(long, action)]
+    pub verify: bool,
+
+    /// Verify and report primary keys of differing rows.
+    /// Keeps hashes of all input rows of a table in memory.
+    #[clap(long, action)]
+    pub verify_detailed: bool,
+}
+
+#[derive(Subcommand, Clone, Debug, PartialEq)]
+pub enum Command {
+    /// Compare tables of input and output databases without moving data
+    Verify,
 }
 
 impl Args {
     pub fn new(input: URI, output: URI) -> Self {
         return Args {
+            command: None,
             input,
             output,
             table: Vec::new(),
+            filter: Vec::new(),
+            incremental: Vec::new(),
+            state_file: None,
+            config: None,
             queue_size: 100_000,
+            reader_workers: 1,
             writer_workers: 1,
+            snapshot: false,
+            slot: None,
+            binlog_state_file: None,
+            follow: false,
+            parallel_tables: 1,
+            max_connections: None,
             batch_write_size: 10_000,
             batch_write_retries: 1,
             quiet: true,
@@ -78,6 +232,20 @@ impl Args {
             no_count: false,
             dry_run: false,
             no_mysql_binary_16_as_uuid: false,
+            mysql_load_data: false,
+            mysql_fetch_size: None,
+            sqlite_fast_write: None,
+            sqlite_decimal_as_real: false,
+            arrays_as_json: false,
+            write_mode: WriteMode::Insert,
+            fast_load: false,
+            drop_indexes: false,
+            create_tables: false,
+            atomic: false,
+            checkpoint_file: None,
+            resume: false,
+            verify: false,
+            verify_detailed: false,
         };
     }
 
@@ -93,29 +261,66 @@ impl Args {
         ));
     }
 
-    fn build_mysql(&self, uri: &str) -> anyhow::Result<Box<MysqlDB>> {
-        let options = MysqlTypeOptions {
+    pub(crate) fn mysql_type_options(&self) -> MysqlTypeOptions {
+        return MysqlTypeOptions {
             binary_16_as_uuid: !self.no_mysql_binary_16_as_uuid,
             ..Default::default()
         };
-        let db = MysqlDB::new(uri, options).context("Unable to connect to the mysql")?;
+    }
+
+    fn build_mysql(&self, uri: &str) -> anyhow::Result<Box<MysqlDB>> {
+        let mut db = MysqlDB::new(uri, self.mysql_type_options())
+            .context("Unable to connect to the mysql")?;
+        if self.mysql_load_data {
+            db.enable_load_data();
+        }
+        if let Some(fetch_size) = self.mysql_fetch_size {
+            db.set_fetch_size(fetch_size);
+        }
         return Ok(Box::new(db));
     }
 
-    pub fn create_reader(&self) -> anyhow::Result<Box<dyn DBReader>> {
-        let reader: Box<dyn DBReader> = match &self.input {
+    fn build_csv(&self, uri: &str) -> anyhow::Result<Box<CsvDB>> {
+        return Ok(Box::new(CsvDB::new(uri).context("Unable to open the csv")?));
+    }
+
+    fn build_reader(&self, uri: &URI) -> anyhow::Result<Box<dyn DBReader>> {
+        let reader: Box<dyn DBReader> = match uri {
             URI::Sqlite(uri) => self.build_sqlite(uri)?,
             URI::Postgres(uri) => self.build_postgres(uri)?,
             URI::Mysql(uri) => self.build_mysql(uri)?,
+            URI::Csv(uri) => self.build_csv(uri)?,
         };
         return Ok(reader);
     }
 
+    pub fn create_reader(&self) -> anyhow::Result<Box<dyn DBReader>> {
+        return self.build_reader(&self.input);
+    }
+
+    // Reader of the output database, used to check moved data
+    pub fn create_output_reader(&self) -> anyhow::Result<Box<dyn DBReader>> {
+        return self.build_reader(&self.output);
+    }
+
     pub fn create_writer(&self) -> anyhow::Result<Box<dyn DBWriter>> {
         let writer: Box<dyn DBWriter> = match &self.output {
-            URI::Sqlite(uri) => self.build_sqlite(uri)?,
+            URI::Sqlite(uri) => {
+                let mut writer = self.build_sqlite(uri)?;
+                if let Some(journal) = self.sqlite_fast_write {
+                    writer.enable_fast_write()?;
+                    if journal == FastWriteJournal::Off {
+                        writer.disable_journal()?;
+                    }
+                }
+                if self.sqlite_decimal_as_real {
+                    writer.enable_decimal_as_real();
+                }
+                writer
+            }
             URI::Postgres(uri) => self.build_postgres(uri)?,
             URI::Mysql(uri) => self.build_mysql(uri)?,
+            URI::Csv(uri) => self.build_csv(uri)?,
         };
         return Ok(writer);
     }
@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::Context;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::databases::table::{Row, Value};
+
+// Tables moved concurrently share the same checkpoint and state files
+static FILE_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum CheckpointKey {
+    I64(i64),
+    I32(i32),
+    I16(i16),
+    Uuid(String),
+}
+
+impl TryFrom<&Value> for CheckpointKey {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        return match value {
+            &Value::I64(val) => Ok(CheckpointKey::I64(val)),
+            &Value::I32(val) => Ok(CheckpointKey::I32(val)),
+            &Value::I16(val) => Ok(CheckpointKey::I16(val)),
+            Value::Uuid(val) => Ok(CheckpointKey::Uuid(val.hyphenated().to_string())),
+            _ => Err(anyhow::anyhow!("Unsupported checkpoint key {value:?}")),
+        };
+    }
+}
+
+impl TryFrom<&CheckpointKey> for Value {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &CheckpointKey) -> Result<Self, Self::Error> {
+        return match value {
+            &CheckpointKey::I64(val) => Ok(Value::I64(val)),
+            &CheckpointKey::I32(val) => Ok(Value::I32(val)),
+            &CheckpointKey::I16(val) => Ok(Value::I16(val)),
+            CheckpointKey::Uuid(val) => Ok(Value::Uuid(
+                uuid::Uuid::parse_str(val).context("Invalid uuid in checkpoint")?,
+            )),
+        };
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub column: String,
+    // Last key written to the output table, all rows with smaller keys are written too
+    pub key: Option<CheckpointKey>,
+    pub finished: bool,
+}
+
+pub fn load(path: &Path) -> anyhow::Result<HashMap<String, Checkpoint>> {
+    return load_entries(path);
+}
+
+// Entries of tables saved in a JSON file, empty if the file doesn't exist
+pub(crate) fn load_entries<T: DeserializeOwned>(path: &Path) -> anyhow::Result<HashMap<String, T>> {
+    let _guard = FILE_LOCK.lock().unwrap();
+    return load_unlocked(path);
+}
+
+fn load_unlocked<T: DeserializeOwned>(path: &Path) -> anyhow::Result<HashMap<String, T>> {
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+    let file =
+        std::fs::File::open(path).with_context(|| format!("Failed to open state file {path:?}"))?;
+    return serde_json::from_reader(file)
+        .with_context(|| format!("Failed to parse state file {path:?}"));
+}
+
+pub(crate) fn store<T: Serialize + DeserializeOwned>(
+    path: &Path,
+    table: &str,
+    entry: T,
+) -> anyhow::Result<()> {
+    let _guard = FILE_LOCK.lock().unwrap();
+    let mut entries: HashMap<String, T> = load_unlocked(path)?;
+    entries.insert(table.to_string(), entry);
+    // Write and rename, so an interrupted write never corrupts the file
+    let tmp_path = path.with_extension("tmp");
+    let file = std::fs::File::create(&tmp_path)
+        .with_context(|| format!("Failed to create state file {tmp_path:?}"))?;
+    serde_json::to_writer_pretty(file, &entries).context("Failed to write state file")?;
+    std::fs::rename(&tmp_path, path)
+        .with_context(|| format!("Failed to replace state file {path:?}"))?;
+    return Ok(());
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TableCheckpoint {
+    path: PathBuf,
+    table: String,
+    column: String,
+    // Position of the key column in written rows
+    key_index: usize,
+}
+
+impl TableCheckpoint {
+    pub fn new(path: &Path, table: &str, column: &str, key_index: usize) -> Self {
+        return Self {
+            path: path.to_path_buf(),
+            table: table.to_string(),
+            column: column.to_string(),
+            key_index,
+        };
+    }
+
+    pub fn save(&self, last_row: &Row) -> anyhow::Result<()> {
+        let key = CheckpointKey::try_from(&last_row[self.key_index])?;
+        let checkpoint = Checkpoint {
+            column: self.column.clone(),
+            key: Some(key),
+            finished: false,
+        };
+        return store(&self.path, &self.table, checkpoint);
+    }
+
+    pub fn finish(&self) -> anyhow::Result<()> {
+        let mut checkpoint = load(&self.path)?
+            .remove(&self.table)
+            .unwrap_or_else(|| Checkpoint {
+                column: self.column.clone(),
+                key: None,
+                finished: false,
+            });
+        checkpoint.finished = true;
+        return store(&self.path, &self.table, checkpoint);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checkpoint_save_and_finish() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let path = tmp_dir.path().join("checkpoint.json");
+        assert!(load(&path).unwrap().is_empty());
+
+        let checkpoint = TableCheckpoint::new(&path, "test", "id", 1);
+        checkpoint.save(&vec![Value::Null, Value::I64(10)]).unwrap();
+        let other = TableCheckpoint::new(&path, "other", "id", 0);
+        let uuid = uuid::Uuid::from_u128(1);
+        other.save(&vec![Value::Uuid(uuid)]).unwrap();
+
+        let checkpoints = load(&path).unwrap();
+        assert_eq!(checkpoints["test"].key, Some(CheckpointKey::I64(10)));
+        assert!(!checkpoints["test"].finished);
+        let key = checkpoints["other"].key.as_ref().unwrap();
+        assert_eq!(Value::try_from(key).unwrap(), Value::Uuid(uuid));
+
+        checkpoint.finish().unwrap();
+        let checkpoints = load(&path).unwrap();
+        assert_eq!(checkpoints["test"].key, Some(CheckpointKey::I64(10)));
+        assert!(checkpoints["test"].finished);
+    }
+
+    #[test]
+    fn test_checkpoint_unsupported_key() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let path = tmp_dir.path().join("checkpoint.json");
+        let checkpoint = TableCheckpoint::new(&path, "test", "id", 0);
+        assert!(checkpoint.save(&vec![Value::Bool(true)]).is_err());
+    }
+}
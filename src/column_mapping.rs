@@ -0,0 +1,363 @@
+use std::collections::HashMap;
+
+use anyhow::Context;
+
+use crate::config::TableConfig;
+use crate::databases::table::{Column, ColumnType, Row, TableInfo, Value};
+
+#[derive(Clone, Debug, PartialEq)]
+enum ColumnSource {
+    // Position in the row read from input
+    Input(usize),
+    Constant(Value),
+}
+
+// Turns rows of the input table into rows of the output table
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColumnMapping {
+    // Input columns with types of matching output columns, in order of output
+    pub reader_format: TableInfo,
+    // Output columns to write, columns filled by database defaults are skipped
+    pub writer_format: TableInfo,
+    layout: Vec<ColumnSource>,
+}
+
+fn is_type_compatible(
+    reader_type: &ColumnType,
+    writer_type: &ColumnType,
+    arrays_as_json: bool,
+) -> bool {
+    return match (reader_type, writer_type) {
+        (ColumnType::I16, ColumnType::I32 | ColumnType::I64)
+        | (ColumnType::I32, ColumnType::I64)
+        | (ColumnType::F32, ColumnType::F64) => true,
+        (ColumnType::Array(reader_item), ColumnType::Array(writer_item)) => {
+            is_type_compatible(reader_item, writer_item, arrays_as_json)
+        }
+        (ColumnType::Array(_), ColumnType::Json) => arrays_as_json,
+        _ => reader_type == writer_type,
+    };
+}
+
+// Value of an input column as a value of the compatible output column
+fn convert_value(value: Value, column_type: &ColumnType) -> anyhow::Result<Value> {
+    let converted = match (value, column_type) {
+        (Value::I16(val), ColumnType::I32) => Value::I32(val.into()),
+        (Value::I16(val), ColumnType::I64) => Value::I64(val.into()),
+        (Value::I32(val), ColumnType::I64) => Value::I64(val.into()),
+        (Value::F32(val), ColumnType::F64) => Value::F64(val.into()),
+        (Value::Array(items), ColumnType::Json) => Value::Json(Value::array_to_json(&items)?),
+        (Value::Array(items), ColumnType::Array(item_type)) => Value::Array(
+            items
+                .into_iter()
+                .map(|item| convert_value(item, item_type))
+                .collect::<anyhow::Result<_>>()?,
+        ),
+        (value, column_type) => {
+            if value
+                .column_type()
+                .is_some_and(|value_type| value_type != *column_type)
+            {
+                return Err(anyhow::anyhow!(
+                    "Value {value:?} can't be written to a column of type {column_type:?}"
+                ));
+            }
+            value
+        }
+    };
+    return Ok(converted);
+}
+
+fn is_compatible(reader_column: &Column, writer_column: &Column, arrays_as_json: bool) -> bool {
+    if !writer_column.nullable && reader_column.nullable {
+        return false;
+    }
+    return is_type_compatible(
+        &reader_column.column_type,
+        &writer_column.column_type,
+        arrays_as_json,
+    );
+}
+
+impl ColumnMapping {
+    pub fn new(
+        reader_info: &TableInfo,
+        writer_info: &TableInfo,
+        config: &TableConfig,
+    ) -> anyhow::Result<Self> {
+        let incompatible = || {
+            anyhow::anyhow!(
+                "Incompatable set of columns for table \"{}\".\n\
+                In: {:?}\n\
+                Out: {:?}",
+                &writer_info.name,
+                reader_info.columns,
+                writer_info.columns
+            )
+        };
+        for name in config.columns.keys() {
+            if reader_info.column(name).is_none() {
+                return Err(anyhow::anyhow!(
+                    "Mapped column {name} not found in input table \"{}\"",
+                    reader_info.name
+                ));
+            }
+        }
+        for name in config.constants.keys().chain(&config.defaults) {
+            if writer_info.column(name).is_none() {
+                return Err(anyhow::anyhow!(
+                    "Column {name} not found in output table \"{}\"",
+                    writer_info.name
+                ));
+            }
+        }
+
+        let mut sources: HashMap<&str, &Column> = HashMap::new();
+        for reader_column in &reader_info.columns {
+            let target = match config.columns.get(&reader_column.name) {
+                Some(Some(target)) => target.as_str(),
+                Some(None) => continue,
+                None => reader_column.name.as_str(),
+            };
+            if sources.insert(target, reader_column).is_some() {
+                return Err(anyhow::anyhow!(
+                    "Several input columns are mapped to column {target}"
+                ));
+            }
+        }
+
+        let mut reader_columns = Vec::new();
+        let mut writer_columns = Vec::new();
+        let mut layout = Vec::new();
+        for writer_column in &writer_info.columns {
+            let name = &writer_column.name;
+            let source = sources.remove(name.as_str());
+            let constant = config.constants.get(name);
+            let default = config.defaults.contains(name);
+            match (source, constant, default) {
+                (Some(reader_column), None, false) => {
+                    if !is_compatible(reader_column, writer_column, config.arrays_as_json) {
+                        return Err(incompatible());
+                    }
+                    layout.push(ColumnSource::Input(reader_columns.len()));
+                    reader_columns.push(Column {
+                        name: reader_column.name.clone(),
+                        column_type: writer_column.column_type.clone(),
+                        nullable: writer_column.nullable,
+                    });
+                    writer_columns.push(writer_column.clone());
+                }
+                (None, Some(constant), false) => {
+                    let value = match constant {
+                        Some(text) => Value::try_from((writer_column, text.as_str()))
+                            .with_context(|| format!("Invalid constant for column {name}"))?,
+                        None => Value::Null,
+                    };
+                    if value == Value::Null && !writer_column.nullable {
+                        return Err(anyhow::anyhow!(
+                            "Column {name} is not nullable, but the constant is null"
+                        ));
+                    }
+                    layout.push(ColumnSource::Constant(value));
+                    writer_columns.push(writer_column.clone());
+                }
+                (None, None, true) => {}
+                (None, None, false) => return Err(incompatible()),
+                _ => {
+                    return Err(anyhow::anyhow!(
+                        "Column {name} of table \"{}\" has several sources",
+                        writer_info.name
+                    ));
+                }
+            }
+        }
+        // Input columns without a matching output column
+        if !sources.is_empty() {
+            return Err(incompatible());
+        }
+
+        return Ok(Self {
+            reader_format: TableInfo {
+                columns: reader_columns,
+                ..reader_info.clone()
+            },
+            writer_format: TableInfo {
+                columns: writer_columns,
+                ..writer_info.clone()
+            },
+            layout,
+        });
+    }
+
+    // Position of the input column in written rows
+    pub fn output_position(&self, input_column: &str) -> Option<usize> {
+        return self.layout.iter().position(|source| {
+            matches!(source, ColumnSource::Input(idx)
+                if self.reader_format.columns[*idx].name == input_column)
+        });
+    }
+
+    /// Output columns and values of a change, which holds values of some input columns only.
+    /// Values are converted to types of output columns and constants are added.
+    pub fn apply_change(
+        &self,
+        columns: &[String],
+        row: &Row,
+    ) -> anyhow::Result<(Vec<Column>, Row)> {
+        let mut output_columns = Vec::with_capacity(self.layout.len());
+        let mut values = Vec::with_capacity(self.layout.len());
+        for (source, column) in std::iter::zip(&self.layout, &self.writer_format.columns) {
+            let value = match source {
+                ColumnSource::Input(idx) => {
+                    let name = &self.reader_format.columns[*idx].name;
+                    let Some(position) = columns.iter().position(|c| c == name) else {
+                        continue;
+                    };
+                    convert_value(row[position].clone(), &column.column_type)
+                        .with_context(|| format!("Invalid value of column {name}"))?
+                }
+                ColumnSource::Constant(value) => value.clone(),
+            };
+            output_columns.push(column.clone());
+            values.push(value);
+        }
+        return Ok((output_columns, values));
+    }
+
+    pub fn apply(&self, row: Row) -> Row {
+        // Input columns are read in order of output, so only constants need to be added
+        if self
+            .layout
+            .iter()
+            .all(|source| matches!(source, ColumnSource::Input(_)))
+        {
+            return row;
+        }
+        let mut values = row.into_iter();
+        return self
+            .layout
+            .iter()
+            .map(|source| match source {
+                ColumnSource::Input(_) => values.next().expect("Row is shorter than mapping"),
+                ColumnSource::Constant(value) => value.clone(),
+            })
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column(name: &str, column_type: ColumnType) -> Column {
+        return Column {
+            name: name.to_string(),
+            column_type,
+            nullable: true,
+        };
+    }
+
+    fn table(columns: Vec<Column>) -> TableInfo {
+        return TableInfo {
+            name: "test".to_string(),
+            num_rows: None,
+            columns,
+            primary_key: Vec::new(),
+        };
+    }
+
+    #[test]
+    fn test_identity_mapping() {
+        let reader_info = table(vec![
+            column("b", ColumnType::I32),
+            column("a", ColumnType::String),
+        ]);
+        let writer_info = table(vec![
+            column("a", ColumnType::String),
+            column("b", ColumnType::I64),
+        ]);
+        let mapping =
+            ColumnMapping::new(&reader_info, &writer_info, &TableConfig::default()).unwrap();
+        assert_eq!(mapping.reader_format.column_names(), vec!["a", "b"]);
+        assert_eq!(
+            mapping.reader_format.columns[1].column_type,
+            ColumnType::I64
+        );
+        assert_eq!(mapping.writer_format, writer_info);
+        let row = vec![Value::Null, Value::I64(1)];
+        assert_eq!(mapping.apply(row.clone()), row);
+    }
+
+    #[test]
+    fn test_rename_drop_and_constants() {
+        let reader_info = table(vec![
+            column("id", ColumnType::I64),
+            column("old_name", ColumnType::String),
+            column("dropped", ColumnType::Bool),
+        ]);
+        let writer_info = table(vec![
+            column("id", ColumnType::I64),
+            column("source", ColumnType::String),
+            column("new_name", ColumnType::String),
+            column("created", ColumnType::Timestamp),
+        ]);
+        let config = TableConfig {
+            columns: HashMap::from([
+                ("old_name".to_string(), Some("new_name".to_string())),
+                ("dropped".to_string(), None),
+            ]),
+            constants: HashMap::from([("source".to_string(), Some("legacy".to_string()))]),
+            defaults: vec!["created".to_string()],
+            ..Default::default()
+        };
+        let mapping = ColumnMapping::new(&reader_info, &writer_info, &config).unwrap();
+        assert_eq!(mapping.reader_format.column_names(), vec!["id", "old_name"]);
+        assert_eq!(
+            mapping.writer_format.column_names(),
+            vec!["id", "source", "new_name"]
+        );
+        assert_eq!(mapping.output_position("old_name"), Some(2));
+        assert_eq!(
+            mapping.apply(vec![Value::I64(1), Value::String("name".to_string())]),
+            vec![
+                Value::I64(1),
+                Value::String("legacy".to_string()),
+                Value::String("name".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_incompatible_mapping() {
+        let reader_info = table(vec![column("a", ColumnType::I64)]);
+        let writer_info = table(vec![column("b", ColumnType::I64)]);
+        let result = ColumnMapping::new(&reader_info, &writer_info, &TableConfig::default());
+        assert!(result.is_err());
+
+        let config = TableConfig {
+            columns: HashMap::from([("a".to_string(), Some("b".to_string()))]),
+            ..Default::default()
+        };
+        assert!(ColumnMapping::new(&reader_info, &writer_info, &config).is_ok());
+
+        let writer_info = table(vec![column("b", ColumnType::I32)]);
+        assert!(ColumnMapping::new(&reader_info, &writer_info, &config).is_err());
+    }
+
+    #[test]
+    fn test_array_mapping() {
+        let array = ColumnType::Array(Box::new(ColumnType::I32));
+        let reader_info = table(vec![column("a", array.clone())]);
+        let wider = ColumnType::Array(Box::new(ColumnType::I64));
+        let writer_info = table(vec![column("a", wider)]);
+        assert!(ColumnMapping::new(&reader_info, &writer_info, &TableConfig::default()).is_ok());
+
+        let writer_info = table(vec![column("a", ColumnType::Json)]);
+        assert!(ColumnMapping::new(&reader_info, &writer_info, &TableConfig::default()).is_err());
+        let config = TableConfig {
+            arrays_as_json: true,
+            ..Default::default()
+        };
+        assert!(ColumnMapping::new(&reader_info, &writer_info, &config).is_ok());
+    }
+}
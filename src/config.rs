@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::Context;
+use serde::Deserialize;
+
+use crate::args::Args;
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    pub tables: HashMap<String, TableConfig>,
+    // Array columns of all tables may be written to JSON columns
+    #[serde(default)]
+    pub arrays_as_json: bool,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TableConfig {
+    // SQL condition for rows of the input table
+    #[serde(rename = "where")]
+    pub filter: Option<String>,
+    // Increasing column, only rows with values larger than moved by the previous run are read
+    pub incremental: Option<String>,
+    // Output names of input columns, null drops the column
+    #[serde(default)]
+    pub columns: HashMap<String, Option<String>>,
+    // Output columns filled with a constant, written in the text format of CSV files
+    #[serde(default)]
+    pub constants: HashMap<String, Option<String>>,
+    // Output columns filled by the database default
+    #[serde(default)]
+    pub defaults: Vec<String>,
+    // Array columns may be written to JSON columns as JSON arrays
+    #[serde(default)]
+    pub arrays_as_json: bool,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let file = std::fs::File::open(path)
+            .with_context(|| format!("Failed to open config file {path:?}"))?;
+        return serde_json::from_reader(file)
+            .with_context(|| format!("Failed to parse config file {path:?}"));
+    }
+
+    // Config file with command line options on top
+    pub fn from_args(args: &Args) -> anyhow::Result<Self> {
+        let mut config = match &args.config {
+            Some(path) => Self::load(path)?,
+            None => Self::default(),
+        };
+        for filter in &args.filter {
+            let (table, condition) = filter
+                .split_once('=')
+                .with_context(|| format!("Expected TABLE=CONDITION, got \"{filter}\""))?;
+            config
+                .tables
+                .entry(table.trim().to_string())
+                .or_default()
+                .filter = Some(condition.to_string());
+        }
+        for incremental in &args.incremental {
+            let (table, column) = incremental
+                .split_once('=')
+                .with_context(|| format!("Expected TABLE=COLUMN, got \"{incremental}\""))?;
+            config
+                .tables
+                .entry(table.trim().to_string())
+                .or_default()
+                .incremental = Some(column.trim().to_string());
+        }
+        config.arrays_as_json |= args.arrays_as_json;
+        return Ok(config);
+    }
+
+    pub fn table(&self, table: &str) -> TableConfig {
+        let mut config = self.tables.get(table).cloned().unwrap_or_default();
+        config.arrays_as_json |= self.arrays_as_json;
+        return config;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::uri::URI;
+
+    #[test]
+    fn test_config_from_args() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let path = tmp_dir.path().join("config.json");
+        std::fs::write(
+            &path,
+            r#"{"tables": {"orders": {"where": "id > 1"}, "users": {"where": "id > 2"}}}"#,
+        )
+        .unwrap();
+        let mut args = Args::new(
+            URI::Sqlite("sqlite://in.db".to_string()),
+            URI::Sqlite("sqlite://out.db".to_string()),
+        );
+        args.config = Some(path);
+        args.filter = vec!["users=name = 'a=b'".to_string()];
+        args.incremental = vec!["orders=updated_at".to_string()];
+
+        let config = Config::from_args(&args).unwrap();
+        assert_eq!(config.table("orders").filter, Some("id > 1".to_string()));
+        assert_eq!(
+            config.table("users").filter,
+            Some("name = 'a=b'".to_string())
+        );
+        assert_eq!(
+            config.table("orders").incremental,
+            Some("updated_at".to_string())
+        );
+        assert_eq!(config.table("other"), TableConfig::default());
+
+        args.arrays_as_json = true;
+        let config = Config::from_args(&args).unwrap();
+        assert!(config.table("other").arrays_as_json);
+    }
+
+    #[test]
+    fn test_config_invalid_filter() {
+        let mut args = Args::new(
+            URI::Sqlite("sqlite://in.db".to_string()),
+            URI::Sqlite("sqlite://out.db".to_string()),
+        );
+        args.filter = vec!["id > 1".to_string()];
+        assert!(Config::from_args(&args).is_err());
+    }
+}
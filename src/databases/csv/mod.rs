@@ -0,0 +1,306 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use tracing::debug;
+
+use crate::databases::table::{Column, ColumnType, ReadFilter, Row, TableInfo, Value};
+use crate::databases::traits::{DBInfoProvider, DBReader, DBWriter};
+
+use super::traits::{ReaderIterator, WriterError};
+
+mod value;
+
+const CSV_EXTENSION: &str = "csv";
+const SCHEMA_SUFFIX: &str = ".schema.json";
+
+pub struct CsvDB {
+    location: Location,
+    writers: HashMap<String, csv::Writer<File>>,
+}
+
+enum Location {
+    File(PathBuf),
+    Directory(PathBuf),
+}
+
+impl CsvDB {
+    pub fn new(uri: &str) -> anyhow::Result<Self> {
+        let path = PathBuf::from(uri.replace("csv://", ""));
+        let location = if path.extension().is_some_and(|ext| ext == CSV_EXTENSION) {
+            Location::File(path)
+        } else {
+            Location::Directory(path)
+        };
+        debug!("Opened csv {uri}");
+        return Ok(Self {
+            location,
+            writers: HashMap::new(),
+        });
+    }
+
+    fn table_path(&self, table: &str) -> anyhow::Result<PathBuf> {
+        return match &self.location {
+            Location::File(path) => {
+                if path.file_stem().is_some_and(|stem| stem == table) {
+                    Ok(path.clone())
+                } else {
+                    Err(anyhow::anyhow!(
+                        "CSV file {path:?} can only contain table with the same name"
+                    ))
+                }
+            }
+            Location::Directory(path) => Ok(path.join(format!("{table}.{CSV_EXTENSION}"))),
+        };
+    }
+
+    fn schema_path(&self, table: &str) -> anyhow::Result<PathBuf> {
+        let path = self.table_path(table)?;
+        return Ok(path.with_file_name(format!("{table}{SCHEMA_SUFFIX}")));
+    }
+
+    fn open_reader(&self, table: &str) -> anyhow::Result<csv::Reader<File>> {
+        let path = self.table_path(table)?;
+        return csv::Reader::from_path(&path)
+            .with_context(|| format!("Failed to open csv file {path:?}"));
+    }
+
+    fn get_columns(&self, table: &str) -> anyhow::Result<Vec<Column>> {
+        let header = self
+            .open_reader(table)?
+            .headers()
+            .context("Failed to read csv header")?
+            .clone();
+        let schema_path = self.schema_path(table)?;
+        if !schema_path.exists() {
+            return Ok(header
+                .iter()
+                .map(|name| Column {
+                    name: name.to_string(),
+                    column_type: ColumnType::String,
+                    nullable: true,
+                })
+                .collect());
+        }
+        let file = File::open(&schema_path)
+            .with_context(|| format!("Failed to open schema file {schema_path:?}"))?;
+        let schema: Vec<Column> = serde_json::from_reader(file)
+            .with_context(|| format!("Failed to parse schema file {schema_path:?}"))?;
+        if schema.len() != header.len() {
+            return Err(anyhow::anyhow!(
+                "Columns of schema file {schema_path:?} don't match csv header"
+            ));
+        }
+        // Keep order of the csv header, writer relies on it
+        return header
+            .iter()
+            .map(|name| {
+                schema
+                    .iter()
+                    .find(|column| column.name == name)
+                    .cloned()
+                    .with_context(|| format!("Column {name} not found in {schema_path:?}"))
+            })
+            .collect();
+    }
+
+    fn get_num_rows(&self, table: &str) -> anyhow::Result<u64> {
+        let mut reader = self.open_reader(table)?;
+        let mut record = csv::StringRecord::new();
+        let mut num_rows = 0;
+        while reader
+            .read_record(&mut record)
+            .context("Failed to read csv record")?
+        {
+            num_rows += 1;
+        }
+        return Ok(num_rows);
+    }
+
+    fn get_writer(&mut self, table: &TableInfo) -> anyhow::Result<&mut csv::Writer<File>> {
+        if !self.writers.contains_key(&table.name) {
+            let header = self.get_columns(&table.name)?;
+            if !header
+                .iter()
+                .map(|c| &c.name)
+                .eq(table.columns.iter().map(|c| &c.name))
+            {
+                return Err(anyhow::anyhow!(
+                    "All columns of csv file should be written in order of the header"
+                ));
+            }
+            let path = self.table_path(&table.name)?;
+            let file = OpenOptions::new()
+                .append(true)
+                .open(&path)
+                .with_context(|| format!("Failed to open csv file {path:?}"))?;
+            let writer = csv::WriterBuilder::new()
+                .has_headers(false)
+                .from_writer(file);
+            self.writers.insert(table.name.clone(), writer);
+        }
+        return Ok(self.writers.get_mut(&table.name).unwrap());
+    }
+
+    fn write_batch_impl(&mut self, batch: &[Row], table: &TableInfo) -> anyhow::Result<()> {
+        let writer = self.get_writer(table)?;
+        let mut record = Vec::new();
+        for row in batch {
+            record.clear();
+            for value in row {
+                record.push(value.to_csv_field()?);
+            }
+            writer
+                .write_record(record.iter().map(|field| field.as_bytes()))
+                .context("Failed to write csv record")?;
+        }
+        writer.flush().context("Failed to flush csv file")?;
+        return Ok(());
+    }
+}
+
+fn is_table_file(path: &Path) -> bool {
+    return path.extension().is_some_and(|ext| ext == CSV_EXTENSION) && path.is_file();
+}
+
+impl DBInfoProvider for CsvDB {
+    fn get_table_info(&mut self, table: &str, no_count: bool) -> anyhow::Result<TableInfo> {
+        let mut num_rows = None;
+        if !no_count {
+            num_rows = Some(
+                self.get_num_rows(table)
+                    .context("Failed to get number of rows in the table")?,
+            );
+        }
+        let columns = self
+            .get_columns(table)
+            .context("Failed to get info about table columns")?;
+        return Ok(TableInfo {
+            name: table.to_string(),
+            num_rows,
+            columns,
+            primary_key: Vec::new(),
+        });
+    }
+
+    fn get_tables(&mut self) -> anyhow::Result<Vec<String>> {
+        let paths = match &self.location {
+            Location::File(path) => vec![path.clone()],
+            Location::Directory(path) if !path.exists() => Vec::new(),
+            Location::Directory(path) => std::fs::read_dir(path)
+                .with_context(|| format!("Failed to list directory {path:?}"))?
+                .map(|entry| entry.map(|e| e.path()))
+                .collect::<Result<Vec<_>, _>>()
+                .context("Failed to list directory")?,
+        };
+        return Ok(paths
+            .iter()
+            .filter(|path| is_table_file(path))
+            .filter_map(|path| path.file_stem())
+            .map(|stem| stem.to_string_lossy().to_string())
+            .collect());
+    }
+}
+
+struct CsvRowsIter {
+    target_format: TableInfo,
+    // Position of each target column in the csv record
+    positions: Vec<usize>,
+    records: csv::StringRecordsIntoIter<File>,
+}
+
+impl Iterator for CsvRowsIter {
+    type Item = anyhow::Result<Row>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        return match self.records.next() {
+            Some(Ok(record)) => {
+                let mut result: Row = Vec::with_capacity(self.target_format.columns.len());
+                for (column, position) in
+                    std::iter::zip(&self.target_format.columns, &self.positions)
+                {
+                    let field = match record.get(*position) {
+                        Some(field) => field,
+                        None => return Some(Err(anyhow::anyhow!("Missing field in csv record"))),
+                    };
+                    match Value::try_from((column, field)) {
+                        Ok(val) => result.push(val),
+                        Err(e) => {
+                            return Some(Err(e).with_context(|| {
+                                format!("Failed to parse value of column {}", column.name)
+                            }));
+                        }
+                    }
+                }
+                Some(Ok(result))
+            }
+            Some(Err(err)) => Some(Err(err).context("Error while reading data from csv")),
+            None => None,
+        };
+    }
+}
+
+impl DBReader for CsvDB {
+    fn read_iter(
+        &mut self,
+        target_format: TableInfo,
+        filter: ReadFilter,
+    ) -> anyhow::Result<ReaderIterator<'_>> {
+        if filter != ReadFilter::default() {
+            return Err(anyhow::anyhow!("Filtering rows is not supported for csv"));
+        }
+        let mut reader = self.open_reader(&target_format.name)?;
+        let header = reader.headers().context("Failed to read csv header")?;
+        let positions = target_format
+            .columns
+            .iter()
+            .map(|column| {
+                header
+                    .iter()
+                    .position(|name| name == column.name)
+                    .with_context(|| format!("Column {} not found in csv header", column.name))
+            })
+            .collect::<anyhow::Result<Vec<usize>>>()?;
+        return Ok(Box::new(CsvRowsIter {
+            target_format,
+            positions,
+            records: reader.into_records(),
+        }));
+    }
+}
+
+impl DBWriter for CsvDB {
+    fn write_batch(&mut self, batch: &[Row], table: &TableInfo) -> Result<(), WriterError> {
+        // Local files are not network dependent, assume that all errors are Unrecoverable
+        return self
+            .write_batch_impl(batch, table)
+            .map_err(WriterError::Unrecoverable);
+    }
+
+    fn create_table(&mut self, table: &TableInfo) -> anyhow::Result<()> {
+        let path = self.table_path(&table.name)?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory {parent:?}"))?;
+        }
+        let mut writer = csv::Writer::from_path(&path)
+            .with_context(|| format!("Failed to create csv file {path:?}"))?;
+        writer
+            .write_record(table.column_names())
+            .context("Failed to write csv header")?;
+        writer.flush().context("Failed to flush csv file")?;
+
+        let schema_path = self.schema_path(&table.name)?;
+        let schema = File::create(&schema_path)
+            .with_context(|| format!("Failed to create schema file {schema_path:?}"))?;
+        serde_json::to_writer_pretty(schema, &table.columns)
+            .context("Failed to write schema file")?;
+        debug!("Created csv table {path:?}");
+        return Ok(());
+    }
+
+    fn recover(&mut self) -> anyhow::Result<()> {
+        return Ok(());
+    }
+}
@@ -0,0 +1,208 @@
+use std::borrow::Cow;
+use std::fmt::Write;
+use std::str::FromStr;
+
+use anyhow::Context;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use rust_decimal::Decimal;
+
+use crate::databases::table::{Column, ColumnType, Value};
+
+const NULL: &str = "\\N";
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.f";
+const DATE_FORMAT: &str = "%Y-%m-%d";
+const TIME_FORMAT: &str = "%H:%M:%S%.f";
+
+// Strings looking like NULL marker (\N, \\N, ...) get one extra leading backslash
+fn is_escaped_null(field: &str) -> bool {
+    return field.len() >= NULL.len()
+        && field.ends_with('N')
+        && field[..field.len() - 1].chars().all(|c| c == '\\');
+}
+
+fn decode_hex(field: &str) -> anyhow::Result<Vec<u8>> {
+    let hex = field
+        .strip_prefix("\\x")
+        .context("Bytes value should start with \\x")?;
+    if hex.len() % 2 != 0 {
+        return Err(anyhow::anyhow!("Odd number of hex digits in bytes value"));
+    }
+    return (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).context("Invalid hex digit"))
+        .collect();
+}
+
+impl TryFrom<(&Column, &str)> for Value {
+    type Error = anyhow::Error;
+
+    fn try_from(value: (&Column, &str)) -> Result<Self, Self::Error> {
+        let (column, field) = value;
+        if field == NULL {
+            return Ok(Value::Null);
+        }
+        let parsed = match column.column_type {
+            ColumnType::I64 => Value::I64(field.parse()?),
+            ColumnType::I32 => Value::I32(field.parse()?),
+            ColumnType::I16 => Value::I16(field.parse()?),
+            ColumnType::F64 => Value::F64(field.parse()?),
+            ColumnType::F32 => Value::F32(field.parse()?),
+            ColumnType::Decimal => Value::Decimal(Decimal::from_str(field)?),
+            ColumnType::Bool => match field {
+                "true" | "1" => Value::Bool(true),
+                "false" | "0" => Value::Bool(false),
+                _ => return Err(anyhow::anyhow!("Invalid bool value {field}")),
+            },
+            ColumnType::String if is_escaped_null(field) => Value::String(field[1..].to_string()),
+            ColumnType::String => Value::String(field.to_string()),
+            ColumnType::Bytes => Value::Bytes(bytes::Bytes::from(decode_hex(field)?)),
+            ColumnType::Timestamptz => {
+                Value::Timestamptz(DateTime::parse_from_rfc3339(field)?.with_timezone(&Utc))
+            }
+            ColumnType::Timestamp => {
+                Value::Timestamp(NaiveDateTime::parse_from_str(field, TIMESTAMP_FORMAT)?)
+            }
+            ColumnType::Date => Value::Date(NaiveDate::parse_from_str(field, DATE_FORMAT)?),
+            ColumnType::Time => Value::Time(NaiveTime::parse_from_str(field, TIME_FORMAT)?),
+            ColumnType::Json => Value::Json(serde_json::from_str(field)?),
+            ColumnType::Uuid => Value::Uuid(uuid::Uuid::parse_str(field)?),
+            ColumnType::Array(ref item_type) => {
+                let item_column = Column {
+                    name: column.name.clone(),
+                    column_type: *item_type.clone(),
+                    nullable: true,
+                };
+                Value::array_from_json(&item_column, serde_json::from_str(field)?)?
+            }
+        };
+        return Ok(parsed);
+    }
+}
+
+impl Value {
+    // Reverse of `array_to_json`
+    fn array_from_json(item_column: &Column, json: serde_json::Value) -> anyhow::Result<Value> {
+        let serde_json::Value::Array(items) = json else {
+            return Err(anyhow::anyhow!("JSON array expected, got {json}"));
+        };
+        let items = items
+            .into_iter()
+            .map(|item| {
+                return match (&item_column.column_type, item) {
+                    (_, serde_json::Value::Null) => Ok(Value::Null),
+                    (ColumnType::Json, item) => Ok(Value::Json(item)),
+                    (ColumnType::String, serde_json::Value::String(item)) => {
+                        Ok(Value::String(item))
+                    }
+                    (ColumnType::Array(item_type), item) => {
+                        let item_column = Column {
+                            column_type: *item_type.clone(),
+                            ..item_column.clone()
+                        };
+                        Value::array_from_json(&item_column, item)
+                    }
+                    (_, serde_json::Value::String(item)) => {
+                        Value::try_from((item_column, item.as_str()))
+                    }
+                    (_, item) => Value::try_from((item_column, item.to_string().as_str())),
+                };
+            })
+            .collect::<anyhow::Result<Vec<Value>>>()?;
+        return Ok(Value::Array(items));
+    }
+
+    pub(crate) fn to_csv_field(&self) -> anyhow::Result<Cow<'_, str>> {
+        let field = match self {
+            Value::Null => Cow::Borrowed(NULL),
+            Value::I64(val) => Cow::Owned(val.to_string()),
+            Value::I32(val) => Cow::Owned(val.to_string()),
+            Value::I16(val) => Cow::Owned(val.to_string()),
+            Value::F64(val) => Cow::Owned(val.to_string()),
+            Value::F32(val) => Cow::Owned(val.to_string()),
+            Value::Decimal(val) => Cow::Owned(val.to_string()),
+            Value::Bool(val) => Cow::Owned(val.to_string()),
+            Value::String(val) if is_escaped_null(val) => Cow::Owned(format!("\\{val}")),
+            Value::String(val) => Cow::Borrowed(val.as_str()),
+            Value::Bytes(val) => {
+                let mut hex = String::with_capacity(2 + val.len() * 2);
+                hex.push_str("\\x");
+                for byte in val.iter() {
+                    write!(hex, "{byte:02x}")?;
+                }
+                Cow::Owned(hex)
+            }
+            Value::Timestamptz(val) => Cow::Owned(val.to_rfc3339()),
+            Value::Timestamp(val) => Cow::Owned(val.format(TIMESTAMP_FORMAT).to_string()),
+            Value::Date(val) => Cow::Owned(val.format(DATE_FORMAT).to_string()),
+            Value::Time(val) => Cow::Owned(val.format(TIME_FORMAT).to_string()),
+            Value::Json(val) => {
+                Cow::Owned(serde_json::to_string(val).context("Failed to serialize json")?)
+            }
+            Value::Uuid(val) => Cow::Owned(val.hyphenated().to_string()),
+            Value::Array(items) => Cow::Owned(Value::array_to_json(items)?.to_string()),
+        };
+        return Ok(field);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(ColumnType::I64, Value::I64(i64::MIN))]
+    #[case(ColumnType::I32, Value::I32(i32::MAX))]
+    #[case(ColumnType::I16, Value::I16(-1))]
+    #[case(ColumnType::F64, Value::F64(0.1))]
+    #[case(ColumnType::F32, Value::F32(123.12345))]
+    #[case(ColumnType::Decimal, Value::Decimal(Decimal::from_str("123.12345678").unwrap()))]
+    #[case(ColumnType::Bool, Value::Bool(true))]
+    #[case(ColumnType::String, Value::String("line\nbreak, \"quoted\"".to_string()))]
+    #[case(ColumnType::String, Value::String(String::new()))]
+    #[case(ColumnType::String, Value::String("\\N".to_string()))]
+    #[case(ColumnType::String, Value::String("\\\\N".to_string()))]
+    #[case(
+        ColumnType::Bytes,
+        Value::Bytes(bytes::Bytes::from_static(b"\x00\xfftest"))
+    )]
+    #[case(
+        ColumnType::Timestamptz,
+        Value::Timestamptz(DateTime::from_timestamp_micros(1_098_181_434_123_456).unwrap())
+    )]
+    #[case(
+        ColumnType::Timestamp,
+        Value::Timestamp(DateTime::from_timestamp_micros(1_098_181_434_000_000).unwrap().naive_utc())
+    )]
+    #[case(ColumnType::Date, Value::Date(NaiveDate::from_ymd_opt(2004, 10, 19).unwrap()))]
+    #[case(ColumnType::Time, Value::Time(NaiveTime::from_hms_micro_opt(10, 23, 54, 1).unwrap()))]
+    #[case(ColumnType::Json, Value::Json(serde_json::json!({"test": [1, null]})))]
+    #[case(
+        ColumnType::Uuid,
+        Value::Uuid(uuid::Uuid::from_u128(0x67e5504410b1426f9247bb680e5fe0c8))
+    )]
+    #[case(ColumnType::Uuid, Value::Null)]
+    #[case(
+        ColumnType::Array(Box::new(ColumnType::I64)),
+        Value::Array(vec![Value::I64(1), Value::Null])
+    )]
+    #[case(
+        ColumnType::Array(Box::new(ColumnType::String)),
+        Value::Array(vec![Value::String("\\N".to_string()), Value::String("1".to_string())])
+    )]
+    #[case(
+        ColumnType::Array(Box::new(ColumnType::Date)),
+        Value::Array(vec![Value::Date(NaiveDate::from_ymd_opt(2004, 10, 19).unwrap())])
+    )]
+    fn test_csv_field_roundtrip(#[case] column_type: ColumnType, #[case] value: Value) {
+        let column = Column {
+            name: "test".to_string(),
+            column_type,
+            nullable: true,
+        };
+        let field = value.to_csv_field().unwrap();
+        let parsed = Value::try_from((&column, field.as_ref())).unwrap();
+        assert_eq!(value, parsed);
+    }
+}
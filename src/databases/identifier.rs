@@ -0,0 +1,56 @@
+use super::table::{TableInfo, split_table_name};
+
+/// Quoting of table and column names in queries, names are used verbatim otherwise.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IdentifierQuote(char);
+
+impl IdentifierQuote {
+    // PostgreSQL and SQLite
+    pub const ANSI: IdentifierQuote = IdentifierQuote('"');
+    pub const MYSQL: IdentifierQuote = IdentifierQuote('`');
+
+    pub fn quote(&self, name: &str) -> String {
+        let quote = self.0;
+        // Quote inside a name is escaped by doubling it
+        let escaped = name.replace(quote, &format!("{quote}{quote}"));
+        return format!("{quote}{escaped}{quote}");
+    }
+
+    // Qualified names are quoted part by part
+    pub fn quote_table(&self, table: &str) -> String {
+        return match split_table_name(table) {
+            (Some(schema), name) => format!("{}.{}", self.quote(schema), self.quote(name)),
+            (None, name) => self.quote(name),
+        };
+    }
+
+    // Comma separated list of quoted columns of the table
+    pub fn quote_columns(&self, table: &TableInfo) -> String {
+        return table
+            .columns
+            .iter()
+            .map(|column| self.quote(&column.name))
+            .collect::<Vec<_>>()
+            .join(", ");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(IdentifierQuote::ANSI.quote("order"), "\"order\"");
+        assert_eq!(
+            IdentifierQuote::ANSI.quote("My \"Name\""),
+            "\"My \"\"Name\"\"\""
+        );
+        assert_eq!(IdentifierQuote::MYSQL.quote("user`s"), "`user``s`");
+        assert_eq!(
+            IdentifierQuote::ANSI.quote_table("Sales.Order Items"),
+            "\"Sales\".\"Order Items\""
+        );
+        assert_eq!(IdentifierQuote::MYSQL.quote_table("order"), "`order`");
+    }
+}
@@ -1,5 +1,8 @@
+pub mod csv;
+pub mod identifier;
 pub mod mysql;
 pub mod postgres;
 pub mod sqlite;
 pub mod table;
 pub mod traits;
+pub mod write_mode;
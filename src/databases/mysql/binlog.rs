@@ -0,0 +1,306 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use mysql::binlog::events::{EventData, RowsEventData, TableMapEvent};
+use mysql::binlog::row::BinlogRow;
+use mysql::prelude::Queryable;
+use mysql::{BinlogDumpFlags, BinlogRequest, Conn, Opts};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use super::{MysqlDB, MysqlTypeOptions};
+use crate::checkpoint;
+use crate::databases::table::{Change, ChangeKind, Row, TableInfo, Value};
+use crate::databases::traits::{ChangeSource, DBInfoProvider};
+
+// Entry of the state file
+const STATE_KEY: &str = "binlog";
+// Replicas of a server need distinct ids, so ids of concurrent runs differ by process id
+const SERVER_ID_BASE: u32 = 3_000_000_000;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BinlogPosition {
+    pub file: String,
+    pub position: u64,
+}
+
+/// Row based binlog, read from a position taken before tables are moved.
+/// Position is saved only after changes are applied, so stopped runs continue from it.
+pub struct BinlogReader {
+    opts: Opts,
+    db: MysqlDB,
+    server_id: u32,
+    current_schema: Option<String>,
+    state_file: Option<PathBuf>,
+    position: BinlogPosition,
+    // Position after the last polled transaction
+    polled: Option<BinlogPosition>,
+    tables: HashMap<String, TableInfo>,
+}
+
+impl BinlogReader {
+    /// Opens the binlog at the position saved in the state file or at the current end.
+    /// Returns true for a saved position.
+    pub fn open(
+        uri: &str,
+        type_options: MysqlTypeOptions,
+        state_file: Option<&Path>,
+    ) -> anyhow::Result<(Self, bool)> {
+        let mut db = MysqlDB::new(uri, type_options)?;
+        let format: Option<String> = db
+            .connection
+            .query_first("SELECT @@binlog_format")
+            .context("Failed to query binlog format")?;
+        if format.as_deref() != Some("ROW") {
+            return Err(anyhow::anyhow!(
+                "Row based binlog is required, binlog format is {}",
+                format.unwrap_or_default()
+            ));
+        }
+        let saved = match state_file {
+            Some(path) => checkpoint::load_entries::<BinlogPosition>(path)?.remove(STATE_KEY),
+            None => None,
+        };
+        let resumed = saved.is_some();
+        let position = match saved {
+            Some(position) => position,
+            None => current_position(&mut db.connection)?,
+        };
+        debug!("Binlog is read from {position:?}");
+        let current_schema = db
+            .connection
+            .query_first("SELECT database()")
+            .context("Failed to query current database")?
+            .flatten();
+        let reader = Self {
+            opts: Opts::from_url(uri)?,
+            db,
+            server_id: SERVER_ID_BASE.wrapping_add(std::process::id()),
+            current_schema,
+            state_file: state_file.map(Path::to_path_buf),
+            position,
+            polled: None,
+            tables: HashMap::new(),
+        };
+        return Ok((reader, resumed));
+    }
+
+    // Columns are read from the database, as binlog keeps only their types
+    fn table_info(&mut self, table: &str, num_columns: usize) -> anyhow::Result<&TableInfo> {
+        let outdated = self
+            .tables
+            .get(table)
+            .is_none_or(|info| info.columns.len() != num_columns);
+        if outdated {
+            let info = self
+                .db
+                .get_table_info(table, true)
+                .with_context(|| format!("Unable to get information about table \"{table}\""))?;
+            if info.columns.len() != num_columns {
+                return Err(anyhow::anyhow!(
+                    "Columns of table \"{table}\" were changed after the binlog position"
+                ));
+            }
+            self.tables.insert(table.to_string(), info);
+        }
+        return Ok(&self.tables[table]);
+    }
+
+    fn decode(
+        &mut self,
+        rows: &RowsEventData,
+        table_map: &TableMapEvent,
+        changes: &mut Vec<Change>,
+    ) -> anyhow::Result<()> {
+        let database = table_map.database_name();
+        let table = if self.current_schema.as_deref() == Some(database.as_ref()) {
+            table_map.table_name().to_string()
+        } else {
+            format!("{database}.{}", table_map.table_name())
+        };
+        let info = self.table_info(&table, table_map.columns_count() as usize)?;
+        // Images keep only columns set in their bitmaps, e.g. with a minimal row image
+        let before_columns: Vec<usize> = rows
+            .columns_before_image()
+            .map(|image| image.iter_ones().collect())
+            .unwrap_or_default();
+        let after_columns: Vec<usize> = rows
+            .columns_after_image()
+            .map(|image| image.iter_ones().collect())
+            .unwrap_or_default();
+        let change = |kind, (columns, row)| Change {
+            table: table.clone(),
+            kind,
+            columns,
+            row,
+        };
+        for images in rows.rows(table_map) {
+            let (before, after) = images
+                .with_context(|| format!("Failed to parse binlog rows of table \"{table}\""))?;
+            let before = before
+                .map(|row| decode_row(info, &before_columns, row))
+                .transpose()?;
+            let after = after
+                .map(|row| decode_row(info, &after_columns, row))
+                .transpose()?;
+            match (before, after) {
+                (Some(before), Some(mut after)) => {
+                    fill_key(info, &before, &mut after);
+                    // Row with a changed key is moved by deleting the old key
+                    if key_changed(info, &before, &after) {
+                        // Columns missing in the image would be lost by the moved row
+                        if after.0.len() != info.columns.len() {
+                            return Err(anyhow::anyhow!(
+                                "Key of table \"{table}\" was changed in a partial row image, \
+                                binlog_row_image=FULL is required"
+                            ));
+                        }
+                        changes.push(change(ChangeKind::Delete, before));
+                        changes.push(change(ChangeKind::Insert, after));
+                    } else {
+                        changes.push(change(ChangeKind::Update, after));
+                    }
+                }
+                (None, Some(after)) => changes.push(change(ChangeKind::Insert, after)),
+                (Some(before), None) => changes.push(change(ChangeKind::Delete, before)),
+                (None, None) => {}
+            }
+        }
+        return Ok(());
+    }
+}
+
+impl ChangeSource for BinlogReader {
+    fn poll(&mut self, limit: usize) -> anyhow::Result<Vec<Change>> {
+        let mut position = self.position.clone();
+        // Server ends the stream at the end of the binlog instead of waiting for new events
+        let request = BinlogRequest::new(self.server_id)
+            .with_filename(position.file.as_bytes())
+            .with_pos(position.position)
+            .with_flags(BinlogDumpFlags::BINLOG_DUMP_NON_BLOCK);
+        let mut stream = Conn::new(self.opts.clone())?
+            .get_binlog_stream(request)
+            .context("Failed to request binlog")?;
+        let mut changes = Vec::new();
+        // Changes of the current transaction, returned once it's committed
+        let mut pending = Vec::new();
+        while changes.len() < limit {
+            let Some(event) = stream.next() else {
+                break;
+            };
+            let event = event.context("Failed to read binlog")?;
+            let Some(data) = event.read_data().context("Failed to parse binlog event")? else {
+                continue;
+            };
+            match data {
+                EventData::RotateEvent(rotate) => {
+                    position = BinlogPosition {
+                        file: rotate.name().to_string(),
+                        position: rotate.position(),
+                    };
+                    continue;
+                }
+                EventData::RowsEvent(rows) => {
+                    let table_map = stream
+                        .get_tme(rows.table_id())
+                        .context("Table of binlog rows is unknown")?;
+                    self.decode(&rows, table_map, &mut pending)?;
+                    continue;
+                }
+                EventData::XidEvent(_) => {}
+                EventData::QueryEvent(query) if query.query() == "COMMIT" => {}
+                EventData::TransactionPayloadEvent(_) => {
+                    return Err(anyhow::anyhow!(
+                        "Compressed binlog transactions are not supported"
+                    ));
+                }
+                _ => continue,
+            }
+            changes.append(&mut pending);
+            position.position = u64::from(event.header().log_pos());
+        }
+        self.polled = Some(position);
+        return Ok(changes);
+    }
+
+    fn confirm(&mut self) -> anyhow::Result<()> {
+        if let Some(position) = self.polled.take() {
+            self.position = position;
+        }
+        if let Some(path) = &self.state_file {
+            checkpoint::store(path, STATE_KEY, self.position.clone())?;
+        }
+        return Ok(());
+    }
+}
+
+fn current_position(connection: &mut Conn) -> anyhow::Result<BinlogPosition> {
+    // Renamed in MySQL 8.4
+    let status: Option<mysql::Row> = match connection.query_first("SHOW BINARY LOG STATUS") {
+        Ok(status) => status,
+        Err(_) => connection
+            .query_first("SHOW MASTER STATUS")
+            .context("Failed to query binlog position")?,
+    };
+    let status = status.context("Binlog is disabled")?;
+    let file: String = status.get_opt(0).context("Value expected")??;
+    let position: u64 = status.get_opt(1).context("Value expected")??;
+    return Ok(BinlogPosition { file, position });
+}
+
+// Names and values of columns present in the row image, `image_columns` are their indexes
+fn decode_row(
+    info: &TableInfo,
+    image_columns: &[usize],
+    mut row: BinlogRow,
+) -> anyhow::Result<(Vec<String>, Row)> {
+    let mut columns = Vec::with_capacity(image_columns.len());
+    let mut values = Vec::with_capacity(image_columns.len());
+    for (position, &idx) in image_columns.iter().enumerate() {
+        let column = info
+            .columns
+            .get(idx)
+            .with_context(|| format!("Unknown column {idx} of table \"{}\"", info.name))?;
+        let Some(value) = row.take(position) else {
+            continue;
+        };
+        let value = Value::try_from((column, value)).with_context(|| {
+            format!(
+                "Failed to decode column {} of table \"{}\"",
+                column.name, info.name
+            )
+        })?;
+        columns.push(column.name.clone());
+        values.push(value);
+    }
+    return Ok((columns, values));
+}
+
+// After images of updates may lack unchanged columns, key is taken from the before image
+fn fill_key(info: &TableInfo, before: &(Vec<String>, Row), after: &mut (Vec<String>, Row)) {
+    let (before_columns, before_row) = before;
+    let (after_columns, after_row) = after;
+    for name in &info.primary_key {
+        if after_columns.contains(name) {
+            continue;
+        }
+        if let Some(idx) = before_columns.iter().position(|column| column == name) {
+            after_columns.push(name.clone());
+            after_row.push(before_row[idx].clone());
+        }
+    }
+}
+
+fn key_changed(info: &TableInfo, before: &(Vec<String>, Row), after: &(Vec<String>, Row)) -> bool {
+    let value = |(columns, row): &(Vec<String>, Row), name: &String| {
+        return columns
+            .iter()
+            .position(|column| column == name)
+            .map(|idx| row[idx].clone());
+    };
+    return info
+        .primary_key
+        .iter()
+        .any(|name| value(before, name) != value(after, name));
+}
@@ -1,25 +1,40 @@
 use std::collections::HashMap;
+use std::io::Write;
 
 use anyhow::Context;
 use itertools::Itertools;
 use mysql::prelude::Queryable;
-use mysql::{Conn, Opts, params};
-use tracing::debug;
+use mysql::{Conn, LocalInfileHandler, Opts, TxOpts, params};
+use tracing::{debug, warn};
 pub use value::MysqlTypeOptions;
 
-use crate::databases::table::{Row, Value};
+use crate::databases::table::{KeyRange, ReadFilter, Row, Value};
 use crate::databases::traits::{DBInfoProvider, DBReader};
 
-use super::table::{Column, ColumnType, TableInfo};
+use super::identifier::IdentifierQuote;
+use super::table::{Column, ColumnType, TableInfo, split_table_name};
 use super::traits::{DBWriter, ReaderIterator, WriterError};
+use super::write_mode::WriteMode;
 
+pub mod binlog;
 mod value;
 
+const QUOTE: IdentifierQuote = IdentifierQuote::MYSQL;
+
 pub struct MysqlDB {
     uri: String,
     connection: Conn,
     is_mariadb: bool,
+    // Row alias of inserted values replaced VALUES() in MySQL 8.0.19, MariaDB has only VALUES()
+    has_row_alias: bool,
     type_options: MysqlTypeOptions,
+    write_mode: WriteMode,
+    fast_load: bool,
+    load_data: bool,
+    // Whether the server allows LOAD DATA LOCAL, checked on the first load
+    local_infile: Option<bool>,
+    // Rows of a page read by a single query, all rows are read by one query without it
+    fetch_size: Option<usize>,
     stmt_cache: HashMap<(String, usize, usize), mysql::Statement>,
 }
 
@@ -31,15 +46,33 @@ impl MysqlDB {
             .query_first("SELECT VERSION()")
             .context("Unable to fetch database version")?
             .unwrap();
+        let is_mariadb = version.contains("MariaDB");
+        let has_row_alias = !is_mariadb && connection.server_version() >= (8, 0, 19);
         return Ok(Self {
             uri: uri.to_string(),
             connection,
-            is_mariadb: version.contains("MariaDB"),
+            is_mariadb,
+            has_row_alias,
             type_options,
+            write_mode: WriteMode::default(),
+            fast_load: false,
+            load_data: false,
+            local_infile: None,
+            fetch_size: None,
             stmt_cache: HashMap::new(),
         });
     }
 
+    /// Write batches with LOAD DATA LOCAL INFILE instead of INSERT, if the server allows it
+    pub fn enable_load_data(&mut self) {
+        self.load_data = true;
+    }
+
+    /// Read tables in pages of `fetch_size` rows ordered by a single column key
+    pub fn set_fetch_size(&mut self, fetch_size: usize) {
+        self.fetch_size = Some(fetch_size);
+    }
+
     fn connect(uri: &str) -> Result<Conn, anyhow::Error> {
         let opts = Opts::from_url(uri)?;
         let mut conn = Conn::new(opts)?;
@@ -48,28 +81,154 @@ impl MysqlDB {
         return Ok(conn);
     }
 
-    fn get_num_rows(&mut self, table: &str) -> anyhow::Result<u64> {
-        let count_query = format!("SELECT count(1) FROM {table}");
+    // Session settings are lost on reconnect, so they are applied to each new connection
+    fn apply_fast_load(&mut self) -> anyhow::Result<()> {
+        if self.fast_load {
+            self.connection
+                .query_drop("SET FOREIGN_KEY_CHECKS = 0, UNIQUE_CHECKS = 0")
+                .context("Failed to disable foreign key and unique checks")?;
+        }
+        return Ok(());
+    }
+
+    // Statements to drop and to create non-unique indexes, except ones of foreign keys
+    fn get_indexes(&mut self, table: &str) -> anyhow::Result<Vec<(String, String)>> {
+        let (schema, table_name) = split_table_name(table);
+        let rows: Vec<(String, Option<String>, Option<u64>, String)> = self
+            .connection
+            .exec(
+                r"SELECT INDEX_NAME, COLUMN_NAME, SUB_PART, INDEX_TYPE
+                FROM INFORMATION_SCHEMA.STATISTICS
+                WHERE TABLE_NAME = :table AND TABLE_SCHEMA = coalesce(:schema, database())
+                    AND NON_UNIQUE = 1 AND INDEX_NAME NOT IN (
+                        SELECT CONSTRAINT_NAME FROM INFORMATION_SCHEMA.TABLE_CONSTRAINTS
+                        WHERE TABLE_NAME = :table AND TABLE_SCHEMA = coalesce(:schema, database())
+                            AND CONSTRAINT_TYPE = 'FOREIGN KEY')
+                ORDER BY INDEX_NAME, SEQ_IN_INDEX",
+                params! {"table" => table_name, schema},
+            )
+            .context("Failed to query indexes of table")?;
+        let mut indexes = Vec::new();
+        for (name, index_rows) in &rows.into_iter().chunk_by(|row| row.0.clone()) {
+            let mut index_type = String::new();
+            let mut columns = Vec::new();
+            for (_, column, sub_part, kind) in index_rows {
+                // Functional indexes have no column, they are kept
+                let Some(column) = column else {
+                    columns.clear();
+                    break;
+                };
+                let sub_part = sub_part.map(|len| format!("({len})")).unwrap_or_default();
+                columns.push(format!("{}{sub_part}", QUOTE.quote(&column)));
+                if kind == "FULLTEXT" || kind == "SPATIAL" {
+                    index_type = format!("{kind} ");
+                }
+            }
+            if columns.is_empty() {
+                continue;
+            }
+            let table = QUOTE.quote_table(table);
+            let name = QUOTE.quote(&name);
+            indexes.push((
+                format!("DROP INDEX {name} ON {table}"),
+                format!(
+                    "CREATE {index_type}INDEX {name} ON {table} ({})",
+                    columns.join(", ")
+                ),
+            ));
+        }
+        return Ok(indexes);
+    }
+
+    fn get_num_rows(&mut self, table: &str, condition: Option<&str>) -> anyhow::Result<u64> {
+        let mut count_query = format!("SELECT count(1) FROM {}", QUOTE.quote_table(table));
+        if let Some(condition) = condition {
+            count_query.push_str(&format!(" WHERE {condition}"));
+        }
         return self
             .connection
             .query_first(count_query)?
             .context("Unable to get count of rows for table");
     }
 
+    fn get_primary_key(&mut self, table: &str) -> anyhow::Result<Vec<String>> {
+        let (schema, table) = split_table_name(table);
+        return self
+            .connection
+            .exec(
+                r"SELECT COLUMN_NAME FROM INFORMATION_SCHEMA.KEY_COLUMN_USAGE
+                WHERE TABLE_NAME = :table AND TABLE_SCHEMA = coalesce(:schema, database()) AND CONSTRAINT_NAME = 'PRIMARY'
+                ORDER BY ORDINAL_POSITION",
+                params! {table, schema},
+            )
+            .context("Failed to query primary key of table");
+    }
+
+    fn query_tables(&mut self, schema: Option<&str>) -> anyhow::Result<Vec<String>> {
+        let rows: Vec<mysql::Row> = self.connection.exec(
+            "SELECT table_name FROM information_schema.tables WHERE table_schema = coalesce(:schema, database())",
+            params! {schema},
+        )?;
+        return rows
+            .iter()
+            .map(|row| {
+                let table: String = row
+                    .get_opt(0)
+                    .context("Value expected")?
+                    .context("Couldn't parse table name")?;
+                return Ok(match schema {
+                    Some(schema) => format!("{schema}.{table}"),
+                    None => table,
+                });
+            })
+            .collect::<anyhow::Result<Vec<String>>>();
+    }
+
+    fn on_duplicate_clause(&self, table: &TableInfo) -> String {
+        let update_columns = WriteMode::update_columns(table);
+        return match self.write_mode {
+            WriteMode::Insert => String::new(),
+            WriteMode::Upsert if !update_columns.is_empty() && self.has_row_alias => format!(
+                " AS new ON DUPLICATE KEY UPDATE {}",
+                update_columns
+                    .iter()
+                    .map(|name| format!("{0} = new.{0}", QUOTE.quote(name)))
+                    .join(", ")
+            ),
+            WriteMode::Upsert if !update_columns.is_empty() => format!(
+                " ON DUPLICATE KEY UPDATE {}",
+                update_columns
+                    .iter()
+                    .map(|name| format!("{0} = VALUES({0})", QUOTE.quote(name)))
+                    .join(", ")
+            ),
+            // No-op update, unlike INSERT IGNORE it doesn't hide other errors
+            WriteMode::Upsert | WriteMode::IgnoreDuplicates => {
+                let column = QUOTE.quote(&table.columns[0].name);
+                format!(" ON DUPLICATE KEY UPDATE {column} = {column}")
+            }
+        };
+    }
+
     fn get_stmt(
         &mut self,
-        table_name: &str,
+        table: &TableInfo,
         values_per_row: usize,
         rows: usize,
     ) -> anyhow::Result<mysql::Statement> {
-        let key = (table_name.to_owned(), values_per_row, rows);
+        let key = (table.name.to_owned(), values_per_row, rows);
         return match self.stmt_cache.get(&key) {
             Some(stmt) => Ok(stmt.to_owned()),
             None => {
                 let placeholder = generate_placeholders(values_per_row, rows);
+                let on_duplicate = self.on_duplicate_clause(table);
                 let stmt = self
                     .connection
-                    .prep(format!("INSERT INTO {table_name} VALUES {placeholder}"))
+                    .prep(format!(
+                        "INSERT INTO {} ({}) VALUES {placeholder}{on_duplicate}",
+                        QUOTE.quote_table(&table.name),
+                        QUOTE.quote_columns(table)
+                    ))
                     .context("Unable to prepare insert query")?;
                 self.stmt_cache.insert(key, stmt.clone());
                 Ok(stmt)
@@ -78,23 +237,121 @@ impl MysqlDB {
     }
 }
 
+impl MysqlDB {
+    fn is_local_infile_allowed(&mut self) -> anyhow::Result<bool> {
+        if let Some(allowed) = self.local_infile {
+            return Ok(allowed);
+        }
+        let allowed: bool = self
+            .connection
+            .query_first("SELECT @@local_infile")
+            .context("Failed to check whether local infile is allowed")?
+            .context("Value expected")?;
+        if !allowed {
+            warn!("Server disallows LOAD DATA LOCAL INFILE (local_infile), INSERT is used instead");
+        }
+        self.local_infile = Some(allowed);
+        return Ok(allowed);
+    }
+
+    // Batch is sent as a tab separated file, bytes are loaded as hex into variables
+    fn load_batch(&mut self, batch: &[Row], table: &TableInfo) -> Result<(), WriterError> {
+        let mut data = Vec::new();
+        for row in batch {
+            for (idx, value) in row.iter().enumerate() {
+                if idx > 0 {
+                    data.push(b'\t');
+                }
+                value.write_mysql_field(&mut data)?;
+            }
+            data.push(b'\n');
+        }
+        let mut columns = Vec::with_capacity(table.columns.len());
+        let mut unhex = Vec::new();
+        for (idx, column) in table.columns.iter().enumerate() {
+            let name = QUOTE.quote(&column.name);
+            if matches!(column.column_type, ColumnType::Bytes | ColumnType::Uuid) {
+                columns.push(format!("@field{idx}"));
+                unhex.push(format!("{name} = UNHEX(@field{idx})"));
+            } else {
+                columns.push(name);
+            }
+        }
+        let set = match unhex.is_empty() {
+            true => String::new(),
+            false => format!(" SET {}", unhex.join(", ")),
+        };
+        let ignore = match self.write_mode {
+            WriteMode::IgnoreDuplicates => "IGNORE ",
+            _ => "",
+        };
+        let query = format!(
+            "LOAD DATA LOCAL INFILE 'db_mover' {ignore}INTO TABLE {} CHARACTER SET utf8mb4 \
+            FIELDS TERMINATED BY '\\t' ESCAPED BY '\\\\' LINES TERMINATED BY '\\n' ({}){set}",
+            QUOTE.quote_table(&table.name),
+            columns.join(", ")
+        );
+        let mut data = Some(data);
+        self.connection
+            .set_local_infile_handler(Some(LocalInfileHandler::new(move |_, stream| {
+                if let Some(data) = data.take() {
+                    stream.write_all(&data)?;
+                }
+                return Ok(());
+            })));
+        let result = self.run_load(&query);
+        self.connection.set_local_infile_handler(None);
+        return result;
+    }
+
+    // Errors of rows are only warnings with LOCAL, such rows are skipped or truncated.
+    // Load is rolled back on warnings, except on duplicates if they are ignored.
+    fn run_load(&mut self, query: &str) -> Result<(), WriterError> {
+        let ignore_duplicates = self.write_mode == WriteMode::IgnoreDuplicates;
+        let mut transaction = self
+            .connection
+            .start_transaction(TxOpts::default())
+            .context("Failed to open transaction")?;
+        transaction
+            .query_drop(query)
+            .context("Unable to load values into mysql")?;
+        if transaction.warnings() > 0 {
+            let warnings: Vec<(String, u32, String)> = transaction
+                .query("SHOW WARNINGS")
+                .context("Failed to query warnings of load")?;
+            // Duplicate entry
+            let warning = warnings
+                .into_iter()
+                .find(|(_, code, _)| !(ignore_duplicates && *code == 1062));
+            if let Some((_, code, message)) = warning {
+                return Err(WriterError::Unrecoverable(anyhow::anyhow!(
+                    "Unable to load values into mysql: {message} ({code})"
+                )));
+            }
+        }
+        transaction.commit().context("Failed to commit")?;
+        return Ok(());
+    }
+}
+
 impl DBInfoProvider for MysqlDB {
     fn get_table_info(&mut self, table: &str, no_count: bool) -> anyhow::Result<TableInfo> {
         let mut num_rows = None;
         if !no_count {
             num_rows = Some(
-                self.get_num_rows(table)
+                self.get_num_rows(table, None)
                     .context("Failed to get number of rows in the table")?,
             );
         }
 
+        let (schema, table_name) = split_table_name(table);
         let info_rows: Vec<mysql::Row> = self.connection.exec(r"SELECT COLUMN_NAME, COLUMN_TYPE, IS_NULLABLE 
                                                                 FROM INFORMATION_SCHEMA.COLUMNS 
-                                                                WHERE table_name = :table AND TABLE_SCHEMA = database()
-                                                                ORDER BY ORDINAL_POSITION", params! {table})?;
+                                                                WHERE table_name = :table AND TABLE_SCHEMA = coalesce(:schema, database())
+                                                                ORDER BY ORDINAL_POSITION", params! {"table" => table_name, schema})?;
         let mut columns = Vec::with_capacity(info_rows.len());
         for row in info_rows {
-            let name = row
+            let name: String = row
                 .get_opt(0)
                 .context("Value expected")?
                 .context("Couldn't parse column name")?;
@@ -109,8 +366,8 @@ impl DBInfoProvider for MysqlDB {
             if column_type == "longtext" && self.is_mariadb {
                 let num_json_constraints: usize = self.connection.exec_first(
                     r"SELECT count(1) FROM INFORMATION_SCHEMA.check_constraints
-                    WHERE CONSTRAINT_SCHEMA = database() AND TABLE_NAME = :table AND CHECK_CLAUSE = :clause",
-                    params! {table, "clause" => format!("json_valid(`{name}`)")},
+                    WHERE CONSTRAINT_SCHEMA = coalesce(:schema, database()) AND TABLE_NAME = :table AND CHECK_CLAUSE = :clause",
+                    params! {"table" => table_name, schema, "clause" => format!("json_valid({})", QUOTE.quote(&name))},
                 ).context("Failed to check json constraint")?.unwrap();
                 if num_json_constraints > 0 {
                     column_type = String::from("json");
@@ -123,31 +380,71 @@ impl DBInfoProvider for MysqlDB {
             });
         }
 
+        let primary_key = self
+            .get_primary_key(table)
+            .context("Failed to get primary key of the table")?;
+
         return Ok(TableInfo {
             name: table.to_string(),
             num_rows,
             columns,
+            primary_key,
         });
     }
 
     fn get_tables(&mut self) -> anyhow::Result<Vec<String>> {
-        let rows: Vec<mysql::Row> = self.connection.query(
-            "SELECT table_name FROM information_schema.tables WHERE table_schema = database()",
-        )?;
-        return rows
-            .iter()
-            .map(|row| {
-                row.get_opt(0)
-                    .context("Value expected")?
-                    .context("Couldn't parse table name")
-            })
-            .collect::<anyhow::Result<Vec<String>>>();
+        return self.query_tables(None);
     }
+
+    fn get_referenced_tables(&mut self, table: &str) -> anyhow::Result<Vec<String>> {
+        // Tables of other databases are qualified, as in table lists
+        let (schema, table) = split_table_name(table);
+        return self
+            .connection
+            .exec(
+                r"SELECT DISTINCT IF(REFERENCED_TABLE_SCHEMA = database(), REFERENCED_TABLE_NAME,
+                    CONCAT(REFERENCED_TABLE_SCHEMA, '.', REFERENCED_TABLE_NAME))
+                FROM INFORMATION_SCHEMA.KEY_COLUMN_USAGE
+                WHERE TABLE_NAME = :table AND TABLE_SCHEMA = coalesce(:schema, database())
+                    AND REFERENCED_TABLE_NAME IS NOT NULL",
+                params! {table, schema},
+            )
+            .context("Failed to query foreign keys of table");
+    }
+
+    fn get_schema_tables(&mut self, schema: &str) -> anyhow::Result<Vec<String>> {
+        return self.query_tables(Some(schema));
+    }
+}
+
+fn parse_row(target_format: &TableInfo, row: mysql::Row) -> anyhow::Result<Row> {
+    let values = row.unwrap();
+    assert_eq!(values.len(), target_format.columns.len());
+    return std::iter::zip(&target_format.columns, values)
+        .map(|(column, value)| Value::try_from((column, value)))
+        .collect();
+}
+
+fn select_query(target_format: &TableInfo, filter: &ReadFilter) -> String {
+    return format!(
+        "SELECT {} FROM {}{}",
+        QUOTE.quote_columns(target_format),
+        QUOTE.quote_table(&target_format.name),
+        filter.to_sql(QUOTE, |_| "?".to_string())
+    );
+}
+
+fn filter_params(filter: &ReadFilter) -> anyhow::Result<Vec<mysql::Value>> {
+    return filter
+        .values()
+        .into_iter()
+        .map(mysql::Value::try_from)
+        .collect();
 }
 
 struct MysqlRowsIter<'a> {
     target_format: TableInfo,
-    rows: mysql::QueryResult<'a, 'a, 'a, mysql::Text>,
+    rows: mysql::QueryResult<'a, 'a, 'a, mysql::Binary>,
 }
 
 impl Iterator for MysqlRowsIter<'_> {
@@ -155,54 +452,211 @@ impl Iterator for MysqlRowsIter<'_> {
 
     fn next(&mut self) -> Option<Self::Item> {
         return match self.rows.next() {
-            Some(Ok(row)) => {
-                let mut result: Row = Vec::with_capacity(self.target_format.columns.len());
-                let values = row.unwrap();
-                assert_eq!(values.len(), self.target_format.columns.len());
-                for (column, value) in std::iter::zip(&self.target_format.columns, values) {
-                    match Value::try_from((column, value)) {
-                        Ok(val) => result.push(val),
-                        Err(e) => return Some(Err(e)),
-                    }
-                }
-                Some(Ok(result))
-            }
+            Some(Ok(row)) => Some(parse_row(&self.target_format, row)),
             Some(Err(err)) => Some(Err(err).context("Error while reading data from mysql")),
             None => None,
         };
     }
 }
 
-impl DBReader for MysqlDB {
-    fn read_iter(&mut self, target_format: TableInfo) -> anyhow::Result<ReaderIterator<'_>> {
+// Rows read by queries of `fetch_size` rows, each continues after the last key of the previous
+struct MysqlPagesIter<'a> {
+    connection: &'a mut Conn,
+    target_format: TableInfo,
+    filter: ReadFilter,
+    // Position of the key column in rows
+    key_idx: usize,
+    fetch_size: usize,
+    page: std::vec::IntoIter<mysql::Row>,
+    is_last_page: bool,
+}
+
+impl MysqlPagesIter<'_> {
+    fn fetch_page(&mut self) -> anyhow::Result<()> {
         let query = format!(
-            "SELECT {} FROM {}",
-            target_format.column_names().join(", "),
-            target_format.name
+            "{} LIMIT {}",
+            select_query(&self.target_format, &self.filter),
+            self.fetch_size
         );
+        let rows: Vec<mysql::Row> = self
+            .connection
+            .exec(query, filter_params(&self.filter)?)
+            .context("Failed to get data from mysql source")?;
+        self.is_last_page = rows.len() < self.fetch_size;
+        if let Some(last) = rows.last() {
+            let column = &self.target_format.columns[self.key_idx];
+            let key = last
+                .as_ref(self.key_idx)
+                .cloned()
+                .context("Value expected")?;
+            let key = Value::try_from((column, key))?;
+            if key == Value::Null {
+                return Err(anyhow::anyhow!(
+                    "Rows can't be read in pages, key column {} has NULL values",
+                    column.name
+                ));
+            }
+            if let Some(key_range) = &mut self.filter.key_range {
+                key_range.after = Some(key);
+            }
+        }
+        self.page = rows.into_iter();
+        return Ok(());
+    }
+}
+
+impl Iterator for MysqlPagesIter<'_> {
+    type Item = anyhow::Result<Row>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.page.len() == 0
+            && !self.is_last_page
+            && let Err(err) = self.fetch_page()
+        {
+            self.is_last_page = true;
+            return Some(Err(err));
+        }
+        return self
+            .page
+            .next()
+            .map(|row| parse_row(&self.target_format, row));
+    }
+}
+
+impl DBReader for MysqlDB {
+    fn opt_clone(&self) -> anyhow::Result<Box<dyn DBReader>> {
+        let mut reader = MysqlDB::new(&self.uri, self.type_options.clone())?;
+        reader.fetch_size = self.fetch_size;
+        return Ok(Box::new(reader));
+    }
+
+    fn start_snapshot(&mut self) -> anyhow::Result<()> {
+        self.connection
+            .query_drop("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ")
+            .context("Failed to set isolation level")?;
+        self.connection
+            .query_drop("START TRANSACTION WITH CONSISTENT SNAPSHOT")
+            .context("Failed to start snapshot")?;
+        return Ok(());
+    }
+
+    fn read_iter(
+        &mut self,
+        target_format: TableInfo,
+        filter: ReadFilter,
+    ) -> anyhow::Result<ReaderIterator<'_>> {
+        // Pages continue after the last key, so the key has to be unique
+        let key_idx = match target_format.primary_key.as_slice() {
+            [key]
+                if filter
+                    .key_range
+                    .as_ref()
+                    .is_none_or(|range| &range.column == key) =>
+            {
+                target_format
+                    .columns
+                    .iter()
+                    .position(|column| &column.name == key)
+            }
+            _ => None,
+        };
+        if let (Some(fetch_size), Some(key_idx)) = (self.fetch_size, key_idx) {
+            let mut filter = filter;
+            filter.key_range.get_or_insert_with(|| KeyRange {
+                column: target_format.columns[key_idx].name.clone(),
+                after: None,
+                up_to: None,
+            });
+            return Ok(Box::new(MysqlPagesIter {
+                connection: &mut self.connection,
+                target_format,
+                filter,
+                key_idx,
+                fetch_size: fetch_size.max(1),
+                page: Vec::new().into_iter(),
+                is_last_page: false,
+            }));
+        }
+        // Otherwise the whole table would be read by a single query
+        if self.fetch_size.is_some() {
+            return Err(anyhow::anyhow!(
+                "Table \"{}\" can't be read in pages, paged reads require a single column primary key",
+                target_format.name
+            ));
+        }
+        // Binary protocol of prepared statements keeps values in their native types
         let rows = self
             .connection
-            .query_iter(query)
+            .exec_iter(
+                select_query(&target_format, &filter),
+                filter_params(&filter)?,
+            )
             .context("Failed to get data from mysql source")?;
         return Ok(Box::new(MysqlRowsIter {
             target_format,
             rows,
         }));
     }
+
+    fn count_rows(&mut self, table: &str, condition: &str) -> anyhow::Result<u64> {
+        return self.get_num_rows(table, Some(condition));
+    }
+
+    fn get_key_bounds(
+        &mut self,
+        table: &str,
+        column: &Column,
+    ) -> anyhow::Result<Option<(Value, Value)>> {
+        let query = |order: &str| {
+            format!(
+                "SELECT {0} FROM {1} WHERE {0} IS NOT NULL ORDER BY {0} {order} LIMIT 1",
+                QUOTE.quote(&column.name),
+                QUOTE.quote_table(table)
+            )
+        };
+        let Some(min) = self
+            .connection
+            .query_first::<mysql::Value, _>(query("ASC"))
+            .context("Failed to query key bounds")?
+        else {
+            return Ok(None);
+        };
+        let max = self
+            .connection
+            .query_first::<mysql::Value, _>(query("DESC"))
+            .context("Failed to query key bounds")?
+            .context("Table became empty while querying key bounds")?;
+        return Ok(Some((
+            Value::try_from((column, min))?,
+            Value::try_from((column, max))?,
+        )));
+    }
 }
 
 impl DBWriter for MysqlDB {
     fn opt_clone(&self) -> anyhow::Result<Box<dyn DBWriter>> {
-        return MysqlDB::new(&self.uri, self.type_options.clone())
-            .map(|writer| Box::new(writer) as _);
+        let mut writer = MysqlDB::new(&self.uri, self.type_options.clone())?;
+        writer.write_mode = self.write_mode;
+        writer.fast_load = self.fast_load;
+        writer.load_data = self.load_data;
+        writer.apply_fast_load()?;
+        return Ok(Box::new(writer));
     }
 
     fn write_batch(&mut self, batch: &[Row], table: &TableInfo) -> Result<(), WriterError> {
-        let stmt = self.get_stmt(&table.name, batch[0].len(), batch.len())?;
+        // REPLACE of LOAD DATA would reset columns missing in the batch, so upserts use INSERT
+        if self.load_data
+            && self.write_mode != WriteMode::Upsert
+            && self.is_local_infile_allowed()?
+        {
+            return self.load_batch(batch, table);
+        }
+        let stmt = self.get_stmt(table, batch[0].len(), batch.len())?;
         let mut values = Vec::with_capacity(batch[0].len() * batch.len());
         for row in batch {
             for value in row {
-                values.push(mysql::Value::from(value));
+                // Values which can't be converted would fail on retries as well
+                values.push(mysql::Value::try_from(value).map_err(WriterError::Unrecoverable)?);
             }
         }
         self.connection
@@ -212,9 +666,227 @@ impl DBWriter for MysqlDB {
         return Ok(());
     }
 
+    fn delete_rows(&mut self, keys: &[Row], table: &TableInfo) -> anyhow::Result<()> {
+        let condition = table
+            .primary_key
+            .iter()
+            .map(|column| format!("{} = ?", QUOTE.quote(column)))
+            .collect::<Vec<_>>()
+            .join(" AND ");
+        let mut transaction = self
+            .connection
+            .start_transaction(mysql::TxOpts::default())
+            .context("Failed to open transaction")?;
+        let stmt = transaction
+            .prep(format!(
+                "DELETE FROM {} WHERE {condition}",
+                QUOTE.quote_table(&table.name)
+            ))
+            .context("Unable to prepare delete query")?;
+        for key in keys {
+            let values = key
+                .iter()
+                .map(mysql::Value::try_from)
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            transaction
+                .exec_drop(&stmt, mysql::Params::Positional(values))
+                .context("Failed to delete rows")?;
+        }
+        transaction.commit().context("Failed to commit")?;
+        return Ok(());
+    }
+
+    fn update_rows(&mut self, rows: &[Row], table: &TableInfo) -> anyhow::Result<()> {
+        let (updated, key) = table.update_positions()?;
+        if updated.is_empty() {
+            return Ok(());
+        }
+        let assignment = |idx: &usize| format!("{} = ?", QUOTE.quote(&table.columns[*idx].name));
+        let mut transaction = self
+            .connection
+            .start_transaction(mysql::TxOpts::default())
+            .context("Failed to open transaction")?;
+        let stmt = transaction
+            .prep(format!(
+                "UPDATE {} SET {} WHERE {}",
+                QUOTE.quote_table(&table.name),
+                updated
+                    .iter()
+                    .map(assignment)
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                key.iter().map(assignment).collect::<Vec<_>>().join(" AND ")
+            ))
+            .context("Unable to prepare update query")?;
+        for row in rows {
+            let values = updated
+                .iter()
+                .chain(&key)
+                .map(|idx| mysql::Value::try_from(&row[*idx]))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            transaction
+                .exec_drop(&stmt, mysql::Params::Positional(values))
+                .context("Failed to update rows")?;
+        }
+        transaction.commit().context("Failed to commit")?;
+        return Ok(());
+    }
+
+    fn create_table(&mut self, table: &TableInfo) -> anyhow::Result<()> {
+        let mut columns = Vec::with_capacity(table.columns.len());
+        for column in &table.columns {
+            // Explicit NULL, otherwise MariaDB makes timestamp columns NOT NULL
+            let null = if column.nullable { "NULL" } else { "NOT NULL" };
+            columns.push(format!(
+                "{} {} {null}",
+                QUOTE.quote(&column.name),
+                column.column_type.to_mysql_type(&self.type_options)?
+            ));
+        }
+        let query = format!(
+            "CREATE TABLE {} ({})",
+            QUOTE.quote_table(&table.name),
+            columns.join(", ")
+        );
+        debug!("Creating table: {query}");
+        self.connection
+            .query_drop(query)
+            .context("Failed to create table in mysql")?;
+        return Ok(());
+    }
+
+    fn set_write_mode(&mut self, mode: WriteMode) -> anyhow::Result<()> {
+        self.write_mode = mode;
+        self.stmt_cache.clear();
+        return Ok(());
+    }
+
+    fn enable_fast_load(&mut self) -> anyhow::Result<()> {
+        self.fast_load = true;
+        return self.apply_fast_load();
+    }
+
+    fn drop_indexes(&mut self, table: &str) -> anyhow::Result<Vec<String>> {
+        let mut dropped = Vec::new();
+        for (drop_query, create_query) in self.get_indexes(table)? {
+            let result = self
+                .connection
+                .query_drop(&drop_query)
+                .with_context(|| format!("Failed to drop index: {drop_query}"));
+            if let Err(err) = result {
+                self.create_indexes(&dropped)?;
+                return Err(err);
+            }
+            dropped.push(create_query);
+        }
+        return Ok(dropped);
+    }
+
+    fn create_indexes(&mut self, indexes: &[String]) -> anyhow::Result<()> {
+        for index in indexes {
+            self.connection
+                .query_drop(index)
+                .with_context(|| format!("Failed to create index: {index}"))?;
+        }
+        return Ok(());
+    }
+
+    fn create_staging_table(&mut self, table: &str, staging: &str) -> anyhow::Result<()> {
+        // Foreign keys and triggers aren't copied, referencing tables would block the swap
+        let (schema, name) = split_table_name(table);
+        let foreign_keys: Option<i64> = self
+            .connection
+            .exec_first(
+                r"SELECT count(1) FROM INFORMATION_SCHEMA.KEY_COLUMN_USAGE
+                WHERE REFERENCED_TABLE_NAME IS NOT NULL
+                    AND ((TABLE_NAME = :name AND TABLE_SCHEMA = coalesce(:schema, database()))
+                    OR (REFERENCED_TABLE_NAME = :name
+                        AND REFERENCED_TABLE_SCHEMA = coalesce(:schema, database())))",
+                params! {name, schema},
+            )
+            .context("Failed to query foreign keys of table")?;
+        if foreign_keys.unwrap_or(0) > 0 {
+            return Err(anyhow::anyhow!(
+                "Tables with foreign keys or referenced by other tables can't be replaced"
+            ));
+        }
+        let triggers: Option<i64> = self
+            .connection
+            .exec_first(
+                r"SELECT count(1) FROM INFORMATION_SCHEMA.TRIGGERS
+                WHERE EVENT_OBJECT_TABLE = :name
+                    AND EVENT_OBJECT_SCHEMA = coalesce(:schema, database())",
+                params! {name, schema},
+            )
+            .context("Failed to query triggers of table")?;
+        if triggers.unwrap_or(0) > 0 {
+            return Err(anyhow::anyhow!("Tables with triggers can't be replaced"));
+        }
+        self.connection
+            .query_drop(format!(
+                "CREATE TABLE {} LIKE {}",
+                QUOTE.quote_table(staging),
+                QUOTE.quote_table(table)
+            ))
+            .context("Failed to create staging table")?;
+        return Ok(());
+    }
+
+    fn swap_staging_table(&mut self, table: &str, staging: &str) -> anyhow::Result<()> {
+        // DDL isn't transactional, but renames of a single statement are atomic
+        let old = QUOTE.quote_table(&format!("{table}_db_mover_old"));
+        self.connection
+            .query_drop(format!(
+                "RENAME TABLE {table} TO {old}, {staging} TO {table}",
+                table = QUOTE.quote_table(table),
+                staging = QUOTE.quote_table(staging)
+            ))
+            .context("Failed to replace table with staging table")?;
+        self.connection
+            .query_drop(format!("DROP TABLE {old}"))
+            .with_context(|| format!("Failed to drop replaced table {old}"))?;
+        return Ok(());
+    }
+
+    fn drop_staging_table(&mut self, staging: &str) -> anyhow::Result<()> {
+        self.connection
+            .query_drop(format!("DROP TABLE {}", QUOTE.quote_table(staging)))
+            .context("Failed to drop staging table")?;
+        return Ok(());
+    }
+
+    fn reset_sequences(&mut self, table: &str) -> anyhow::Result<()> {
+        let (schema, table_name) = split_table_name(table);
+        let column: Option<String> = self
+            .connection
+            .exec_first(
+                r"SELECT COLUMN_NAME FROM INFORMATION_SCHEMA.COLUMNS
+                WHERE TABLE_NAME = :table AND TABLE_SCHEMA = coalesce(:schema, database())
+                    AND EXTRA LIKE '%auto_increment%'",
+                params! {"table" => table_name, schema},
+            )
+            .context("Failed to query auto increment column of table")?;
+        let Some(column) = column else {
+            return Ok(());
+        };
+        let table = QUOTE.quote_table(table);
+        let next: u64 = self
+            .connection
+            .query_first(format!(
+                "SELECT greatest(coalesce(max({}), 0), 0) + 1 FROM {table}",
+                QUOTE.quote(&column)
+            ))?
+            .context("Unable to get largest value of auto increment column")?;
+        self.connection
+            .query_drop(format!("ALTER TABLE {table} AUTO_INCREMENT = {next}"))
+            .with_context(|| format!("Failed to reset auto increment of table {table}"))?;
+        return Ok(());
+    }
+
     fn recover(&mut self) -> anyhow::Result<()> {
         debug!("Trying to reconnect to the mysql");
         self.connection = Self::connect(&self.uri)?;
+        self.apply_fast_load()?;
         debug!("Successfully reconnected to the mysql");
         return Ok(());
     }
@@ -1,7 +1,13 @@
-use chrono::{NaiveDateTime, TimeZone, Utc};
+use std::io::Write;
+
+use anyhow::Context;
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use mysql::binlog::value::BinlogValue;
 
 use crate::databases::table::{Column, ColumnType, Value};
 
+const DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.6f";
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct MysqlTypeOptions {
     pub binary_16_as_uuid: bool,
@@ -29,6 +35,12 @@ impl ColumnType {
         if options.tinyint_as_bool && formated == "tinyint(1)" {
             return Ok(ColumnType::Bool);
         }
+        // Fractional seconds precision of temporal types, e.g. datetime(6)
+        if let Some((name, _)) = formated.split_once('(')
+            && matches!(name, "timestamp" | "datetime" | "time")
+        {
+            return ColumnType::try_from_mysql_type(name, options);
+        }
         if formated.starts_with("char") || formated.starts_with("varchar") {
             return Ok(ColumnType::String);
         }
@@ -61,6 +73,37 @@ impl ColumnType {
             _ => Err(anyhow::anyhow!("Unknown column type {type_name}")),
         };
     }
+
+    pub fn to_mysql_type(&self, options: &MysqlTypeOptions) -> anyhow::Result<&'static str> {
+        let type_name = match self {
+            ColumnType::I64 => "bigint",
+            ColumnType::I32 => "int",
+            ColumnType::I16 => "smallint",
+            ColumnType::F64 => "double",
+            ColumnType::F32 => "float",
+            ColumnType::Decimal => "decimal(65, 28)",
+            ColumnType::Bool => "boolean",
+            ColumnType::String => "longtext",
+            ColumnType::Bytes => "longblob",
+            ColumnType::Timestamptz => "timestamp(6)",
+            ColumnType::Timestamp => "datetime(6)",
+            ColumnType::Date => "date",
+            ColumnType::Time => "time(6)",
+            ColumnType::Json => "json",
+            ColumnType::Uuid if options.binary_16_as_uuid => "binary(16)",
+            ColumnType::Array(_) => {
+                return Err(anyhow::anyhow!(
+                    "MySQL has no arrays, array columns can be created as json with --arrays-as-json"
+                ));
+            }
+            ColumnType::Uuid => {
+                return Err(anyhow::anyhow!(
+                    "Uuid columns can't be created when binary(16) is not treated as uuid"
+                ));
+            }
+        };
+        return Ok(type_name);
+    }
 }
 
 impl TryFrom<(&Column, mysql::Value)> for Value {
@@ -92,14 +135,55 @@ impl TryFrom<(&Column, mysql::Value)> for Value {
             ColumnType::Time => Value::Time(mysql::from_value_opt(val)?),
             ColumnType::Json => Value::Json(mysql::from_value_opt(val)?),
             ColumnType::Uuid => Value::Uuid(mysql::from_value_opt(val)?),
+            ColumnType::Array(_) => return Err(anyhow::anyhow!("MySQL has no arrays")),
         };
         return Ok(parsed);
     }
 }
 
-impl From<&Value> for mysql::Value {
-    fn from(value: &Value) -> Self {
-        match value {
+// Binlog keeps timestamps as unix time and JSON in the binary format
+impl TryFrom<(&Column, BinlogValue<'_>)> for Value {
+    type Error = anyhow::Error;
+
+    fn try_from(value: (&Column, BinlogValue<'_>)) -> Result<Self, Self::Error> {
+        let (column, val) = value;
+        let val = match (&column.column_type, val) {
+            (ColumnType::Timestamptz, BinlogValue::Value(mysql::Value::Int(secs))) => {
+                timestamp_to_mysql(&secs.to_string())?
+            }
+            (ColumnType::Timestamptz, BinlogValue::Value(mysql::Value::Bytes(bytes))) => {
+                timestamp_to_mysql(std::str::from_utf8(&bytes)?)?
+            }
+            (_, val) => mysql::Value::try_from(val)?,
+        };
+        return Value::try_from((column, val));
+    }
+}
+
+// Unix time as SECONDS[.MICROSECONDS] to UTC datetime
+fn timestamp_to_mysql(unix_time: &str) -> anyhow::Result<mysql::Value> {
+    let (secs, micros) = unix_time.split_once('.').unwrap_or((unix_time, "0"));
+    let timestamp = DateTime::from_timestamp(secs.parse()?, micros.parse::<u32>()? * 1000)
+        .with_context(|| format!("Invalid timestamp {unix_time}"))?;
+    return Ok(timestamp.naive_utc().into());
+}
+
+// MySQL timestamp columns only hold 1970-01-01 00:00:01 to 2038-01-19 03:14:07.999999 UTC,
+// values outside would be stored as zero or rejected depending on the SQL mode
+fn timestamp_in_range(val: &DateTime<Utc>) -> anyhow::Result<NaiveDateTime> {
+    if !(1..=i64::from(i32::MAX)).contains(&val.timestamp()) {
+        return Err(anyhow::anyhow!(
+            "Timestamp {val} is out of range of MySQL timestamp columns"
+        ));
+    }
+    return Ok(val.naive_utc());
+}
+
+impl TryFrom<&Value> for mysql::Value {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        let converted = match value {
             Value::Null => mysql::Value::NULL,
             Value::I64(val) => val.into(),
             Value::I32(val) => val.into(),
@@ -110,12 +194,69 @@ impl From<&Value> for mysql::Value {
             Value::Bool(val) => val.into(),
             Value::String(val) => val.into(),
             Value::Bytes(val) => val.as_ref().into(),
-            Value::Timestamptz(val) => val.naive_utc().into(),
+            Value::Timestamptz(val) => timestamp_in_range(val)?.into(),
             Value::Timestamp(val) => val.into(),
             Value::Date(val) => val.into(),
             Value::Time(val) => val.into(),
             Value::Json(val) => val.into(),
             Value::Uuid(val) => val.into(),
+            // Arrays are written to json columns
+            Value::Array(items) => Value::array_to_json(items)?.into(),
+        };
+        return Ok(converted);
+    }
+}
+
+// Special characters of LOAD DATA fields, escaped with a backslash
+fn write_escaped(out: &mut Vec<u8>, bytes: &[u8]) {
+    for &byte in bytes {
+        match byte {
+            b'\\' => out.extend_from_slice(b"\\\\"),
+            b'\t' => out.extend_from_slice(b"\\t"),
+            b'\n' => out.extend_from_slice(b"\\n"),
+            b'\r' => out.extend_from_slice(b"\\r"),
+            b'\0' => out.extend_from_slice(b"\\0"),
+            _ => out.push(byte),
+        }
+    }
+}
+
+fn write_hex(out: &mut Vec<u8>, bytes: &[u8]) -> std::io::Result<()> {
+    for byte in bytes {
+        write!(out, "{byte:02x}")?;
+    }
+    return Ok(());
+}
+
+impl Value {
+    // Field of tab separated LOAD DATA input, bytes are hex encoded and unhexed by the query
+    pub(crate) fn write_mysql_field(&self, out: &mut Vec<u8>) -> std::io::Result<()> {
+        match self {
+            Value::Null => out.extend_from_slice(b"\\N"),
+            Value::I64(val) => write!(out, "{val}")?,
+            Value::I32(val) => write!(out, "{val}")?,
+            Value::I16(val) => write!(out, "{val}")?,
+            Value::F64(val) => write!(out, "{val}")?,
+            Value::F32(val) => write!(out, "{val}")?,
+            Value::Decimal(val) => write!(out, "{val}")?,
+            Value::Bool(val) => write!(out, "{}", u8::from(*val))?,
+            Value::String(val) => write_escaped(out, val.as_bytes()),
+            Value::Bytes(val) => write_hex(out, val)?,
+            // UTC timezone set on connection
+            Value::Timestamptz(val) => {
+                let val = timestamp_in_range(val).map_err(std::io::Error::other)?;
+                write!(out, "{}", val.format(DATETIME_FORMAT))?;
+            }
+            Value::Timestamp(val) => write!(out, "{}", val.format(DATETIME_FORMAT))?,
+            Value::Date(val) => write!(out, "{}", val.format("%Y-%m-%d"))?,
+            Value::Time(val) => write!(out, "{}", val.format("%H:%M:%S%.6f"))?,
+            Value::Json(val) => write_escaped(out, val.to_string().as_bytes()),
+            Value::Uuid(val) => write_hex(out, val.as_bytes())?,
+            Value::Array(items) => {
+                let json = Value::array_to_json(items).map_err(std::io::Error::other)?;
+                write_escaped(out, json.to_string().as_bytes());
+            }
         }
+        return Ok(());
     }
 }
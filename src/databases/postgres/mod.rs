@@ -1,24 +1,36 @@
+use std::borrow::Cow;
 use std::collections::HashMap;
-use std::io::Write;
+use std::io::{BufReader, Read, Write};
 
 use anyhow::Context;
-use postgres::fallible_iterator::FallibleIterator;
-use postgres::{Client, NoTls};
+use postgres::types::Type;
+use postgres::{Client, GenericClient, NoTls};
 use tracing::debug;
 use value::PostgreColumn;
 
-use crate::databases::table::{Row, Value};
+use crate::databases::table::{ReadFilter, Row, Value};
 use crate::databases::traits::{DBInfoProvider, DBReader, DBWriter};
 
-use super::table::{Column, TableInfo};
+use super::identifier::IdentifierQuote;
+use super::table::{Column, TableInfo, split_table_name};
 use super::traits::{ReaderIterator, WriterError};
+use super::write_mode::WriteMode;
 
+pub mod replication;
 mod value;
 
+const QUOTE: IdentifierQuote = IdentifierQuote::ANSI;
+
 pub struct PostgresDB {
     uri: String,
     client: Client,
+    write_mode: WriteMode,
+    fast_load: bool,
+    // Exported snapshot joined by cloned readers
+    snapshot: Option<String>,
     table_columns_cache: HashMap<String, Vec<PostgreColumn>>,
+    // Temporary tables of merged writes by output table and written columns
+    staging_tables: HashMap<(String, Vec<String>), String>,
 }
 
 impl PostgresDB {
@@ -28,7 +40,11 @@ impl PostgresDB {
         return Ok(Self {
             client,
             uri: uri.to_string(),
+            write_mode: WriteMode::default(),
+            fast_load: false,
+            snapshot: None,
             table_columns_cache: HashMap::default(),
+            staging_tables: HashMap::default(),
         });
     }
 
@@ -36,8 +52,21 @@ impl PostgresDB {
         return Client::connect(uri, NoTls);
     }
 
-    fn get_num_rows(&mut self, table: &str) -> anyhow::Result<u64> {
-        let count_query = format!("SELECT count(1) FROM {table}");
+    // Session settings are lost on reconnect, so they are applied to each new connection
+    fn apply_fast_load(&mut self) -> anyhow::Result<()> {
+        if self.fast_load {
+            self.client
+                .batch_execute("SET session_replication_role = replica")
+                .context("Failed to disable triggers and foreign key checks")?;
+        }
+        return Ok(());
+    }
+
+    fn get_num_rows(&mut self, table: &str, condition: Option<&str>) -> anyhow::Result<u64> {
+        let mut count_query = format!("SELECT count(1) FROM {}", QUOTE.quote_table(table));
+        if let Some(condition) = condition {
+            count_query.push_str(&format!(" WHERE {condition}"));
+        }
         return self
             .client
             .query_one(&count_query, &[])?
@@ -48,14 +77,15 @@ impl PostgresDB {
 
     fn get_columns(&mut self, table: &str) -> anyhow::Result<Vec<PostgreColumn>> {
         let mut columns = Vec::new();
+        let (schema, name) = split_table_name(table);
         let rows = self
             .client
             .query(
                 "SELECT column_name, is_nullable
             FROM information_schema.columns 
-            WHERE table_name = $1 AND table_schema = current_schema
+            WHERE table_name = $1 AND table_schema = coalesce($2::text, current_schema)
             ORDER BY ordinal_position",
-                &[&table],
+                &[&name, &schema],
             )
             .context("Failed to query information about table")?;
         for row in rows {
@@ -66,8 +96,12 @@ impl PostgresDB {
                 nullable: is_nullable == "YES",
             })
         }
-        let column_names: Vec<&str> = columns.iter().map(|c| c.name.as_str()).collect();
-        let query = format!("SELECT {} FROM {}", column_names.join(", "), table);
+        let column_names: Vec<String> = columns.iter().map(|c| QUOTE.quote(&c.name)).collect();
+        let query = format!(
+            "SELECT {} FROM {}",
+            column_names.join(", "),
+            QUOTE.quote_table(table)
+        );
         let stmt = self
             .client
             .prepare(&query)
@@ -90,6 +124,49 @@ impl PostgresDB {
         return Ok(columns);
     }
 
+    fn get_primary_key(&mut self, table: &str) -> anyhow::Result<Vec<String>> {
+        let (schema, name) = split_table_name(table);
+        let rows = self
+            .client
+            .query(
+                "SELECT kcu.column_name
+            FROM information_schema.table_constraints tc
+            JOIN information_schema.key_column_usage kcu
+                ON tc.constraint_name = kcu.constraint_name
+                AND tc.table_schema = kcu.table_schema
+                AND tc.table_name = kcu.table_name
+            WHERE tc.constraint_type = 'PRIMARY KEY'
+                AND tc.table_name = $1 AND tc.table_schema = coalesce($2::text, current_schema)
+            ORDER BY kcu.ordinal_position",
+                &[&name, &schema],
+            )
+            .context("Failed to query primary key of table")?;
+        return Ok(rows.iter().map(|row| row.get(0)).collect());
+    }
+
+    fn query_tables(&mut self, schema: Option<&str>) -> anyhow::Result<Vec<String>> {
+        let rows = self
+            .client
+            .query(
+                "SELECT table_name FROM information_schema.tables
+                WHERE table_schema = coalesce($1::text, current_schema)",
+                &[&schema],
+            )
+            .context("Failed to query tables")?;
+        return rows
+            .iter()
+            .map(|row| {
+                let table = row
+                    .try_get::<_, String>(0)
+                    .context("Failed to read table name")?;
+                return Ok(match schema {
+                    Some(schema) => format!("{schema}.{table}"),
+                    None => table,
+                });
+            })
+            .collect::<anyhow::Result<Vec<String>>>();
+    }
+
     fn get_columns_cached(&mut self, table: &str) -> anyhow::Result<Vec<PostgreColumn>> {
         return match self.table_columns_cache.get(table) {
             Some(columns) => Ok(columns.clone()),
@@ -108,7 +185,7 @@ impl DBInfoProvider for PostgresDB {
         let mut num_rows = None;
         if !no_count {
             num_rows = Some(
-                self.get_num_rows(table)
+                self.get_num_rows(table, None)
                     .context("Failed to get number of rows in the table")?,
             );
         }
@@ -119,130 +196,742 @@ impl DBInfoProvider for PostgresDB {
             .into_iter()
             .map(Column::try_from)
             .collect::<anyhow::Result<Vec<Column>>>()?;
+        let primary_key = self
+            .get_primary_key(table)
+            .context("Failed to get primary key of the table")?;
         return Ok(TableInfo {
             name: table.to_string(),
             num_rows,
             columns,
+            primary_key,
         });
     }
 
     fn get_tables(&mut self) -> anyhow::Result<Vec<String>> {
+        return self.query_tables(None);
+    }
+
+    fn get_referenced_tables(&mut self, table: &str) -> anyhow::Result<Vec<String>> {
+        // Tables of other schemas are qualified, as in table lists
         let rows = self
             .client
             .query(
-                "SELECT table_name FROM information_schema.tables WHERE table_schema=current_schema",
-                &[]
+                "SELECT DISTINCT CASE WHEN n.nspname = current_schema
+                    THEN c.relname ELSE n.nspname || '.' || c.relname END
+            FROM pg_constraint con
+            JOIN pg_class c ON c.oid = con.confrelid
+            JOIN pg_namespace n ON n.oid = c.relnamespace
+            WHERE con.contype = 'f' AND con.conrelid = $1::text::regclass",
+                &[&QUOTE.quote_table(table)],
             )
-            .context("Failed to query tables")?;
-        return rows
-            .iter()
-            .map(|row| {
-                row.try_get::<_, String>(0)
-                    .context("Failed to read table name")
-            })
-            .collect::<anyhow::Result<Vec<String>>>();
+            .context("Failed to query foreign keys of table")?;
+        return Ok(rows.iter().map(|row| row.get(0)).collect());
+    }
+
+    fn get_schema_tables(&mut self, schema: &str) -> anyhow::Result<Vec<String>> {
+        return self.query_tables(Some(schema));
     }
 }
 
-struct PostgresRowsIter<'a> {
+// Rows of a binary COPY, fields are decoded by types of input columns
+struct PostgresCopyIter<R: Read> {
     target_format: TableInfo,
-    rows: postgres::RowIter<'a>,
+    types: Vec<Type>,
+    reader: R,
+    buffer: Vec<u8>,
+    finished: bool,
 }
 
-impl Iterator for PostgresRowsIter<'_> {
+impl<R: Read> PostgresCopyIter<R> {
+    fn new(target_format: TableInfo, types: Vec<Type>, mut reader: R) -> anyhow::Result<Self> {
+        let mut signature = [0; BINARY_SIGNATURE.len()];
+        reader.read_exact(&mut signature)?;
+        if signature != BINARY_SIGNATURE {
+            return Err(anyhow::anyhow!("Unexpected signature of binary COPY"));
+        }
+        // Flags (4 bytes)
+        read_i32(&mut reader)?;
+        // Header extension, skipped
+        let extension_len = read_i32(&mut reader)?;
+        std::io::copy(
+            &mut reader.by_ref().take(extension_len as u64),
+            &mut std::io::sink(),
+        )?;
+        return Ok(Self {
+            target_format,
+            types,
+            reader,
+            buffer: Vec::new(),
+            finished: false,
+        });
+    }
+
+    fn read_row(&mut self) -> anyhow::Result<Option<Row>> {
+        // Count of fields, -1 ends the data
+        let num_fields = read_i16(&mut self.reader)?;
+        if num_fields == -1 {
+            return Ok(None);
+        }
+        if num_fields as usize != self.target_format.columns.len() {
+            return Err(anyhow::anyhow!(
+                "Expected {} fields in a row, got {num_fields}",
+                self.target_format.columns.len()
+            ));
+        }
+        let mut row = Vec::with_capacity(self.target_format.columns.len());
+        for (column, ty) in std::iter::zip(&self.target_format.columns, &self.types) {
+            // Length of the field, -1 for NULL
+            let len = read_i32(&mut self.reader)?;
+            let raw = match usize::try_from(len) {
+                Ok(len) => {
+                    self.buffer.resize(len, 0);
+                    self.reader.read_exact(&mut self.buffer)?;
+                    Some(self.buffer.as_slice())
+                }
+                Err(_) => None,
+            };
+            let value = Value::read_postgres_bytes(&column.column_type, ty, raw)
+                .with_context(|| format!("Failed to decode column {}", column.name))?;
+            row.push(value);
+        }
+        return Ok(Some(row));
+    }
+}
+
+impl<R: Read> Iterator for PostgresCopyIter<R> {
     type Item = anyhow::Result<Row>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        return match self
-            .rows
-            .next()
-            .context("Error while reading data from postgres")
-        {
-            Ok(Some(row)) => {
-                let mut result: Row = Vec::with_capacity(self.target_format.columns.len());
-                for (idx, column) in self.target_format.columns.iter().enumerate() {
-                    match Value::try_from((column.column_type, &row, idx)) {
-                        Ok(val) => result.push(val),
-                        Err(e) => return Some(Err(e)),
-                    }
-                }
-                Some(Ok(result))
-            }
-            Ok(None) => None,
-            Err(e) => Some(Err(e)),
-        };
+        if self.finished {
+            return None;
+        }
+        let result = self
+            .read_row()
+            .context("Error while reading data from postgres");
+        if !matches!(result, Ok(Some(_))) {
+            self.finished = true;
+        }
+        return result.transpose();
     }
 }
 
+fn read_i16(reader: &mut impl Read) -> std::io::Result<i16> {
+    let mut bytes = [0; 2];
+    reader.read_exact(&mut bytes)?;
+    return Ok(i16::from_be_bytes(bytes));
+}
+
+fn read_i32(reader: &mut impl Read) -> std::io::Result<i32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    return Ok(i32::from_be_bytes(bytes));
+}
+
 impl DBReader for PostgresDB {
-    fn read_iter(&mut self, target_format: TableInfo) -> anyhow::Result<ReaderIterator<'_>> {
+    fn opt_clone(&self) -> anyhow::Result<Box<dyn DBReader>> {
+        let mut reader = PostgresDB::new(&self.uri)?;
+        if let Some(snapshot) = &self.snapshot {
+            reader
+                .client
+                .batch_execute(&format!(
+                    "BEGIN ISOLATION LEVEL REPEATABLE READ READ ONLY; SET TRANSACTION SNAPSHOT '{snapshot}'"
+                ))
+                .context("Failed to join snapshot")?;
+            reader.snapshot = Some(snapshot.clone());
+        }
+        return Ok(Box::new(reader));
+    }
+
+    fn start_snapshot(&mut self) -> anyhow::Result<()> {
+        self.client
+            .batch_execute("BEGIN ISOLATION LEVEL REPEATABLE READ READ ONLY")
+            .context("Failed to start snapshot")?;
+        let snapshot: String = self
+            .client
+            .query_one("SELECT pg_export_snapshot()", &[])
+            .context("Failed to export snapshot")?
+            .get(0);
+        debug!("Exported postgres snapshot {snapshot}");
+        self.snapshot = Some(snapshot);
+        return Ok(());
+    }
+
+    fn is_snapshot_shared(&self) -> bool {
+        return true;
+    }
+
+    fn read_iter(
+        &mut self,
+        target_format: TableInfo,
+        filter: ReadFilter,
+    ) -> anyhow::Result<ReaderIterator<'_>> {
+        let literals = filter
+            .values()
+            .into_iter()
+            .map(Value::postgres_literal)
+            .collect::<anyhow::Result<Vec<String>>>()?;
         let query = format!(
-            "SELECT {} FROM {}",
-            target_format.column_names().join(", "),
-            target_format.name
+            "COPY (SELECT {} FROM {}{}) TO STDOUT WITH BINARY",
+            QUOTE.quote_columns(&target_format),
+            QUOTE.quote_table(&target_format.name),
+            filter.to_sql(QUOTE, |idx| literals[idx].clone())
         );
-        let stmt = self
-            .client
-            .prepare(&query)
-            .context("Failed to prepare select statement")?;
-        let rows = self
+        // Fields are encoded by types of input columns, not by types of the target format
+        let columns = self.get_columns_cached(&target_format.name)?;
+        let types = target_format
+            .columns
+            .iter()
+            .map(|column| {
+                return columns
+                    .iter()
+                    .find(|postgres_column| postgres_column.name == column.name)
+                    .map(|postgres_column| postgres_column.column_type.clone())
+                    .with_context(|| format!("Column {} not found", column.name));
+            })
+            .collect::<anyhow::Result<Vec<Type>>>()?;
+        let reader = self
             .client
-            .query_raw(&stmt, &[] as &[&str; 0])
+            .copy_out(&query)
             .context("Failed to get data from postgres source")?;
-        return Ok(Box::new(PostgresRowsIter {
-            target_format,
-            rows,
-        }));
+        let rows = PostgresCopyIter::new(target_format, types, BufReader::new(reader))
+            .context("Failed to start reading data from postgres")?;
+        return Ok(Box::new(rows));
+    }
+
+    fn count_rows(&mut self, table: &str, condition: &str) -> anyhow::Result<u64> {
+        return self.get_num_rows(table, Some(condition));
+    }
+
+    fn get_key_bounds(
+        &mut self,
+        table: &str,
+        column: &Column,
+    ) -> anyhow::Result<Option<(Value, Value)>> {
+        let query = |order: &str| {
+            format!(
+                "SELECT {0} FROM {1} WHERE {0} IS NOT NULL ORDER BY {0} {order} LIMIT 1",
+                QUOTE.quote(&column.name),
+                QUOTE.quote_table(table)
+            )
+        };
+        let Some(min) = self
+            .client
+            .query_opt(&query("ASC"), &[])
+            .context("Failed to query key bounds")?
+        else {
+            return Ok(None);
+        };
+        let max = self
+            .client
+            .query_one(&query("DESC"), &[])
+            .context("Failed to query key bounds")?;
+        return Ok(Some((
+            Value::try_from((column.column_type.clone(), &min, 0))?,
+            Value::try_from((column.column_type.clone(), &max, 0))?,
+        )));
     }
 }
 
 // Binary COPY signature (first 15 bytes)
 const BINARY_SIGNATURE: &[u8] = b"PGCOPY\n\xFF\r\n\0";
 
+fn copy_batch(
+    client: &mut impl GenericClient,
+    table: &str,
+    columns: &[PostgreColumn],
+    batch: &[Row],
+) -> Result<(), WriterError> {
+    let column_names = columns
+        .iter()
+        .map(|column| QUOTE.quote(&column.name))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let query = format!(
+        "COPY {} ({column_names}) FROM STDIN WITH BINARY",
+        QUOTE.quote_table(table)
+    );
+    let mut writer = client
+        .copy_in(&query)
+        .context("Failed to start writing data into postgres")?;
+
+    writer.write_all(BINARY_SIGNATURE)?;
+
+    // Flags (4 bytes).
+    writer.write_all(&0_i32.to_be_bytes())?;
+
+    // Header extension length (4 bytes)
+    writer.write_all(&0_i32.to_be_bytes())?;
+
+    for row in batch {
+        // Count of fields
+        writer.write_all(&(row.len() as i16).to_be_bytes())?;
+        assert_eq!(
+            columns.len(),
+            row.len(),
+            "Number of columns should be equal number of value in a row"
+        );
+        for (value, column) in std::iter::zip(row, columns) {
+            value.write_postgres_bytes(&mut writer, column)?;
+        }
+    }
+    writer.write_all(&(-1_i16).to_be_bytes())?;
+    writer
+        .finish()
+        .context("Failed to finish writing to postgres")?;
+    return Ok(());
+}
+
 impl DBWriter for PostgresDB {
     fn opt_clone(&self) -> anyhow::Result<Box<dyn DBWriter>> {
-        return PostgresDB::new(&self.uri).map(|writer| Box::new(writer) as _);
+        let mut writer = PostgresDB::new(&self.uri)?;
+        writer.write_mode = self.write_mode;
+        writer.fast_load = self.fast_load;
+        writer.apply_fast_load()?;
+        return Ok(Box::new(writer));
     }
 
     fn write_batch(&mut self, batch: &[Row], table: &TableInfo) -> Result<(), WriterError> {
-        let columns = self.get_columns_cached(&table.name)?;
-        let query = format!("COPY {} FROM STDIN WITH BINARY", table.name);
-        let mut writer = self
+        let table_columns = self.get_columns_cached(&table.name)?;
+        // Only the given columns are written, in the given order
+        let columns = table
+            .columns
+            .iter()
+            .map(|column| {
+                table_columns
+                    .iter()
+                    .find(|c| c.name == column.name)
+                    .cloned()
+                    .with_context(|| format!("Column {} not found", column.name))
+            })
+            .collect::<anyhow::Result<Vec<PostgreColumn>>>()?;
+        if self.write_mode == WriteMode::Insert {
+            return copy_batch(&mut self.client, &table.name, &columns, batch);
+        }
+        let batch = match self.write_mode {
+            WriteMode::Upsert => WriteMode::last_rows_by_key(batch, table),
+            _ => Cow::Borrowed(batch),
+        };
+        // Rows are copied into a temporary table and merged, to keep the speed of binary COPY.
+        // It has only the written columns, others are filled by defaults of the output table.
+        let key = (
+            table.name.clone(),
+            table.columns.iter().map(|c| c.name.clone()).collect(),
+        );
+        let next_staging = format!("db_mover_staging_{}", self.staging_tables.len());
+        let staging = self
+            .staging_tables
+            .entry(key)
+            .or_insert(next_staging)
+            .clone();
+        let column_names = QUOTE.quote_columns(table);
+        let merge_query = format!(
+            "INSERT INTO {} ({column_names}) SELECT {column_names} FROM {}{}",
+            QUOTE.quote_table(&table.name),
+            QUOTE.quote(&staging),
+            self.write_mode.on_conflict_clause(table)
+        );
+        let mut transaction = self
+            .client
+            .transaction()
+            .context("Failed to open transaction")?;
+        transaction
+            .batch_execute(&format!(
+                "CREATE TEMP TABLE IF NOT EXISTS {} ON COMMIT DELETE ROWS
+                AS SELECT {column_names} FROM {} WITH NO DATA",
+                QUOTE.quote(&staging),
+                QUOTE.quote_table(&table.name)
+            ))
+            .context("Failed to create staging table")?;
+        copy_batch(&mut transaction, &staging, &columns, &batch)?;
+        transaction
+            .batch_execute(&merge_query)
+            .context("Failed to merge staging table")?;
+        transaction.commit().context("Failed to commit")?;
+        return Ok(());
+    }
+
+    fn delete_rows(&mut self, keys: &[Row], table: &TableInfo) -> anyhow::Result<()> {
+        let condition = table
+            .primary_key
+            .iter()
+            .enumerate()
+            .map(|(idx, column)| format!("{} = ${}", QUOTE.quote(column), idx + 1))
+            .collect::<Vec<_>>()
+            .join(" AND ");
+        let key_types = table
+            .primary_key
+            .iter()
+            .map(|name| {
+                table
+                    .column(name)
+                    .map(|column| postgres::types::Type::from(column.column_type.clone()))
+                    .with_context(|| format!("Key column {name} not found"))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let mut transaction = self
+            .client
+            .transaction()
+            .context("Failed to open transaction")?;
+        let stmt = transaction
+            .prepare_typed(
+                &format!(
+                    "DELETE FROM {} WHERE {condition}",
+                    QUOTE.quote_table(&table.name)
+                ),
+                &key_types,
+            )
+            .context("Failed to prepare delete statement")?;
+        for key in keys {
+            let params: Vec<&(dyn postgres::types::ToSql + Sync)> =
+                key.iter().map(|value| value as _).collect();
+            transaction
+                .execute(&stmt, &params)
+                .context("Failed to delete rows")?;
+        }
+        transaction.commit().context("Failed to commit")?;
+        return Ok(());
+    }
+
+    fn update_rows(&mut self, rows: &[Row], table: &TableInfo) -> anyhow::Result<()> {
+        let (updated, key) = table.update_positions()?;
+        if updated.is_empty() {
+            return Ok(());
+        }
+        let table_columns = self.get_columns_cached(&table.name)?;
+        // Parameters are typed by output columns, updated columns go first
+        let order: Vec<usize> = updated.iter().chain(&key).copied().collect();
+        let types = order
+            .iter()
+            .map(|idx| {
+                let name = &table.columns[*idx].name;
+                table_columns
+                    .iter()
+                    .find(|column| &column.name == name)
+                    .map(|column| column.column_type.clone())
+                    .with_context(|| format!("Column {name} not found"))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let assignment = |(param, idx): (usize, &usize)| {
+            format!(
+                "{} = ${}",
+                QUOTE.quote(&table.columns[*idx].name),
+                param + 1
+            )
+        };
+        let query = format!(
+            "UPDATE {} SET {} WHERE {}",
+            QUOTE.quote_table(&table.name),
+            updated
+                .iter()
+                .enumerate()
+                .map(assignment)
+                .collect::<Vec<_>>()
+                .join(", "),
+            key.iter()
+                .enumerate()
+                .map(|(param, idx)| assignment((param + updated.len(), idx)))
+                .collect::<Vec<_>>()
+                .join(" AND ")
+        );
+        let mut transaction = self
             .client
-            .copy_in(&query)
-            .context("Failed to start writing data into postgres")?;
+            .transaction()
+            .context("Failed to open transaction")?;
+        let stmt = transaction
+            .prepare_typed(&query, &types)
+            .context("Failed to prepare update statement")?;
+        for row in rows {
+            let params: Vec<&(dyn postgres::types::ToSql + Sync)> =
+                order.iter().map(|idx| &row[*idx] as _).collect();
+            transaction
+                .execute(&stmt, &params)
+                .context("Failed to update rows")?;
+        }
+        transaction.commit().context("Failed to commit")?;
+        return Ok(());
+    }
 
-        writer.write_all(BINARY_SIGNATURE)?;
+    fn create_table(&mut self, table: &TableInfo) -> anyhow::Result<()> {
+        let columns = table
+            .columns
+            .iter()
+            .map(|column| {
+                let column_type = postgres::types::Type::from(column.column_type.clone());
+                let not_null = if column.nullable { "" } else { " NOT NULL" };
+                format!(
+                    "{} {}{not_null}",
+                    QUOTE.quote(&column.name),
+                    column_type.name()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let query = format!(
+            "CREATE TABLE {} ({columns})",
+            QUOTE.quote_table(&table.name)
+        );
+        debug!("Creating table: {query}");
+        self.client
+            .batch_execute(&query)
+            .context("Failed to create table in postgres")?;
+        return Ok(());
+    }
 
-        // Flags (4 bytes).
-        writer.write_all(&0_i32.to_be_bytes())?;
+    fn set_write_mode(&mut self, mode: WriteMode) -> anyhow::Result<()> {
+        self.write_mode = mode;
+        return Ok(());
+    }
 
-        // Header extension length (4 bytes)
-        writer.write_all(&0_i32.to_be_bytes())?;
+    fn enable_fast_load(&mut self) -> anyhow::Result<()> {
+        self.fast_load = true;
+        return self.apply_fast_load();
+    }
 
-        for row in batch {
-            // Count of fields
-            writer.write_all(&(row.len() as i16).to_be_bytes())?;
-            assert_eq!(
-                columns.len(),
-                row.len(),
-                "Number of columns should be equal number of value in a row"
-            );
-            for (value, column) in std::iter::zip(row, &columns) {
-                value.write_postgres_bytes(&mut writer, column)?;
+    fn drop_indexes(&mut self, table: &str) -> anyhow::Result<Vec<String>> {
+        let rows = self
+            .client
+            .query(
+                "SELECT quote_ident(n.nspname) || '.' || quote_ident(i.relname),
+                pg_get_indexdef(i.oid)
+            FROM pg_index x
+            JOIN pg_class i ON i.oid = x.indexrelid
+            JOIN pg_namespace n ON n.oid = i.relnamespace
+            WHERE x.indrelid = $1::text::regclass AND NOT x.indisunique
+                AND NOT EXISTS (SELECT 1 FROM pg_constraint c WHERE c.conindid = x.indexrelid)",
+                &[&QUOTE.quote_table(table)],
+            )
+            .context("Failed to query indexes of table")?;
+        let mut dropped = Vec::with_capacity(rows.len());
+        for row in rows {
+            let name: String = row.get(0);
+            let result = self
+                .client
+                .batch_execute(&format!("DROP INDEX {name}"))
+                .with_context(|| format!("Failed to drop index {name}"));
+            if let Err(err) = result {
+                self.create_indexes(&dropped)?;
+                return Err(err);
             }
+            dropped.push(row.get(1));
+        }
+        return Ok(dropped);
+    }
+
+    fn create_indexes(&mut self, indexes: &[String]) -> anyhow::Result<()> {
+        for index in indexes {
+            self.client
+                .batch_execute(index)
+                .with_context(|| format!("Failed to create index: {index}"))?;
+        }
+        return Ok(());
+    }
+
+    fn create_staging_table(&mut self, table: &str, staging: &str) -> anyhow::Result<()> {
+        // Foreign keys and triggers aren't copied, referencing tables would block the swap
+        let row = self
+            .client
+            .query_one(
+                "SELECT
+                (SELECT count(1) FROM pg_constraint WHERE contype = 'f'
+                    AND (conrelid = $1::text::regclass OR confrelid = $1::text::regclass)),
+                (SELECT count(1) FROM pg_trigger
+                    WHERE tgrelid = $1::text::regclass AND NOT tgisinternal)",
+                &[&QUOTE.quote_table(table)],
+            )
+            .context("Failed to query foreign keys and triggers of table")?;
+        let (foreign_keys, triggers): (i64, i64) = (row.get(0), row.get(1));
+        if foreign_keys > 0 {
+            return Err(anyhow::anyhow!(
+                "Tables with foreign keys or referenced by other tables can't be replaced"
+            ));
+        }
+        if triggers > 0 {
+            return Err(anyhow::anyhow!("Tables with triggers can't be replaced"));
+        }
+        self.client
+            .batch_execute(&format!(
+                "CREATE TABLE {} (LIKE {} INCLUDING ALL)",
+                QUOTE.quote_table(staging),
+                QUOTE.quote_table(table)
+            ))
+            .context("Failed to create staging table")?;
+        return Ok(());
+    }
+
+    fn swap_staging_table(&mut self, table: &str, staging: &str) -> anyhow::Result<()> {
+        let quoted_table = QUOTE.quote_table(table);
+        let quoted_staging = QUOTE.quote_table(staging);
+        let mut transaction = self
+            .client
+            .transaction()
+            .context("Failed to open transaction")?;
+        // Serial sequences are shared with the staging table, but dropped with their owner
+        let sequences = transaction
+            .query(
+                "SELECT a.attname, pg_get_serial_sequence($1, a.attname)
+            FROM pg_attribute a
+            WHERE a.attrelid = $1::text::regclass AND a.attnum > 0 AND NOT a.attisdropped
+                AND a.attidentity = '' AND pg_get_serial_sequence($1, a.attname) IS NOT NULL",
+                &[&quoted_table],
+            )
+            .context("Failed to query sequences of table")?;
+        for row in sequences {
+            let column: String = row.get(0);
+            let sequence: String = row.get(1);
+            transaction
+                .batch_execute(&format!(
+                    "ALTER SEQUENCE {sequence} OWNED BY {quoted_staging}.{}",
+                    QUOTE.quote(&column)
+                ))
+                .with_context(|| format!("Failed to move sequence {sequence}"))?;
+        }
+        transaction
+            .batch_execute(&format!(
+                "DROP TABLE {quoted_table}; ALTER TABLE {quoted_staging} RENAME TO {}",
+                QUOTE.quote(split_table_name(table).1)
+            ))
+            .context("Failed to replace table with staging table")?;
+        transaction.commit().context("Failed to commit")?;
+        return Ok(());
+    }
+
+    fn drop_staging_table(&mut self, staging: &str) -> anyhow::Result<()> {
+        self.client
+            .batch_execute(&format!("DROP TABLE {}", QUOTE.quote_table(staging)))
+            .context("Failed to drop staging table")?;
+        return Ok(());
+    }
+
+    fn reset_sequences(&mut self, table: &str) -> anyhow::Result<()> {
+        let table = QUOTE.quote_table(table);
+        // Covers both serial and identity columns
+        let rows = self
+            .client
+            .query(
+                "SELECT a.attname, pg_get_serial_sequence($1, a.attname)
+            FROM pg_attribute a
+            WHERE a.attrelid = $1::text::regclass AND a.attnum > 0 AND NOT a.attisdropped
+                AND pg_get_serial_sequence($1, a.attname) IS NOT NULL",
+                &[&table],
+            )
+            .context("Failed to query sequences of table")?;
+        for row in rows {
+            let column: String = row.get(0);
+            let sequence: String = row.get(1);
+            self.client
+                .execute(
+                    &format!(
+                        "SELECT setval($1::text::regclass, coalesce(max({}), 0) + 1, false) FROM {table}",
+                        QUOTE.quote(&column)
+                    ),
+                    &[&sequence],
+                )
+                .with_context(|| format!("Failed to reset sequence {sequence}"))?;
         }
-        writer.write_all(&(-1_i16).to_be_bytes())?;
-        writer
-            .finish()
-            .context("Failed to finish writing to postgres")?;
         return Ok(());
     }
 
     fn recover(&mut self) -> anyhow::Result<()> {
         debug!("Trying to reconnect to the postgres");
         self.client = Self::connect(&self.uri)?;
+        self.apply_fast_load()?;
         debug!("Successfully reconnected to the postgres");
         return Ok(());
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::databases::table::ColumnType;
+
+    #[test]
+    fn test_binary_copy_round_trip() {
+        let column = |name: &str, column_type| PostgreColumn {
+            name: name.to_string(),
+            column_type,
+            nullable: true,
+        };
+        let postgres_columns = vec![
+            column("id", Type::INT4),
+            column("name", Type::TEXT),
+            column("created", Type::TIMESTAMPTZ),
+            column("data", Type::JSONB),
+            column("note", Type::TEXT),
+            column("tags", Type::INT8_ARRAY),
+            column("labels", Type::TEXT_ARRAY),
+            column("ids", Type::INT4_ARRAY),
+        ];
+        let created = chrono::DateTime::parse_from_rfc3339("2024-01-01T10:00:00.5Z")
+            .unwrap()
+            .to_utc();
+        let written = vec![
+            Value::I32(1),
+            Value::String("it's".to_string()),
+            Value::Timestamptz(created),
+            Value::Json(serde_json::json!({"a": 1})),
+            Value::Null,
+            Value::Array(vec![Value::I64(1), Value::Null, Value::I64(3)]),
+            Value::Array(Vec::new()),
+            Value::Array(vec![Value::I32(2)]),
+        ];
+        let mut data = BINARY_SIGNATURE.to_vec();
+        data.extend_from_slice(&0_i32.to_be_bytes());
+        data.extend_from_slice(&0_i32.to_be_bytes());
+        data.extend_from_slice(&(written.len() as i16).to_be_bytes());
+        for (value, column) in std::iter::zip(&written, &postgres_columns) {
+            value.write_postgres_bytes(&mut data, column).unwrap();
+        }
+        data.extend_from_slice(&(-1_i16).to_be_bytes());
+
+        let mut columns = postgres_columns
+            .iter()
+            .cloned()
+            .map(Column::try_from)
+            .collect::<anyhow::Result<Vec<Column>>>()
+            .unwrap();
+        // Integer column is read as a wider type
+        columns[0].column_type = ColumnType::I64;
+        // Array is read as JSON for outputs without arrays
+        columns[7].column_type = ColumnType::Json;
+        let target_format = TableInfo {
+            name: "test".to_string(),
+            num_rows: None,
+            columns,
+            primary_key: Vec::new(),
+        };
+        let types = postgres_columns
+            .iter()
+            .map(|column| column.column_type.clone())
+            .collect();
+        let rows = PostgresCopyIter::new(target_format, types, data.as_slice())
+            .unwrap()
+            .collect::<anyhow::Result<Vec<Row>>>()
+            .unwrap();
+        assert_eq!(
+            rows,
+            vec![vec![
+                Value::I64(1),
+                Value::String("it's".to_string()),
+                Value::Timestamptz(created),
+                Value::Json(serde_json::json!({"a": 1})),
+                Value::Null,
+                Value::Array(vec![Value::I64(1), Value::Null, Value::I64(3)]),
+                Value::Array(Vec::new()),
+                Value::Json(serde_json::json!([2])),
+            ]]
+        );
+        assert_eq!(
+            Value::String("it's".to_string())
+                .postgres_literal()
+                .unwrap(),
+            "'it''s'::text"
+        );
+        assert_eq!(
+            Value::Decimal("12.50".parse().unwrap())
+                .postgres_literal()
+                .unwrap(),
+            "'12.50'::numeric"
+        );
+    }
+}
@@ -0,0 +1,370 @@
+use anyhow::Context;
+use postgres::{Client, NoTls};
+use tracing::{debug, warn};
+
+use crate::databases::table::{Change, ChangeKind, ColumnType, Value};
+use crate::databases::traits::ChangeSource;
+
+const PLUGIN: &str = "test_decoding";
+// Plugin options, shared by peeking and consuming to decode changes the same way
+const OPTIONS: &str = "'include-xids', '0', 'skip-empty-xacts', '1'";
+// Postgres allows up to 1664 columns in a select list
+const MAX_DECODED_COLUMNS: usize = 1000;
+
+// Column of a decoded change with the value as an SQL literal
+#[derive(Debug, PartialEq)]
+struct RawColumn {
+    name: String,
+    type_name: String,
+    literal: String,
+}
+
+#[derive(Debug, PartialEq)]
+struct RawChange {
+    schema: String,
+    table: String,
+    kind: ChangeKind,
+    columns: Vec<RawColumn>,
+}
+
+/// Logical replication slot of the `test_decoding` plugin, consumed with SQL functions.
+/// Changes are consumed only after they are applied, so stopped runs continue from the slot.
+pub struct LogicalSlot {
+    client: Client,
+    name: String,
+    current_schema: String,
+    // Position of the last polled change
+    polled_lsn: Option<String>,
+}
+
+impl LogicalSlot {
+    /// Opens the slot, creating it if missing. Returns true for created slots.
+    pub fn open(uri: &str, name: &str) -> anyhow::Result<(Self, bool)> {
+        let mut client = Client::connect(uri, NoTls)?;
+        let exists = client
+            .query_opt(
+                "SELECT plugin FROM pg_replication_slots WHERE slot_name = $1",
+                &[&name],
+            )
+            .context("Failed to query replication slots")?;
+        let created = match exists {
+            Some(row) => {
+                let plugin: String = row.get(0);
+                if plugin != PLUGIN {
+                    return Err(anyhow::anyhow!(
+                        "Replication slot {name} uses plugin {plugin}, {PLUGIN} expected"
+                    ));
+                }
+                false
+            }
+            None => {
+                client
+                    .execute(
+                        "SELECT pg_create_logical_replication_slot($1, $2)",
+                        &[&name, &PLUGIN],
+                    )
+                    .context("Failed to create replication slot")?;
+                debug!("Created replication slot {name}");
+                true
+            }
+        };
+        let current_schema: String = client
+            .query_one("SELECT current_schema", &[])
+            .context("Failed to query current schema")?
+            .get(0);
+        let slot = Self {
+            client,
+            name: name.to_string(),
+            current_schema,
+            polled_lsn: None,
+        };
+        return Ok((slot, created));
+    }
+
+    // Literals are converted by postgres, so values are read like rows of tables.
+    // Columns of all changes are converted by a few queries instead of one per change.
+    fn decode(&mut self, raws: Vec<RawChange>) -> anyhow::Result<Vec<Change>> {
+        let literals: Vec<String> = raws
+            .iter()
+            .flat_map(|raw| &raw.columns)
+            .map(|column| format!("{}::{}", column.literal, column.type_name))
+            .collect();
+        let mut values = Vec::with_capacity(literals.len());
+        for chunk in literals.chunks(MAX_DECODED_COLUMNS) {
+            let row = self
+                .client
+                .query_one(&format!("SELECT {}", chunk.join(", ")), &[])
+                .context("Failed to decode changes")?;
+            for (idx, column) in row.columns().iter().enumerate() {
+                let column_type = ColumnType::try_from(column.type_().clone())?;
+                values.push(Value::try_from((column_type, &row, idx))?);
+            }
+        }
+        let mut values = values.into_iter();
+        let changes = raws
+            .into_iter()
+            .map(|raw| {
+                let table = if raw.schema == self.current_schema {
+                    raw.table
+                } else {
+                    format!("{}.{}", raw.schema, raw.table)
+                };
+                return Change {
+                    table,
+                    kind: raw.kind,
+                    row: values.by_ref().take(raw.columns.len()).collect(),
+                    columns: raw.columns.into_iter().map(|column| column.name).collect(),
+                };
+            })
+            .collect();
+        return Ok(changes);
+    }
+}
+
+impl ChangeSource for LogicalSlot {
+    fn poll(&mut self, limit: usize) -> anyhow::Result<Vec<Change>> {
+        let limit = i32::try_from(limit).unwrap_or(i32::MAX);
+        let rows = self
+            .client
+            .query(
+                &format!(
+                    "SELECT lsn::text, data FROM pg_logical_slot_peek_changes($1, NULL, $2, {OPTIONS})"
+                ),
+                &[&self.name, &limit],
+            )
+            .context("Failed to read changes of replication slot")?;
+        let mut raws = Vec::new();
+        for row in &rows {
+            let data: &str = row.get(1);
+            raws.extend(parse_change(data)?);
+        }
+        let changes = self.decode(raws)?;
+        self.polled_lsn = rows.last().map(|row| row.get(0));
+        return Ok(changes);
+    }
+
+    // Changes are consumed only now, so unapplied changes are kept by the slot
+    fn confirm(&mut self) -> anyhow::Result<()> {
+        let Some(lsn) = self.polled_lsn.take() else {
+            return Ok(());
+        };
+        self.client
+            .execute(
+                &format!(
+                    "SELECT count(1) FROM pg_logical_slot_get_changes($1, $2::text::pg_lsn, NULL, {OPTIONS})"
+                ),
+                &[&self.name, &lsn],
+            )
+            .context("Failed to consume changes of replication slot")?;
+        return Ok(());
+    }
+
+    fn discard(mut self: Box<Self>) -> anyhow::Result<()> {
+        self.client
+            .execute("SELECT pg_drop_replication_slot($1)", &[&self.name])
+            .context("Failed to drop replication slot")?;
+        return Ok(());
+    }
+}
+
+// Identifier, quoted if it needs quotes, and the rest of the input
+fn parse_identifier(input: &str) -> anyhow::Result<(String, &str)> {
+    let Some(quoted) = input.strip_prefix('"') else {
+        let end = input.find(['.', '[', ':', ' ']).unwrap_or(input.len());
+        return Ok((input[..end].to_string(), &input[end..]));
+    };
+    let mut name = String::new();
+    let mut chars = quoted.char_indices().peekable();
+    while let Some((idx, c)) = chars.next() {
+        if c != '"' {
+            name.push(c);
+            continue;
+        }
+        if chars.peek().is_some_and(|(_, next)| *next == '"') {
+            chars.next();
+            name.push('"');
+            continue;
+        }
+        return Ok((name, &quoted[idx + 1..]));
+    }
+    return Err(anyhow::anyhow!("Unterminated identifier in {input}"));
+}
+
+// Literal is a quoted string with doubled quotes or a word up to the next space
+fn parse_literal(input: &str) -> anyhow::Result<(&str, &str)> {
+    if !input.starts_with('\'') {
+        let end = input.find(' ').unwrap_or(input.len());
+        return Ok((&input[..end], &input[end..]));
+    }
+    let mut chars = input.char_indices().skip(1).peekable();
+    while let Some((idx, c)) = chars.next() {
+        if c != '\'' {
+            continue;
+        }
+        if chars.peek().is_some_and(|(_, next)| *next == '\'') {
+            chars.next();
+            continue;
+        }
+        return Ok((&input[..idx + 1], &input[idx + 1..]));
+    }
+    return Err(anyhow::anyhow!("Unterminated literal in {input}"));
+}
+
+// Columns as `name[type]:value`, separated by spaces
+fn parse_columns(mut input: &str) -> anyhow::Result<Vec<RawColumn>> {
+    let mut columns = Vec::new();
+    loop {
+        input = input.trim_start();
+        if input.is_empty() || input == "(no-tuple-data)" {
+            return Ok(columns);
+        }
+        let (name, rest) = parse_identifier(input)?;
+        let rest = rest
+            .strip_prefix('[')
+            .with_context(|| format!("Expected type of column {name}"))?;
+        // Types of arrays end with brackets too
+        let type_end = rest
+            .find("]:")
+            .with_context(|| format!("Expected value of column {name}"))?;
+        let type_name = rest[..type_end].to_string();
+        let (literal, rest) = parse_literal(&rest[type_end + 2..])?;
+        // Unchanged TOASTed values aren't logged, columns missing in changes keep their values
+        if literal == "unchanged-toast-datum" {
+            input = rest;
+            continue;
+        }
+        columns.push(RawColumn {
+            name,
+            type_name,
+            literal: literal.to_string(),
+        });
+        input = rest;
+    }
+}
+
+// Line of test_decoding output, updates changing the key are split into a delete and an upsert
+fn parse_change(data: &str) -> anyhow::Result<Vec<RawChange>> {
+    let Some(data) = data.strip_prefix("table ") else {
+        // Transaction boundaries and messages
+        return Ok(Vec::new());
+    };
+    let (schema, rest) = parse_identifier(data)?;
+    let rest = rest
+        .strip_prefix('.')
+        .with_context(|| format!("Expected qualified table name in {data}"))?;
+    let (table, rest) = parse_identifier(rest)?;
+    let rest = rest
+        .strip_prefix(": ")
+        .with_context(|| format!("Expected action in {data}"))?;
+    let (action, rest) = rest
+        .split_once(':')
+        .with_context(|| format!("Expected action in {data}"))?;
+    let change = |kind, columns| RawChange {
+        schema: schema.clone(),
+        table: table.clone(),
+        kind,
+        columns,
+    };
+    return match action {
+        "INSERT" => Ok(vec![change(ChangeKind::Insert, parse_columns(rest)?)]),
+        "UPDATE" => match rest.trim_start().strip_prefix("old-key:") {
+            Some(rest) => {
+                let (old_key, new_tuple) = rest
+                    .split_once(" new-tuple:")
+                    .with_context(|| format!("Expected new tuple in {data}"))?;
+                Ok(vec![
+                    change(ChangeKind::Delete, parse_columns(old_key)?),
+                    change(ChangeKind::Insert, parse_columns(new_tuple)?),
+                ])
+            }
+            None => Ok(vec![change(ChangeKind::Update, parse_columns(rest)?)]),
+        },
+        "DELETE" => {
+            let columns = parse_columns(rest)?;
+            if columns.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "Delete of table \"{schema}.{table}\" has no key, table needs a primary key"
+                ));
+            }
+            Ok(vec![change(ChangeKind::Delete, columns)])
+        }
+        _ => {
+            warn!("Skipped {action} of table \"{schema}.{table}\"");
+            Ok(Vec::new())
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column(name: &str, type_name: &str, literal: &str) -> RawColumn {
+        return RawColumn {
+            name: name.to_string(),
+            type_name: type_name.to_string(),
+            literal: literal.to_string(),
+        };
+    }
+
+    #[test]
+    fn test_parse_insert() {
+        let changes = parse_change(
+            "table public.\"Order\": INSERT: id[integer]:1 \"Note\"[text]:'it''s a test' \
+            tags[text[]]:'{a,b}' created[timestamp with time zone]:'2024-01-01 00:00:00+00' \
+            deleted[boolean]:null",
+        )
+        .unwrap();
+        assert_eq!(
+            changes,
+            vec![RawChange {
+                schema: "public".to_string(),
+                table: "Order".to_string(),
+                kind: ChangeKind::Insert,
+                columns: vec![
+                    column("id", "integer", "1"),
+                    column("Note", "text", "'it''s a test'"),
+                    column("tags", "text[]", "'{a,b}'"),
+                    column(
+                        "created",
+                        "timestamp with time zone",
+                        "'2024-01-01 00:00:00+00'"
+                    ),
+                    column("deleted", "boolean", "null"),
+                ],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_update_and_delete() {
+        let changes = parse_change(
+            "table s.t: UPDATE: old-key: id[bigint]:1 new-tuple: id[bigint]:2 v[text]:'x'",
+        )
+        .unwrap();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].kind, ChangeKind::Delete);
+        assert_eq!(changes[0].columns, vec![column("id", "bigint", "1")]);
+        assert_eq!(changes[1].kind, ChangeKind::Insert);
+        assert_eq!(changes[1].columns.len(), 2);
+
+        let changes = parse_change("table s.t: DELETE: id[bigint]:2").unwrap();
+        assert_eq!(changes[0].kind, ChangeKind::Delete);
+        assert!(parse_change("table s.t: DELETE: (no-tuple-data)").is_err());
+        let changes = parse_change(
+            "table s.t: UPDATE: id[bigint]:2 v[text]:unchanged-toast-datum w[text]:'y'",
+        )
+        .unwrap();
+        assert_eq!(changes[0].kind, ChangeKind::Update);
+        assert_eq!(
+            changes[0].columns,
+            vec![column("id", "bigint", "2"), column("w", "text", "'y'")]
+        );
+        assert!(parse_change("BEGIN").unwrap().is_empty());
+        assert!(
+            parse_change("table s.t: TRUNCATE: (no-flags)")
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -2,7 +2,7 @@ use std::io::Write;
 
 use anyhow::Context;
 use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime, NaiveTime, Timelike, Utc};
-use postgres::types::{ToSql, Type};
+use postgres::types::{FromSql, IsNull, Kind, ToSql, Type, to_sql_checked};
 use rust_decimal::Decimal;
 
 use crate::databases::{
@@ -28,14 +28,78 @@ impl TryFrom<Type> for ColumnType {
             Type::TIMESTAMP => ColumnType::Timestamp,
             Type::DATE => ColumnType::Date,
             Type::TIME => ColumnType::Time,
-            Type::JSON | Type::JSON_ARRAY | Type::JSONB | Type::JSONB_ARRAY => ColumnType::Json,
+            Type::JSON | Type::JSONB => ColumnType::Json,
             Type::UUID => ColumnType::Uuid,
-            _ => return Err(anyhow::anyhow!("Unsupported postgres type {value}")),
+            _ => match value.kind() {
+                Kind::Array(item) => ColumnType::Array(Box::new(item.clone().try_into()?)),
+                _ => return Err(anyhow::anyhow!("Unsupported postgres type {value}")),
+            },
         };
         return Ok(column_type);
     }
 }
 
+impl From<ColumnType> for Type {
+    fn from(value: ColumnType) -> Self {
+        return match value {
+            ColumnType::I64 => Type::INT8,
+            ColumnType::I32 => Type::INT4,
+            ColumnType::I16 => Type::INT2,
+            ColumnType::F64 => Type::FLOAT8,
+            ColumnType::F32 => Type::FLOAT4,
+            ColumnType::Decimal => Type::NUMERIC,
+            ColumnType::Bool => Type::BOOL,
+            ColumnType::String => Type::TEXT,
+            ColumnType::Bytes => Type::BYTEA,
+            ColumnType::Timestamptz => Type::TIMESTAMPTZ,
+            ColumnType::Timestamp => Type::TIMESTAMP,
+            ColumnType::Date => Type::DATE,
+            ColumnType::Time => Type::TIME,
+            ColumnType::Json => Type::JSONB,
+            ColumnType::Uuid => Type::UUID,
+            ColumnType::Array(item) => array_type(Type::from(*item)),
+        };
+    }
+}
+
+// Type of arrays of the given items, arrays of arrays have the type of their items
+fn array_type(item: Type) -> Type {
+    return match item {
+        Type::INT8 => Type::INT8_ARRAY,
+        Type::INT4 => Type::INT4_ARRAY,
+        Type::INT2 => Type::INT2_ARRAY,
+        Type::FLOAT8 => Type::FLOAT8_ARRAY,
+        Type::FLOAT4 => Type::FLOAT4_ARRAY,
+        Type::NUMERIC => Type::NUMERIC_ARRAY,
+        Type::BOOL => Type::BOOL_ARRAY,
+        Type::TEXT => Type::TEXT_ARRAY,
+        Type::BYTEA => Type::BYTEA_ARRAY,
+        Type::TIMESTAMPTZ => Type::TIMESTAMPTZ_ARRAY,
+        Type::TIMESTAMP => Type::TIMESTAMP_ARRAY,
+        Type::DATE => Type::DATE_ARRAY,
+        Type::TIME => Type::TIME_ARRAY,
+        Type::JSONB => Type::JSONB_ARRAY,
+        Type::UUID => Type::UUID_ARRAY,
+        item => item,
+    };
+}
+
+// Field in the binary format, decoded by `Value::read_postgres_bytes`
+struct RawField<'a>(&'a [u8]);
+
+impl<'a> FromSql<'a> for RawField<'a> {
+    fn from_sql(
+        _ty: &Type,
+        raw: &'a [u8],
+    ) -> Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+        return Ok(RawField(raw));
+    }
+
+    fn accepts(_ty: &Type) -> bool {
+        return true;
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct PostgreColumn {
     pub name: String,
@@ -61,6 +125,10 @@ impl TryFrom<(ColumnType, &postgres::Row, usize)> for Value {
     fn try_from(value: (ColumnType, &postgres::Row, usize)) -> Result<Self, Self::Error> {
         let (column_type, row, idx) = value;
         let real_columnt_type = &row.columns()[idx];
+        if let Kind::Array(_) = real_columnt_type.type_().kind() {
+            let raw = row.get::<_, Option<RawField>>(idx).map(|raw| raw.0);
+            return Value::read_postgres_bytes(&column_type, real_columnt_type.type_(), raw);
+        }
         let value = match column_type {
             ColumnType::I64 => {
                 if real_columnt_type.type_() == &Type::INT2 {
@@ -128,22 +196,213 @@ impl TryFrom<(ColumnType, &postgres::Row, usize)> for Value {
             ColumnType::Uuid => row
                 .get::<_, Option<uuid::Uuid>>(idx)
                 .map_or(Value::Null, Value::Uuid),
+            ColumnType::Array(_) => {
+                return Err(anyhow::anyhow!(
+                    "Column of type {} can't be read as array",
+                    real_columnt_type.type_()
+                ));
+            }
         };
         return Ok(value);
     }
 }
 
+// Used for query parameters, types of parameters should be declared from values
+impl ToSql for Value {
+    fn to_sql(
+        &self,
+        ty: &Type,
+        out: &mut bytes::BytesMut,
+    ) -> Result<IsNull, Box<dyn std::error::Error + Sync + Send>> {
+        return match self {
+            Value::Null => Ok(IsNull::Yes),
+            Value::I64(val) => val.to_sql(ty, out),
+            Value::I32(val) => val.to_sql(ty, out),
+            Value::I16(val) => val.to_sql(ty, out),
+            Value::F64(val) => val.to_sql(ty, out),
+            Value::F32(val) => val.to_sql(ty, out),
+            Value::Decimal(val) => val.to_sql(ty, out),
+            Value::Bool(val) => val.to_sql(ty, out),
+            Value::String(val) => val.to_sql(ty, out),
+            Value::Bytes(val) => val.as_ref().to_sql(ty, out),
+            Value::Timestamptz(val) => val.to_sql(ty, out),
+            Value::Timestamp(val) => val.to_sql(ty, out),
+            Value::Date(val) => val.to_sql(ty, out),
+            Value::Time(val) => val.to_sql(ty, out),
+            Value::Json(val) => postgres::types::Json(val).to_sql(ty, out),
+            Value::Uuid(val) => val.to_sql(ty, out),
+            Value::Array(items) => {
+                let mut buffer = Vec::new();
+                write_postgres_array(items, ty, &mut buffer)?;
+                out.extend_from_slice(&buffer);
+                Ok(IsNull::No)
+            }
+        };
+    }
+
+    fn accepts(_ty: &Type) -> bool {
+        return true;
+    }
+
+    to_sql_checked!();
+}
+
 const POSTGRES_EPOCH: NaiveDateTime = NaiveDate::from_ymd_opt(2000, 1, 1)
     .unwrap()
     .and_hms_opt(0, 0, 0)
     .unwrap();
 
+fn from_sql<'a, T: FromSql<'a>>(ty: &Type, raw: &'a [u8]) -> anyhow::Result<T> {
+    return T::from_sql(ty, raw).map_err(anyhow::Error::from_boxed);
+}
+
+// Items of a one dimensional array in the binary format
+fn read_postgres_array(
+    item_type: &ColumnType,
+    ty: &Type,
+    raw: &[u8],
+) -> anyhow::Result<Vec<Value>> {
+    let Kind::Array(item_ty) = ty.kind() else {
+        return Err(anyhow::anyhow!("Array expected, got {ty}"));
+    };
+    let mut reader = raw;
+    let dimensions = super::read_i32(&mut reader)?;
+    // Flag of NULL items and type of items
+    super::read_i32(&mut reader)?;
+    super::read_i32(&mut reader)?;
+    if dimensions == 0 {
+        return Ok(Vec::new());
+    }
+    if dimensions != 1 {
+        return Err(anyhow::anyhow!(
+            "Only one dimensional arrays are supported, got {dimensions} dimensions"
+        ));
+    }
+    let len = super::read_i32(&mut reader)?;
+    // Lower bound
+    super::read_i32(&mut reader)?;
+    let mut items = Vec::with_capacity(usize::try_from(len)?);
+    for _ in 0..len {
+        let item = match usize::try_from(super::read_i32(&mut reader)?) {
+            Ok(size) => {
+                let (item, rest) = reader
+                    .split_at_checked(size)
+                    .context("Array is shorter than its items")?;
+                reader = rest;
+                Some(item)
+            }
+            Err(_) => None,
+        };
+        items.push(Value::read_postgres_bytes(item_type, item_ty, item)?);
+    }
+    return Ok(items);
+}
+
+// Array in the binary format, without the length
+fn write_postgres_array(items: &[Value], ty: &Type, out: &mut Vec<u8>) -> Result<(), WriterError> {
+    let Kind::Array(item_ty) = ty.kind() else {
+        return Err(anyhow::anyhow!("Array can't be written to column of type {ty}").into());
+    };
+    let dimensions = i32::from(!items.is_empty());
+    let has_nulls = i32::from(items.contains(&Value::Null));
+    out.write_all(&dimensions.to_be_bytes())?;
+    out.write_all(&has_nulls.to_be_bytes())?;
+    out.write_all(&item_ty.oid().to_be_bytes())?;
+    if !items.is_empty() {
+        out.write_all(&(items.len() as i32).to_be_bytes())?;
+        // Lower bound
+        out.write_all(&1_i32.to_be_bytes())?;
+    }
+    for item in items {
+        item.write_postgres_field(out, item_ty)?;
+    }
+    return Ok(());
+}
+
 impl Value {
+    // COPY doesn't accept parameters, so values of filters are inlined
+    pub(crate) fn postgres_literal(&self) -> anyhow::Result<String> {
+        let quote = |text: String, type_name: &str| {
+            return format!("'{}'::{type_name}", text.replace('\'', "''"));
+        };
+        let literal = match self {
+            Value::I64(val) => val.to_string(),
+            Value::I32(val) => val.to_string(),
+            Value::I16(val) => val.to_string(),
+            Value::String(val) => quote(val.clone(), "text"),
+            Value::Decimal(val) => quote(val.to_string(), "numeric"),
+            Value::Uuid(val) => quote(val.hyphenated().to_string(), "uuid"),
+            Value::Timestamptz(val) => quote(val.to_rfc3339(), "timestamptz"),
+            Value::Timestamp(val) => {
+                quote(val.format("%Y-%m-%d %H:%M:%S%.f").to_string(), "timestamp")
+            }
+            Value::Date(val) => quote(val.to_string(), "date"),
+            _ => return Err(anyhow::anyhow!("Unsupported filter value {self:?}")),
+        };
+        return Ok(literal);
+    }
+
+    // Field of a binary COPY tuple, None for NULL. `ty` is the type of the input column.
+    pub(crate) fn read_postgres_bytes(
+        column_type: &ColumnType,
+        ty: &Type,
+        raw: Option<&[u8]>,
+    ) -> anyhow::Result<Value> {
+        let Some(raw) = raw else {
+            return Ok(Value::Null);
+        };
+        let value = match column_type {
+            ColumnType::I64 => match *ty {
+                Type::INT2 => Value::I64(from_sql::<i16>(ty, raw)?.into()),
+                Type::INT4 => Value::I64(from_sql::<i32>(ty, raw)?.into()),
+                _ => Value::I64(from_sql(ty, raw)?),
+            },
+            ColumnType::I32 => match *ty {
+                Type::INT2 => Value::I32(from_sql::<i16>(ty, raw)?.into()),
+                _ => Value::I32(from_sql(ty, raw)?),
+            },
+            ColumnType::I16 => Value::I16(from_sql(ty, raw)?),
+            ColumnType::F64 => match *ty {
+                Type::FLOAT4 => Value::F64(from_sql::<f32>(ty, raw)?.into()),
+                _ => Value::F64(from_sql(ty, raw)?),
+            },
+            ColumnType::F32 => Value::F32(from_sql(ty, raw)?),
+            ColumnType::Decimal => Value::Decimal(from_sql(ty, raw)?),
+            ColumnType::Bool => Value::Bool(from_sql(ty, raw)?),
+            ColumnType::String => Value::String(from_sql::<&str>(ty, raw)?.to_string()),
+            ColumnType::Bytes => {
+                Value::Bytes(bytes::Bytes::copy_from_slice(from_sql::<&[u8]>(ty, raw)?))
+            }
+            ColumnType::Timestamptz => Value::Timestamptz(from_sql(ty, raw)?),
+            ColumnType::Timestamp => Value::Timestamp(from_sql(ty, raw)?),
+            ColumnType::Date => Value::Date(from_sql(ty, raw)?),
+            ColumnType::Time => Value::Time(from_sql(ty, raw)?),
+            // Arrays written to JSON columns
+            ColumnType::Json if matches!(ty.kind(), Kind::Array(_)) => {
+                let Kind::Array(item_ty) = ty.kind() else {
+                    unreachable!()
+                };
+                let item_type = ColumnType::try_from(item_ty.clone())?;
+                let items = read_postgres_array(&item_type, ty, raw)?;
+                Value::Json(Value::array_to_json(&items)?)
+            }
+            ColumnType::Json => Value::Json(from_sql(ty, raw)?),
+            ColumnType::Uuid => Value::Uuid(from_sql(ty, raw)?),
+            ColumnType::Array(item_type) => Value::Array(read_postgres_array(item_type, ty, raw)?),
+        };
+        return Ok(value);
+    }
+
     pub(crate) fn write_postgres_bytes(
         &self,
         writer: &mut impl Write,
         column: &PostgreColumn,
     ) -> Result<(), WriterError> {
+        return self.write_postgres_field(writer, &column.column_type);
+    }
+
+    // Length and bytes of the value, `ty` is the type of the output column
+    fn write_postgres_field(&self, writer: &mut impl Write, ty: &Type) -> Result<(), WriterError> {
         match self {
             &Value::Null => {
                 writer.write_all(&(-1_i32).to_be_bytes())?;
@@ -213,7 +472,7 @@ impl Value {
             Value::Json(value) => {
                 let bytes =
                     serde_json::to_vec(value).context("Failed to serialize json into bytes")?;
-                if column.column_type == Type::JSONB || column.column_type == Type::JSONB_ARRAY {
+                if *ty == Type::JSONB {
                     let jsonb_version = 1_u8;
                     let len = (bytes.len() + size_of_val(&jsonb_version)) as i32;
                     writer.write_all(&(len).to_be_bytes())?;
@@ -228,6 +487,12 @@ impl Value {
                 writer.write_all(&(bytes.len() as i32).to_be_bytes())?;
                 writer.write_all(bytes)?;
             }
+            Value::Array(items) => {
+                let mut buffer = Vec::new();
+                write_postgres_array(items, ty, &mut buffer)?;
+                writer.write_all(&(buffer.len() as i32).to_be_bytes())?;
+                writer.write_all(&buffer)?;
+            }
         };
         return Ok(());
     }
@@ -1,23 +1,81 @@
 use std::str::FromStr;
 
 use anyhow::Context;
-use rusqlite::{Connection, OpenFlags, params_from_iter};
+use rusqlite::limits::Limit;
+use rusqlite::types::ValueRef;
+use rusqlite::{Connection, OpenFlags, OptionalExtension, params_from_iter};
 use tracing::debug;
 
 use crate::databases::{
-    table::{Row, Value},
+    table::{ReadFilter, Row, Value},
     traits::{DBInfoProvider, DBReader, DBWriter},
+    write_mode::WriteMode,
 };
 
 use super::{
-    table::{Column, TableInfo},
+    identifier::IdentifierQuote,
+    table::{Column, ColumnType, TableInfo},
     traits::{ReaderIterator, WriterError},
 };
 
 mod value;
 
+use value::SqliteParam;
+
+const QUOTE: IdentifierQuote = IdentifierQuote::ANSI;
+// Cache of the fast write mode in KiB
+const FAST_WRITE_CACHE_SIZE: i64 = -256 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, clap::ValueEnum)]
+pub enum FastWriteJournal {
+    /// Write-ahead log, interrupted writes are rolled back
+    Wal,
+    /// No journal, interrupted writes may corrupt the database
+    Off,
+}
+
+impl FastWriteJournal {
+    fn as_str(&self) -> &'static str {
+        return match self {
+            FastWriteJournal::Wal => "wal",
+            FastWriteJournal::Off => "off",
+        };
+    }
+}
+
+/// Journal mode persists in the database file and is shared by all connections,
+/// so it's changed once for the whole run and restored when the guard is dropped.
+pub struct JournalModeGuard {
+    uri: String,
+    previous: String,
+}
+
+impl JournalModeGuard {
+    pub fn new(uri: &str, journal: FastWriteJournal) -> anyhow::Result<Self> {
+        let mut db = SqliteDB::new(uri)?;
+        let previous = db.journal_mode()?;
+        db.set_journal_mode(journal.as_str())?;
+        return Ok(JournalModeGuard {
+            uri: uri.to_string(),
+            previous,
+        });
+    }
+
+    /// Restore the previous journal mode, all other connections must be closed
+    pub fn restore(self) -> anyhow::Result<()> {
+        let mut db = SqliteDB::new(&self.uri)?;
+        return db
+            .set_journal_mode(&self.previous)
+            .with_context(|| format!("Failed to restore journal mode {}", self.previous));
+    }
+}
+
 pub struct SqliteDB {
+    uri: String,
     connection: Connection,
+    write_mode: WriteMode,
+    fast_write: bool,
+    decimal_as_real: bool,
 }
 
 impl SqliteDB {
@@ -30,7 +88,62 @@ impl SqliteDB {
                 | OpenFlags::SQLITE_OPEN_URI,
         )?;
         debug!("Connected to sqlite {uri}");
-        return Ok(SqliteDB { connection: conn });
+        return Ok(SqliteDB {
+            uri: uri.to_string(),
+            connection: conn,
+            write_mode: WriteMode::default(),
+            fast_write: false,
+            decimal_as_real: false,
+        });
+    }
+
+    /// Write without synchronous writes, with a large cache and multi-row inserts.
+    /// Synchronous writes and cache are set for this connection only,
+    /// journal mode of the database is set by `run`, see [`JournalModeGuard`].
+    pub fn enable_fast_write(&mut self) -> anyhow::Result<()> {
+        self.connection
+            .pragma_update(None, "synchronous", "OFF")
+            .context("Failed to set pragma synchronous")?;
+        self.connection
+            .pragma_update(None, "cache_size", FAST_WRITE_CACHE_SIZE)
+            .context("Failed to set pragma cache_size")?;
+        self.fast_write = true;
+        return Ok(());
+    }
+
+    /// Write without a rollback journal. Unlike WAL, rollback journal modes are kept
+    /// by the connection only, so the mode isn't changed if the database uses WAL.
+    pub fn disable_journal(&mut self) -> anyhow::Result<()> {
+        if self.journal_mode()?.eq_ignore_ascii_case("wal") {
+            return Ok(());
+        }
+        return self.set_journal_mode(FastWriteJournal::Off.as_str());
+    }
+
+    /// Store decimals as REAL instead of lossless TEXT
+    pub fn enable_decimal_as_real(&mut self) {
+        self.decimal_as_real = true;
+    }
+
+    fn journal_mode(&mut self) -> anyhow::Result<String> {
+        return self
+            .connection
+            .pragma_query_value(None, "journal_mode", |row| row.get(0))
+            .context("Failed to query journal mode");
+    }
+
+    fn set_journal_mode(&mut self, value: &str) -> anyhow::Result<()> {
+        // Journal mode isn't changed while other connections use the database
+        let mode: String = self
+            .connection
+            .pragma_update_and_check(None, "journal_mode", value, |row| row.get(0))
+            .context("Failed to set journal mode")?;
+        if !mode.eq_ignore_ascii_case(value) {
+            return Err(anyhow::anyhow!(
+                "Failed to set journal mode {value}, journal mode is {mode}"
+            ));
+        }
+        return Ok(());
     }
 
     fn get_columns(&mut self, table: &str) -> anyhow::Result<Vec<Column>> {
@@ -52,22 +165,59 @@ impl SqliteDB {
         return Ok(result);
     }
 
-    fn write_batch_impl(&mut self, batch: &[Row], table: &str) -> anyhow::Result<()> {
+    fn get_num_rows(&mut self, table: &str, condition: Option<&str>) -> anyhow::Result<u64> {
+        let mut query = format!("SELECT count(1) FROM {}", QUOTE.quote(table));
+        if let Some(condition) = condition {
+            query.push_str(&format!(" WHERE {condition}"));
+        }
+        return Ok(self
+            .connection
+            .query_row(&query, [], |row| row.get::<_, u32>(0))?
+            .into());
+    }
+
+    fn get_primary_key(&mut self, table: &str) -> anyhow::Result<Vec<String>> {
+        let mut stmt = self
+            .connection
+            .prepare("SELECT name FROM pragma_table_info WHERE arg=? AND pk > 0 ORDER BY pk")?;
+        let mut rows = stmt.query([table])?;
+        let mut result = Vec::new();
+        while let Some(row) = rows.next()? {
+            result.push(row.get(0)?);
+        }
+        return Ok(result);
+    }
+
+    fn write_batch_impl(&mut self, batch: &[Row], table: &TableInfo) -> anyhow::Result<()> {
+        let values_per_row = batch[0].len();
+        // Rows of an insert are limited by the number of variables of a statement
+        let rows_per_insert = if self.fast_write {
+            let max_variables = self.connection.limit(Limit::SQLITE_LIMIT_VARIABLE_NUMBER)?;
+            (max_variables as usize / values_per_row.max(1)).max(1)
+        } else {
+            1
+        };
+        let write_mode = self.write_mode;
+        let decimal_as_real = self.decimal_as_real;
+        let full_query = insert_query(table, write_mode, values_per_row, rows_per_insert);
         let trx = self
             .connection
             .transaction()
             .context("Failed to open transaction")?;
         {
-            let placeholder = format!(
-                "({})",
-                batch[0].iter().map(|_| "?").collect::<Vec<_>>().join(", ")
-            );
-            let query = format!("INSERT INTO {table} VALUES {placeholder}");
             let mut stmt = trx
-                .prepare(&query)
+                .prepare(&full_query)
                 .context("Failed to create write query")?;
-            for row in batch {
-                stmt.execute(params_from_iter(row.iter()))
+            let mut chunks = batch.chunks_exact(rows_per_insert);
+            for chunk in &mut chunks {
+                stmt.execute(row_params(chunk, decimal_as_real))
+                    .context("Failed to write data")?;
+            }
+            let rest = chunks.remainder();
+            if !rest.is_empty() {
+                trx.prepare(&insert_query(table, write_mode, values_per_row, rest.len()))
+                    .context("Failed to create write query")?
+                    .execute(row_params(rest, decimal_as_real))
                     .context("Failed to write data")?;
             }
         }
@@ -76,28 +226,100 @@ impl SqliteDB {
     }
 }
 
+fn insert_query(
+    table: &TableInfo,
+    write_mode: WriteMode,
+    values_per_row: usize,
+    rows: usize,
+) -> String {
+    let placeholder = format!("({})", vec!["?"; values_per_row].join(", "));
+    return format!(
+        "INSERT INTO {} ({}) VALUES {}{}",
+        QUOTE.quote(&table.name),
+        QUOTE.quote_columns(table),
+        vec![placeholder; rows].join(", "),
+        write_mode.on_conflict_clause(table)
+    );
+}
+
+fn row_params(rows: &[Row], decimal_as_real: bool) -> impl rusqlite::Params + '_ {
+    return params_from_iter(rows.iter().flatten().map(move |value| SqliteParam {
+        value,
+        decimal_as_real,
+    }));
+}
+
+// Definition of a table following its name, names may be quoted and contain any chars
+fn skip_table_name(sql: &str) -> Option<&str> {
+    let rest = sql.strip_prefix("CREATE TABLE ")?.trim_start();
+    let rest = match rest.get(..14) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("IF NOT EXISTS ") => rest[14..].trim_start(),
+        _ => rest,
+    };
+    let close = match rest.chars().next()? {
+        '"' => '"',
+        '`' => '`',
+        '\'' => '\'',
+        '[' => ']',
+        _ => {
+            return rest
+                .find(|c: char| c == '(' || c.is_whitespace())
+                .map(|pos| &rest[pos..]);
+        }
+    };
+    let mut chars = rest.char_indices().skip(1).peekable();
+    while let Some((idx, c)) = chars.next() {
+        if c != close {
+            continue;
+        }
+        // Quotes are escaped by doubling them, except for brackets
+        if close != ']' && chars.peek().is_some_and(|(_, next)| *next == close) {
+            chars.next();
+            continue;
+        }
+        return Some(&rest[idx + 1..]);
+    }
+    return None;
+}
+
 impl DBInfoProvider for SqliteDB {
     fn get_table_info(&mut self, table: &str, no_count: bool) -> anyhow::Result<TableInfo> {
         let mut num_rows = None;
         if !no_count {
-            let query = format!("SELECT count(1) FROM {table}");
             num_rows = Some(
-                self.connection
-                    .query_row(&query, [], |row| row.get::<_, u32>(0))
-                    .context("Failed to get number of rows in the table")?
-                    .into(),
+                self.get_num_rows(table, None)
+                    .context("Failed to get number of rows in the table")?,
             );
         }
         let columns = self
             .get_columns(table)
             .context("Failed to get info about table columns")?;
+        let primary_key = self
+            .get_primary_key(table)
+            .context("Failed to get primary key of the table")?;
         return Ok(TableInfo {
             name: table.to_string(),
             num_rows,
             columns,
+            primary_key,
         });
     }
 
+    fn get_referenced_tables(&mut self, table: &str) -> anyhow::Result<Vec<String>> {
+        let mut stmt = self
+            .connection
+            .prepare("SELECT DISTINCT \"table\" FROM pragma_foreign_key_list(?)")
+            .context("Failed to create query for foreign keys")?;
+        let mut rows = stmt
+            .query([table])
+            .context("Failed to query foreign keys")?;
+        let mut tables = Vec::new();
+        while let Some(row) = rows.next().context("Failed to fetch foreign keys")? {
+            tables.push(row.get(0).context("Failed to parse table name")?);
+        }
+        return Ok(tables);
+    }
+
     fn get_tables(&mut self) -> anyhow::Result<Vec<String>> {
         let mut stmt = self
             .connection
@@ -152,12 +374,33 @@ impl Iterator for SqliteRowsIter<'_> {
 }
 
 impl DBReader for SqliteDB {
-    fn read_iter(&mut self, target_format: TableInfo) -> anyhow::Result<ReaderIterator<'_>> {
+    fn opt_clone(&self) -> anyhow::Result<Box<dyn DBReader>> {
+        return SqliteDB::new(&self.uri).map(|reader| Box::new(reader) as _);
+    }
+
+    fn start_snapshot(&mut self) -> anyhow::Result<()> {
+        // Deferred transaction starts reading only with the first query
+        self.connection
+            .execute_batch("BEGIN")
+            .context("Failed to start snapshot")?;
+        self.connection
+            .query_row("SELECT count(1) FROM sqlite_master", [], |_| Ok(()))
+            .context("Failed to start snapshot")?;
+        return Ok(());
+    }
+
+    fn read_iter(
+        &mut self,
+        target_format: TableInfo,
+        filter: ReadFilter,
+    ) -> anyhow::Result<ReaderIterator<'_>> {
         let query = format!(
-            "SELECT {} FROM {}",
-            target_format.column_names().join(", "),
-            target_format.name
+            "SELECT {} FROM {}{}",
+            QUOTE.quote_columns(&target_format),
+            QUOTE.quote(&target_format.name),
+            filter.to_sql(QUOTE, |_| String::from("?"))
         );
+        let params: Vec<Value> = filter.values().into_iter().cloned().collect();
         let stmt = self
             .connection
             .prepare(&query)
@@ -166,22 +409,255 @@ impl DBReader for SqliteDB {
             target_format,
             stmt,
             rows_builder: |stmt| {
-                return stmt.query([]).context("Failed to read rows");
+                return stmt
+                    .query(params_from_iter(params.iter()))
+                    .context("Failed to read rows");
             },
         }
         .try_build()?;
         return Ok(Box::new(iterator));
     }
+
+    fn count_rows(&mut self, table: &str, condition: &str) -> anyhow::Result<u64> {
+        return self.get_num_rows(table, Some(condition));
+    }
+
+    fn get_key_bounds(
+        &mut self,
+        table: &str,
+        column: &Column,
+    ) -> anyhow::Result<Option<(Value, Value)>> {
+        let query_bound = |order: &str| -> anyhow::Result<Option<Value>> {
+            let query = format!(
+                "SELECT {0} FROM {1} WHERE {0} IS NOT NULL ORDER BY {0} {order} LIMIT 1",
+                QUOTE.quote(&column.name),
+                QUOTE.quote(table)
+            );
+            let raw: Option<rusqlite::types::Value> = self
+                .connection
+                .query_row(&query, [], |row| row.get(0))
+                .optional()
+                .context("Failed to query key bounds")?;
+            return raw
+                .map(|raw| Value::try_from((column, ValueRef::from(&raw))))
+                .transpose();
+        };
+        let (Some(min), Some(max)) = (query_bound("ASC")?, query_bound("DESC")?) else {
+            return Ok(None);
+        };
+        return Ok(Some((min, max)));
+    }
 }
 
 impl DBWriter for SqliteDB {
     fn write_batch(&mut self, batch: &[Row], table: &TableInfo) -> Result<(), WriterError> {
         // SQLite is not network dependent, assume that all errors are Unrecoverable
         return self
-            .write_batch_impl(batch, &table.name)
+            .write_batch_impl(batch, table)
             .map_err(WriterError::Unrecoverable);
     }
 
+    fn set_write_mode(&mut self, mode: WriteMode) -> anyhow::Result<()> {
+        self.write_mode = mode;
+        return Ok(());
+    }
+
+    fn enable_fast_load(&mut self) -> anyhow::Result<()> {
+        self.connection
+            .execute_batch("PRAGMA foreign_keys = OFF")
+            .context("Failed to disable foreign key checks")?;
+        return Ok(());
+    }
+
+    fn drop_indexes(&mut self, table: &str) -> anyhow::Result<Vec<String>> {
+        let indexes = {
+            let mut stmt = self
+                .connection
+                .prepare(
+                    "SELECT m.name, m.sql FROM pragma_index_list(?) l
+                    JOIN sqlite_master m ON m.type = 'index' AND m.name = l.name
+                    WHERE l.\"unique\" = 0 AND l.origin = 'c' AND m.sql IS NOT NULL",
+                )
+                .context("Failed to create query for indexes")?;
+            stmt.query_map([table], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })
+            .context("Failed to query indexes")?
+            .collect::<Result<Vec<_>, _>>()
+            .context("Failed to fetch indexes")?
+        };
+        let mut dropped = Vec::with_capacity(indexes.len());
+        for (name, sql) in indexes {
+            let result = self
+                .connection
+                .execute_batch(&format!("DROP INDEX {}", QUOTE.quote(&name)))
+                .with_context(|| format!("Failed to drop index {name}"));
+            if let Err(err) = result {
+                self.create_indexes(&dropped)?;
+                return Err(err);
+            }
+            dropped.push(sql);
+        }
+        return Ok(dropped);
+    }
+
+    fn create_indexes(&mut self, indexes: &[String]) -> anyhow::Result<()> {
+        for index in indexes {
+            self.connection
+                .execute_batch(index)
+                .with_context(|| format!("Failed to create index: {index}"))?;
+        }
+        return Ok(());
+    }
+
+    fn delete_rows(&mut self, keys: &[Row], table: &TableInfo) -> anyhow::Result<()> {
+        let condition = table
+            .primary_key
+            .iter()
+            .map(|column| format!("{} = ?", QUOTE.quote(column)))
+            .collect::<Vec<_>>()
+            .join(" AND ");
+        let trx = self
+            .connection
+            .transaction()
+            .context("Failed to open transaction")?;
+        {
+            let mut stmt = trx
+                .prepare(&format!(
+                    "DELETE FROM {} WHERE {condition}",
+                    QUOTE.quote(&table.name)
+                ))
+                .context("Failed to create delete query")?;
+            for key in keys {
+                stmt.execute(params_from_iter(key.iter()))
+                    .context("Failed to delete rows")?;
+            }
+        }
+        trx.commit().context("Failed to commit")?;
+        return Ok(());
+    }
+
+    fn update_rows(&mut self, rows: &[Row], table: &TableInfo) -> anyhow::Result<()> {
+        let (updated, key) = table.update_positions()?;
+        if updated.is_empty() {
+            return Ok(());
+        }
+        let assignment = |idx: &usize| format!("{} = ?", QUOTE.quote(&table.columns[*idx].name));
+        let query = format!(
+            "UPDATE {} SET {} WHERE {}",
+            QUOTE.quote(&table.name),
+            updated
+                .iter()
+                .map(assignment)
+                .collect::<Vec<_>>()
+                .join(", "),
+            key.iter().map(assignment).collect::<Vec<_>>().join(" AND ")
+        );
+        let trx = self
+            .connection
+            .transaction()
+            .context("Failed to open transaction")?;
+        {
+            let mut stmt = trx
+                .prepare(&query)
+                .context("Failed to create update query")?;
+            for row in rows {
+                stmt.execute(params_from_iter(updated.iter().chain(&key).map(|idx| {
+                    SqliteParam {
+                        value: &row[*idx],
+                        decimal_as_real: self.decimal_as_real,
+                    }
+                })))
+                .context("Failed to update rows")?;
+            }
+        }
+        trx.commit().context("Failed to commit")?;
+        return Ok(());
+    }
+
+    fn create_staging_table(&mut self, table: &str, staging: &str) -> anyhow::Result<()> {
+        let sql: String = self
+            .connection
+            .query_row(
+                "SELECT sql FROM sqlite_master WHERE type = 'table' AND name = ?",
+                [table],
+                |row| row.get(0),
+            )
+            .context("Failed to get definition of table")?;
+        // Definition of columns follows the table name, indexes are created on swap
+        let columns = skip_table_name(&sql).context("Unexpected definition of table")?;
+        self.connection
+            .execute_batch(&format!("CREATE TABLE {}{columns}", QUOTE.quote(staging)))
+            .context("Failed to create staging table")?;
+        return Ok(());
+    }
+
+    fn swap_staging_table(&mut self, table: &str, staging: &str) -> anyhow::Result<()> {
+        let transaction = self
+            .connection
+            .transaction()
+            .context("Failed to open transaction")?;
+        let indexes = {
+            let mut stmt = transaction
+                .prepare(
+                    "SELECT sql FROM sqlite_master
+                    WHERE type = 'index' AND tbl_name = ? AND sql IS NOT NULL",
+                )
+                .context("Failed to create query for indexes")?;
+            stmt.query_map([table], |row| row.get::<_, String>(0))
+                .context("Failed to query indexes")?
+                .collect::<Result<Vec<_>, _>>()
+                .context("Failed to fetch indexes")?
+        };
+        transaction
+            .execute_batch(&format!(
+                "DROP TABLE {}; ALTER TABLE {} RENAME TO {}",
+                QUOTE.quote(table),
+                QUOTE.quote(staging),
+                QUOTE.quote(table)
+            ))
+            .context("Failed to replace table with staging table")?;
+        for index in indexes {
+            transaction
+                .execute_batch(&index)
+                .with_context(|| format!("Failed to create index: {index}"))?;
+        }
+        transaction.commit().context("Failed to commit")?;
+        return Ok(());
+    }
+
+    fn drop_staging_table(&mut self, staging: &str) -> anyhow::Result<()> {
+        self.connection
+            .execute_batch(&format!("DROP TABLE {}", QUOTE.quote(staging)))
+            .context("Failed to drop staging table")?;
+        return Ok(());
+    }
+
+    fn create_table(&mut self, table: &TableInfo) -> anyhow::Result<()> {
+        let mut columns = Vec::with_capacity(table.columns.len());
+        for column in &table.columns {
+            let not_null = if column.nullable { "" } else { " NOT NULL" };
+            columns.push(format!(
+                "{} {}{not_null}",
+                QUOTE.quote(&column.name),
+                match &column.column_type {
+                    ColumnType::Decimal if self.decimal_as_real => "decimal",
+                    column_type => column_type.to_sqlite_type()?,
+                }
+            ));
+        }
+        let query = format!(
+            "CREATE TABLE {} ({})",
+            QUOTE.quote(&table.name),
+            columns.join(", ")
+        );
+        debug!("Creating table: {query}");
+        self.connection
+            .execute(&query, [])
+            .context("Failed to create table in sqlite")?;
+        return Ok(());
+    }
+
     fn recover(&mut self) -> anyhow::Result<()> {
         return Ok(());
     }
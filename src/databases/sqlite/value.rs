@@ -1,8 +1,42 @@
+use std::str::FromStr;
+
 use crate::databases::table::{Column, ColumnType, Value};
+use anyhow::Context;
 use rusqlite::{
     ToSql,
     types::{FromSql, ToSqlOutput, ValueRef},
 };
+use rust_decimal::Decimal;
+use rust_decimal::prelude::ToPrimitive;
+
+impl ColumnType {
+    pub fn to_sqlite_type(&self) -> anyhow::Result<&'static str> {
+        let type_name = match self {
+            ColumnType::I64 => "bigint",
+            ColumnType::I32 => "integer",
+            ColumnType::I16 => "smallint",
+            ColumnType::F64 => "double",
+            ColumnType::F32 => "real",
+            ColumnType::Bool => "boolean",
+            ColumnType::String => "text",
+            ColumnType::Bytes => "blob",
+            ColumnType::Timestamptz => "timestamptz",
+            ColumnType::Timestamp => "timestamp",
+            ColumnType::Date => "date",
+            ColumnType::Time => "time",
+            ColumnType::Json => "json",
+            ColumnType::Uuid => "uuid",
+            // Decimal columns have numeric affinity, text affinity keeps all digits
+            ColumnType::Decimal => "decimal text",
+            ColumnType::Array(_) => {
+                return Err(anyhow::anyhow!(
+                    "SQLite doesn't support arrays, use --arrays-as-json to write them as JSON"
+                ));
+            }
+        };
+        return Ok(type_name);
+    }
+}
 
 impl TryFrom<(&Column, ValueRef<'_>)> for Value {
     type Error = anyhow::Error;
@@ -12,7 +46,7 @@ impl TryFrom<(&Column, ValueRef<'_>)> for Value {
         if val == ValueRef::Null {
             return Ok(Value::Null);
         }
-        let parsed = match column.column_type {
+        let parsed = match &column.column_type {
             ColumnType::I64 => Value::I64(FromSql::column_result(val)?),
             ColumnType::I32 => Value::I32(FromSql::column_result(val)?),
             ColumnType::I16 => Value::I16(FromSql::column_result(val)?),
@@ -30,14 +64,32 @@ impl TryFrom<(&Column, ValueRef<'_>)> for Value {
             ColumnType::Time => Value::Time(FromSql::column_result(val)?),
             ColumnType::Json => Value::Json(FromSql::column_result(val)?),
             ColumnType::Uuid => Value::Uuid(FromSql::column_result(val)?),
-            ColumnType::Decimal => {
-                return Err(anyhow::anyhow!("Decimal is not supported for sqlite"));
+            ColumnType::Decimal => Value::Decimal(decimal_from_sql(val)?),
+            ColumnType::Array(_) => {
+                return Err(anyhow::anyhow!("SQLite doesn't support arrays"));
             }
         };
         return Ok(parsed);
     }
 }
 
+// Numeric affinity stores decimals as integers or reals when it doesn't lose digits
+fn decimal_from_sql(val: ValueRef<'_>) -> anyhow::Result<Decimal> {
+    return match val {
+        ValueRef::Integer(val) => Ok(Decimal::from(val)),
+        ValueRef::Real(val) => {
+            Decimal::try_from(val).with_context(|| format!("Invalid decimal {val}"))
+        }
+        ValueRef::Text(val) => {
+            let val = std::str::from_utf8(val)?;
+            Decimal::from_str(val)
+                .or_else(|_| Decimal::from_scientific(val))
+                .with_context(|| format!("Invalid decimal {val}"))
+        }
+        _ => Err(anyhow::anyhow!("Invalid decimal {val:?}")),
+    };
+}
+
 impl ToSql for Value {
     fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
         match self {
@@ -56,11 +108,34 @@ impl ToSql for Value {
             Value::Time(val) => val.to_sql(),
             Value::Json(val) => val.to_sql(),
             Value::Uuid(val) => val.to_sql(),
-            Value::Decimal(_) => {
-                return Err(rusqlite::Error::ToSqlConversionFailure(
-                    anyhow::anyhow!("Decimal is not supported for sqlite").into(),
-                ));
+            Value::Decimal(val) => Ok(ToSqlOutput::from(val.to_string())),
+            Value::Array(items) => {
+                let json = Value::array_to_json(items)
+                    .map_err(|err| rusqlite::Error::ToSqlConversionFailure(err.into()))?;
+                Ok(ToSqlOutput::from(json.to_string()))
             }
         }
     }
 }
+
+/// Written value, decimals are stored as REAL instead of TEXT if `decimal_as_real` is set
+pub(super) struct SqliteParam<'a> {
+    pub value: &'a Value,
+    pub decimal_as_real: bool,
+}
+
+impl ToSql for SqliteParam<'_> {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        return match self.value {
+            Value::Decimal(val) if self.decimal_as_real => {
+                let val = val.to_f64().ok_or_else(|| {
+                    rusqlite::Error::ToSqlConversionFailure(
+                        anyhow::anyhow!("Decimal {val} doesn't fit into REAL").into(),
+                    )
+                })?;
+                Ok(ToSqlOutput::from(val))
+            }
+            value => value.to_sql(),
+        };
+    }
+}
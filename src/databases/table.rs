@@ -1,7 +1,11 @@
+use anyhow::Context;
 use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
 use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 
+use super::identifier::IdentifierQuote;
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum Value {
     String(String),
@@ -19,12 +23,69 @@ pub enum Value {
     Time(NaiveTime),
     Json(serde_json::Value),
     Uuid(uuid::Uuid),
+    // One dimensional array, elements may be NULL
+    Array(Vec<Value>),
     Null,
 }
 
+impl Value {
+    pub fn column_type(&self) -> Option<ColumnType> {
+        let column_type = match self {
+            Value::String(_) => ColumnType::String,
+            Value::Bytes(_) => ColumnType::Bytes,
+            Value::I64(_) => ColumnType::I64,
+            Value::I32(_) => ColumnType::I32,
+            Value::I16(_) => ColumnType::I16,
+            Value::F64(_) => ColumnType::F64,
+            Value::F32(_) => ColumnType::F32,
+            Value::Decimal(_) => ColumnType::Decimal,
+            Value::Bool(_) => ColumnType::Bool,
+            Value::Timestamptz(_) => ColumnType::Timestamptz,
+            Value::Timestamp(_) => ColumnType::Timestamp,
+            Value::Date(_) => ColumnType::Date,
+            Value::Time(_) => ColumnType::Time,
+            Value::Json(_) => ColumnType::Json,
+            Value::Uuid(_) => ColumnType::Uuid,
+            // Type of an array is known from its first non-NULL element
+            Value::Array(items) => {
+                ColumnType::Array(Box::new(items.iter().find_map(Value::column_type)?))
+            }
+            Value::Null => return None,
+        };
+        return Some(column_type);
+    }
+
+    /// Array as JSON, numbers and bools are kept, other elements are strings in the CSV format
+    pub fn array_to_json(items: &[Value]) -> anyhow::Result<serde_json::Value> {
+        let number = |val: f64| {
+            return serde_json::Number::from_f64(val)
+                .map_or_else(|| serde_json::Value::String(val.to_string()), Into::into);
+        };
+        return items
+            .iter()
+            .map(|item| {
+                let json = match item {
+                    Value::Null => serde_json::Value::Null,
+                    &Value::I64(val) => val.into(),
+                    &Value::I32(val) => val.into(),
+                    &Value::I16(val) => val.into(),
+                    &Value::F64(val) => number(val),
+                    &Value::F32(val) => number(val.into()),
+                    &Value::Bool(val) => val.into(),
+                    Value::String(val) => val.clone().into(),
+                    Value::Json(val) => val.clone(),
+                    Value::Array(items) => Value::array_to_json(items)?,
+                    other => other.to_csv_field()?.into_owned().into(),
+                };
+                return Ok(json);
+            })
+            .collect();
+    }
+}
+
 pub type Row = Vec<Value>;
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum ColumnType {
     String,
     Bytes,
@@ -41,6 +102,7 @@ pub enum ColumnType {
     Time,
     Uuid,
     Json,
+    Array(Box<ColumnType>),
 }
 
 impl FromStr for ColumnType {
@@ -55,6 +117,9 @@ impl FromStr for ColumnType {
         {
             return Ok(ColumnType::String);
         }
+        if formated.starts_with("decimal") || formated.starts_with("numeric") {
+            return Ok(ColumnType::Decimal);
+        }
         return match formated.as_str() {
             "tinyint" | "smallint" | "smallserial" => Ok(ColumnType::I16),
             "integer" | "serial" | "int" => Ok(ColumnType::I32),
@@ -77,7 +142,7 @@ impl FromStr for ColumnType {
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Column {
     pub name: String,
     pub column_type: ColumnType,
@@ -89,10 +154,377 @@ pub struct TableInfo {
     pub name: String,
     pub num_rows: Option<u64>,
     pub columns: Vec<Column>,
+    pub primary_key: Vec<String>,
 }
 
 impl TableInfo {
     pub fn column_names(&self) -> Vec<&str> {
         return self.columns.iter().map(|c| c.name.as_str()).collect();
     }
+
+    pub fn column(&self, name: &str) -> Option<&Column> {
+        return self.columns.iter().find(|c| c.name == name);
+    }
+
+    // Positions of updated columns and of primary key columns, parameters of updates
+    pub fn update_positions(&self) -> anyhow::Result<(Vec<usize>, Vec<usize>)> {
+        let key = self
+            .primary_key
+            .iter()
+            .map(|name| {
+                self.columns
+                    .iter()
+                    .position(|column| &column.name == name)
+                    .with_context(|| format!("Key column {name} not found"))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let updated = (0..self.columns.len())
+            .filter(|idx| !key.contains(idx))
+            .collect();
+        return Ok((updated, key));
+    }
+}
+
+// Splits a name qualified with a schema (PostgreSQL) or a database (MySQL),
+// only used for databases with schemas
+pub fn split_table_name(table: &str) -> (Option<&str>, &str) {
+    return match table.split_once('.') {
+        Some((schema, name)) => (Some(schema), name),
+        None => (None, table),
+    };
+}
+
+/// Names of a table in input and output databases, parsed from `SOURCE[:DESTINATION]`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TableMapping {
+    pub source: String,
+    pub destination: String,
+}
+
+impl TableMapping {
+    pub fn new(table: &str) -> Self {
+        return Self {
+            source: table.to_string(),
+            destination: table.to_string(),
+        };
+    }
+
+    // Same table written into a staging table next to the destination
+    pub fn staging(&self) -> Self {
+        return Self {
+            source: self.source.clone(),
+            destination: format!("{}_db_mover_staging", self.destination),
+        };
+    }
+}
+
+impl FromStr for TableMapping {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (source, destination) = s.split_once(':').unwrap_or((s, s));
+        let (source, destination) = (source.trim(), destination.trim());
+        if source.is_empty() || destination.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Expected TABLE or SOURCE:DESTINATION, got \"{s}\""
+            ));
+        }
+        return Ok(Self {
+            source: source.to_string(),
+            destination: destination.to_string(),
+        });
+    }
+}
+
+impl std::fmt::Display for TableMapping {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.source == self.destination {
+            return write!(f, "{}", self.source);
+        }
+        return write!(f, "{} -> {}", self.source, self.destination);
+    }
+}
+
+// Order preserving mapping of integer and uuid keys into u128
+fn key_to_u128(value: &Value) -> Option<u128> {
+    const SIGN: u128 = 1 << 127;
+    return match value {
+        &Value::I64(val) => Some(val as i128 as u128 ^ SIGN),
+        &Value::I32(val) => Some(val as i128 as u128 ^ SIGN),
+        &Value::I16(val) => Some(val as i128 as u128 ^ SIGN),
+        Value::Uuid(val) => Some(val.as_u128()),
+        _ => None,
+    };
+}
+
+fn key_from_u128(key: u128, like: &Value) -> Value {
+    const SIGN: u128 = 1 << 127;
+    let int = (key ^ SIGN) as i128;
+    // Keys are always between bounds of the same type, so casts are lossless
+    return match like {
+        Value::I64(_) => Value::I64(int as i64),
+        Value::I32(_) => Value::I32(int as i32),
+        Value::I16(_) => Value::I16(int as i16),
+        Value::Uuid(_) => Value::Uuid(uuid::Uuid::from_u128(key)),
+        _ => unreachable!("Only integer and uuid keys are supported"),
+    };
+}
+
+/// Range of rows with `column > after AND column <= up_to`, unset bound means unbounded.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyRange {
+    pub column: String,
+    pub after: Option<Value>,
+    pub up_to: Option<Value>,
+}
+
+impl KeyRange {
+    pub fn is_supported_key(column_type: &ColumnType) -> bool {
+        return matches!(
+            column_type,
+            ColumnType::I64 | ColumnType::I32 | ColumnType::I16 | ColumnType::Uuid
+        );
+    }
+
+    /// Split keys between `min` and `max` (inclusive) into at most `parts` ranges
+    /// covering the whole key space.
+    pub fn split(
+        column: &str,
+        min: &Value,
+        max: &Value,
+        parts: usize,
+    ) -> anyhow::Result<Vec<Self>> {
+        let (Some(low), Some(high)) = (key_to_u128(min), key_to_u128(max)) else {
+            return Err(anyhow::anyhow!(
+                "Only integer and uuid keys can be split, got {min:?} and {max:?}"
+            ));
+        };
+        if min.column_type() != max.column_type() || low > high {
+            return Err(anyhow::anyhow!("Invalid key bounds {min:?} and {max:?}"));
+        }
+        let step = (high - low) / parts.max(1) as u128;
+        let mut boundaries: Vec<Value> = Vec::new();
+        if step > 0 {
+            for i in 1..parts as u128 {
+                boundaries.push(key_from_u128(low + step * i, min));
+            }
+        }
+        let mut ranges = Vec::with_capacity(boundaries.len() + 1);
+        let mut after = None;
+        for boundary in boundaries {
+            ranges.push(KeyRange {
+                column: column.to_string(),
+                after,
+                up_to: Some(boundary.clone()),
+            });
+            after = Some(boundary);
+        }
+        ranges.push(KeyRange {
+            column: column.to_string(),
+            after,
+            up_to: None,
+        });
+        return Ok(ranges);
+    }
+
+    pub fn values(&self) -> Vec<&Value> {
+        return self.after.iter().chain(self.up_to.iter()).collect();
+    }
+
+    /// SQL condition, placeholder produces parameter for n-th value
+    pub fn condition(
+        &self,
+        quote: IdentifierQuote,
+        placeholder: impl Fn(usize) -> String,
+    ) -> String {
+        let column = quote.quote(&self.column);
+        let mut conditions = Vec::new();
+        if self.after.is_some() {
+            conditions.push(format!("{column} > {}", placeholder(conditions.len())));
+        }
+        if self.up_to.is_some() {
+            conditions.push(format!("{column} <= {}", placeholder(conditions.len())));
+        }
+        if conditions.is_empty() {
+            return String::from("1 = 1");
+        }
+        return conditions.join(" AND ");
+    }
+}
+
+/// Rows to read: key range and raw SQL condition, both optional
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReadFilter {
+    pub key_range: Option<KeyRange>,
+    pub condition: Option<String>,
+}
+
+impl ReadFilter {
+    pub fn values(&self) -> Vec<&Value> {
+        return self
+            .key_range
+            .as_ref()
+            .map(KeyRange::values)
+            .unwrap_or_default();
+    }
+
+    /// WHERE and ORDER BY part of a select query, rows of a key range are ordered by the key
+    pub fn to_sql(&self, quote: IdentifierQuote, placeholder: impl Fn(usize) -> String) -> String {
+        let mut conditions = Vec::new();
+        if let Some(condition) = &self.condition {
+            conditions.push(format!("({condition})"));
+        }
+        if let Some(key_range) = &self.key_range {
+            conditions.push(key_range.condition(quote, &placeholder));
+        }
+        let mut sql = String::new();
+        if !conditions.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&conditions.join(" AND "));
+        }
+        if let Some(key_range) = &self.key_range {
+            sql.push_str(" ORDER BY ");
+            sql.push_str(&quote.quote(&key_range.column));
+        }
+        return sql;
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ChangeKind {
+    // Row holds values of inserted columns
+    Insert,
+    // Row holds values of the key and of changed columns
+    Update,
+    // Row holds values of the key columns
+    Delete,
+}
+
+/// Row changed in the input database, values are in order of `columns`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Change {
+    // Qualified only for tables outside of the current schema
+    pub table: String,
+    pub kind: ChangeKind,
+    pub columns: Vec<String>,
+    pub row: Row,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_positions() {
+        let column = |name: &str| Column {
+            name: name.to_string(),
+            column_type: ColumnType::I64,
+            nullable: false,
+        };
+        let mut table = TableInfo {
+            name: "test".to_string(),
+            num_rows: None,
+            columns: vec![column("a"), column("id"), column("b")],
+            primary_key: vec!["id".to_string()],
+        };
+        assert_eq!(table.update_positions().unwrap(), (vec![0, 2], vec![1]));
+        table.primary_key.push("missing".to_string());
+        assert!(table.update_positions().is_err());
+    }
+
+    #[test]
+    fn test_key_range_split_integers() {
+        let ranges = KeyRange::split("id", &Value::I64(1), &Value::I64(100), 4).unwrap();
+        assert_eq!(ranges.len(), 4);
+        assert_eq!(ranges[0].after, None);
+        assert_eq!(ranges[0].up_to, Some(Value::I64(25)));
+        assert_eq!(ranges[1].after, Some(Value::I64(25)));
+        assert_eq!(ranges[1].up_to, Some(Value::I64(49)));
+        assert_eq!(ranges[3].after, Some(Value::I64(73)));
+        assert_eq!(ranges[3].up_to, None);
+    }
+
+    #[test]
+    fn test_key_range_split_negative() {
+        let ranges = KeyRange::split("id", &Value::I32(-10), &Value::I32(10), 2).unwrap();
+        assert_eq!(ranges.len(), 2);
+        assert_eq!(ranges[0].up_to, Some(Value::I32(0)));
+        assert_eq!(ranges[1].after, Some(Value::I32(0)));
+    }
+
+    #[test]
+    fn test_key_range_split_too_small() {
+        let ranges = KeyRange::split("id", &Value::I16(5), &Value::I16(5), 4).unwrap();
+        assert_eq!(ranges.len(), 1);
+        assert_eq!(
+            ranges[0].condition(IdentifierQuote::ANSI, |_| "?".to_string()),
+            "1 = 1"
+        );
+    }
+
+    #[test]
+    fn test_key_range_split_uuid() {
+        let min = Value::Uuid(uuid::Uuid::nil());
+        let max = Value::Uuid(uuid::Uuid::max());
+        let ranges = KeyRange::split("id", &min, &max, 2).unwrap();
+        assert_eq!(ranges.len(), 2);
+        assert_eq!(
+            ranges[0].up_to,
+            Some(Value::Uuid(uuid::Uuid::from_u128(u128::MAX / 2)))
+        );
+    }
+
+    #[test]
+    fn test_read_filter_to_sql() {
+        let quote = IdentifierQuote::ANSI;
+        assert_eq!(ReadFilter::default().to_sql(quote, |_| "?".to_string()), "");
+        let filter = ReadFilter {
+            key_range: Some(KeyRange {
+                column: "id".to_string(),
+                after: Some(Value::I64(1)),
+                up_to: Some(Value::I64(10)),
+            }),
+            condition: Some("a = 1 OR b = 2".to_string()),
+        };
+        assert_eq!(
+            filter.to_sql(quote, |idx| format!("${}", idx + 1)),
+            " WHERE (a = 1 OR b = 2) AND \"id\" > $1 AND \"id\" <= $2 ORDER BY \"id\""
+        );
+    }
+
+    #[test]
+    fn test_table_mapping() {
+        let mapping: TableMapping = "src.orders:dst.orders_archive".parse().unwrap();
+        assert_eq!(mapping.source, "src.orders");
+        assert_eq!(mapping.destination, "dst.orders_archive");
+        assert_eq!(
+            split_table_name(&mapping.destination),
+            (Some("dst"), "orders_archive")
+        );
+        assert_eq!(split_table_name("orders"), (None, "orders"));
+        assert_eq!(
+            "orders".parse::<TableMapping>().unwrap(),
+            TableMapping::new("orders")
+        );
+        assert!("orders:".parse::<TableMapping>().is_err());
+    }
+
+    #[test]
+    fn test_key_range_split_unsupported() {
+        let result = KeyRange::split("id", &Value::Bool(false), &Value::Bool(true), 2);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_key_range_condition() {
+        let range = KeyRange {
+            column: "id".to_string(),
+            after: Some(Value::I64(1)),
+            up_to: Some(Value::I64(2)),
+        };
+        assert_eq!(
+            range.condition(IdentifierQuote::MYSQL, |i| format!("${}", i + 1)),
+            "`id` > $1 AND `id` <= $2"
+        );
+    }
 }
@@ -4,18 +4,71 @@ use tracing::error;
 
 use crate::retry::ExponentialRetry;
 
-use super::table::{Row, TableInfo};
+use super::table::{Change, Column, ReadFilter, Row, TableInfo, Value};
+use super::write_mode::WriteMode;
 
 pub trait DBInfoProvider: Send {
     fn get_tables(&mut self) -> anyhow::Result<Vec<String>>;
 
+    // Tables of the schema (PostgreSQL) or the database (MySQL), qualified with its name
+    fn get_schema_tables(&mut self, _schema: &str) -> anyhow::Result<Vec<String>> {
+        return Err(anyhow::anyhow!(
+            "This type of databases doesn't support qualified table names"
+        ));
+    }
+
     fn get_table_info(&mut self, table: &str, no_count: bool) -> anyhow::Result<TableInfo>;
+
+    // Tables referenced by foreign keys of the table, databases without constraints have none
+    fn get_referenced_tables(&mut self, _table: &str) -> anyhow::Result<Vec<String>> {
+        return Ok(Vec::new());
+    }
 }
 
 pub type ReaderIterator<'a> = Box<dyn Iterator<Item = anyhow::Result<Row>> + 'a>;
 
 pub trait DBReader: Send + DBInfoProvider {
-    fn read_iter(&mut self, target_format: TableInfo) -> anyhow::Result<ReaderIterator<'_>>;
+    fn opt_clone(&self) -> anyhow::Result<Box<dyn DBReader>> {
+        return Err(anyhow::anyhow!(
+            "This type of databases doesn't support mutiple readers"
+        ));
+    }
+
+    fn read_iter(
+        &mut self,
+        target_format: TableInfo,
+        filter: ReadFilter,
+    ) -> anyhow::Result<ReaderIterator<'_>>;
+
+    // Start a read transaction, so following reads see the database at the same point in time
+    fn start_snapshot(&mut self) -> anyhow::Result<()> {
+        return Err(anyhow::anyhow!(
+            "This type of databases doesn't support consistent snapshots"
+        ));
+    }
+
+    // Whether readers cloned after the snapshot is started read the same snapshot
+    fn is_snapshot_shared(&self) -> bool {
+        return false;
+    }
+
+    // Number of rows matching the SQL condition
+    fn count_rows(&mut self, _table: &str, _condition: &str) -> anyhow::Result<u64> {
+        return Err(anyhow::anyhow!(
+            "This type of databases doesn't support filtering rows"
+        ));
+    }
+
+    // Smallest and largest values of the column, None if the table is empty
+    fn get_key_bounds(
+        &mut self,
+        _table: &str,
+        _column: &Column,
+    ) -> anyhow::Result<Option<(Value, Value)>> {
+        return Err(anyhow::anyhow!(
+            "This type of databases doesn't support reading by key ranges"
+        ));
+    }
 }
 
 #[derive(Error, Debug)]
@@ -41,6 +94,77 @@ pub trait DBWriter: Send + DBInfoProvider {
 
     fn write_batch(&mut self, batch: &[Row], table: &TableInfo) -> Result<(), WriterError>;
 
+    // Delete rows by values of primary key columns of the table, in order of the key
+    fn delete_rows(&mut self, _keys: &[Row], _table: &TableInfo) -> anyhow::Result<()> {
+        return Err(anyhow::anyhow!(
+            "This type of databases doesn't support deleting rows"
+        ));
+    }
+
+    // Update rows found by values of primary key columns, other columns of the table are set
+    fn update_rows(&mut self, _rows: &[Row], _table: &TableInfo) -> anyhow::Result<()> {
+        return Err(anyhow::anyhow!(
+            "This type of databases doesn't support updating rows"
+        ));
+    }
+
+    // Create a table with native types matching the given table info
+    fn create_table(&mut self, table: &TableInfo) -> anyhow::Result<()>;
+
+    fn set_write_mode(&mut self, mode: WriteMode) -> anyhow::Result<()> {
+        if mode != WriteMode::Insert {
+            return Err(anyhow::anyhow!(
+                "This type of databases doesn't support write mode {mode:?}"
+            ));
+        }
+        return Ok(());
+    }
+
+    // Disable triggers and foreign key checks for this and cloned connections
+    fn enable_fast_load(&mut self) -> anyhow::Result<()> {
+        return Err(anyhow::anyhow!(
+            "This type of databases doesn't support fast load"
+        ));
+    }
+
+    // Drop non-unique secondary indexes of the table, returns statements to recreate them
+    fn drop_indexes(&mut self, _table: &str) -> anyhow::Result<Vec<String>> {
+        return Err(anyhow::anyhow!(
+            "This type of databases doesn't support dropping indexes"
+        ));
+    }
+
+    fn create_indexes(&mut self, _indexes: &[String]) -> anyhow::Result<()> {
+        return Err(anyhow::anyhow!(
+            "This type of databases doesn't support dropping indexes"
+        ));
+    }
+
+    // Create an empty table with the structure of the given one, to be swapped with it later
+    fn create_staging_table(&mut self, _table: &str, _staging: &str) -> anyhow::Result<()> {
+        return Err(anyhow::anyhow!(
+            "This type of databases doesn't support atomic load"
+        ));
+    }
+
+    // Replace the table with the staging table at once
+    fn swap_staging_table(&mut self, _table: &str, _staging: &str) -> anyhow::Result<()> {
+        return Err(anyhow::anyhow!(
+            "This type of databases doesn't support atomic load"
+        ));
+    }
+
+    fn drop_staging_table(&mut self, _staging: &str) -> anyhow::Result<()> {
+        return Err(anyhow::anyhow!(
+            "This type of databases doesn't support atomic load"
+        ));
+    }
+
+    // Move sequences of the table past the largest written value, databases without sequences have none
+    fn reset_sequences(&mut self, _table: &str) -> anyhow::Result<()> {
+        return Ok(());
+    }
+
     fn write_batch_with_retry(
         &mut self,
         batch: &[Row],
@@ -80,6 +204,20 @@ pub trait DBWriter: Send + DBInfoProvider {
     fn recover(&mut self) -> anyhow::Result<()>;
 }
 
+/// Changes of input tables after a saved position, like a replication slot or a binlog
+pub trait ChangeSource: Send {
+    /// Changes not confirmed yet, whole transactions up to about `limit` changes
+    fn poll(&mut self, limit: usize) -> anyhow::Result<Vec<Change>>;
+
+    /// Saves the position after polled changes, they are not returned again
+    fn confirm(&mut self) -> anyhow::Result<()>;
+
+    // Removes the saved position, used when tables failed to move
+    fn discard(self: Box<Self>) -> anyhow::Result<()> {
+        return Ok(());
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::time::Duration;
@@ -100,6 +238,7 @@ mod tests {
         }
         impl DBWriter for DB {
             fn write_batch(&mut self, batch: &[Row], table: &TableInfo) -> Result<(), WriterError>;
+            fn create_table(&mut self, table: &TableInfo) -> anyhow::Result<()>;
             fn recover(&mut self) -> anyhow::Result<()>;
         }
     }
@@ -109,6 +248,7 @@ mod tests {
             name: "test".to_string(),
             columns: Vec::new(),
             num_rows: None,
+            primary_key: Vec::new(),
         };
     }
 
@@ -0,0 +1,160 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hasher};
+
+use super::identifier::IdentifierQuote;
+use super::table::{Row, TableInfo};
+use crate::verify::hash_value;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum)]
+pub enum WriteMode {
+    /// Plain insert, destination table should be empty
+    #[default]
+    Insert,
+    /// Update rows with the same primary key
+    Upsert,
+    /// Skip rows conflicting with existing ones
+    IgnoreDuplicates,
+}
+
+impl WriteMode {
+    // Columns updated on conflict, all except the primary key
+    pub(crate) fn update_columns(table: &TableInfo) -> Vec<&str> {
+        return table
+            .column_names()
+            .into_iter()
+            .filter(|name| !table.primary_key.iter().any(|key| key == name))
+            .collect();
+    }
+
+    // Rows of the batch without earlier rows of the same primary key, as a single
+    // statement can't update a row twice
+    pub(crate) fn last_rows_by_key<'a>(batch: &'a [Row], table: &TableInfo) -> Cow<'a, [Row]> {
+        let key_positions: Vec<usize> = table
+            .primary_key
+            .iter()
+            .filter_map(|key| table.columns.iter().position(|column| &column.name == key))
+            .collect();
+        if key_positions.is_empty() {
+            return Cow::Borrowed(batch);
+        }
+        let key_hash = |row: &Row| {
+            let mut hasher = DefaultHasher::new();
+            for &idx in &key_positions {
+                hash_value(&row[idx], &mut hasher);
+            }
+            return hasher.finish();
+        };
+        let same_key = |a: &Row, b: &Row| key_positions.iter().all(|&idx| a[idx] == b[idx]);
+        // Positions of the last rows of keys, grouped by hashes of keys
+        let mut last_rows: HashMap<u64, Vec<usize>> = HashMap::new();
+        let mut has_duplicates = false;
+        for (idx, row) in batch.iter().enumerate() {
+            let rows = last_rows.entry(key_hash(row)).or_default();
+            match rows.iter_mut().find(|last| same_key(&batch[**last], row)) {
+                Some(last) => {
+                    *last = idx;
+                    has_duplicates = true;
+                }
+                None => rows.push(idx),
+            }
+        }
+        if !has_duplicates {
+            return Cow::Borrowed(batch);
+        }
+        let mut positions: Vec<usize> = last_rows.into_values().flatten().collect();
+        positions.sort_unstable();
+        return Cow::Owned(
+            positions
+                .into_iter()
+                .map(|idx| batch[idx].clone())
+                .collect(),
+        );
+    }
+
+    // ON CONFLICT clause shared by PostgreSQL and SQLite
+    pub(crate) fn on_conflict_clause(&self, table: &TableInfo) -> String {
+        let quote = |name: &str| IdentifierQuote::ANSI.quote(name);
+        let update_columns = Self::update_columns(table);
+        return match self {
+            WriteMode::Insert => String::new(),
+            WriteMode::Upsert if !update_columns.is_empty() => format!(
+                " ON CONFLICT ({}) DO UPDATE SET {}",
+                table
+                    .primary_key
+                    .iter()
+                    .map(|key| quote(key))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                update_columns
+                    .iter()
+                    .map(|name| format!("{0} = excluded.{0}", quote(name)))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            WriteMode::Upsert | WriteMode::IgnoreDuplicates => {
+                String::from(" ON CONFLICT DO NOTHING")
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::databases::table::{Column, ColumnType, Value};
+
+    fn make_table_info(primary_key: &[&str]) -> TableInfo {
+        let column = |name: &str| Column {
+            name: name.to_string(),
+            column_type: ColumnType::I64,
+            nullable: false,
+        };
+        return TableInfo {
+            name: "test".to_string(),
+            num_rows: None,
+            columns: vec![column("id"), column("a"), column("b")],
+            primary_key: primary_key.iter().map(|key| key.to_string()).collect(),
+        };
+    }
+
+    #[test]
+    fn test_on_conflict_clause() {
+        let table = make_table_info(&["id"]);
+        assert_eq!(WriteMode::Insert.on_conflict_clause(&table), "");
+        assert_eq!(
+            WriteMode::IgnoreDuplicates.on_conflict_clause(&table),
+            " ON CONFLICT DO NOTHING"
+        );
+        assert_eq!(
+            WriteMode::Upsert.on_conflict_clause(&table),
+            r#" ON CONFLICT ("id") DO UPDATE SET "a" = excluded."a", "b" = excluded."b""#
+        );
+    }
+
+    #[test]
+    fn test_on_conflict_clause_only_key_columns() {
+        let table = make_table_info(&["id", "a", "b"]);
+        assert_eq!(
+            WriteMode::Upsert.on_conflict_clause(&table),
+            " ON CONFLICT DO NOTHING"
+        );
+    }
+
+    #[test]
+    fn test_last_rows_by_key() {
+        let table = make_table_info(&["id", "a"]);
+        let row = |id, a, b| vec![Value::I64(id), Value::I64(a), Value::I64(b)];
+        let batch = vec![row(1, 1, 1), row(1, 2, 2), row(2, 1, 3)];
+        assert!(matches!(
+            WriteMode::last_rows_by_key(&batch, &table),
+            Cow::Borrowed(_)
+        ));
+
+        let batch = vec![row(1, 1, 1), row(2, 1, 2), row(1, 1, 3), row(1, 1, 4)];
+        assert_eq!(
+            WriteMode::last_rows_by_key(&batch, &table).into_owned(),
+            vec![row(2, 1, 2), row(1, 1, 4)]
+        );
+    }
+}
@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use anyhow::Context;
+use tracing::{debug, info};
+
+use crate::args::Args;
+use crate::column_mapping::ColumnMapping;
+use crate::config::Config;
+use crate::databases::table::{Change, ChangeKind, Row, TableInfo, TableMapping};
+use crate::databases::traits::{ChangeSource, DBWriter};
+use crate::databases::write_mode::WriteMode;
+use crate::retry::ExponentialRetry;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+// Consecutive changes of the same kind and columns, written at once
+struct Batch {
+    table: TableInfo,
+    kind: ChangeKind,
+    rows: Vec<Row>,
+}
+
+impl Batch {
+    fn accepts(&self, table: &TableInfo, kind: &ChangeKind) -> bool {
+        return self.kind == *kind
+            && self.table.name == table.name
+            && self.table.columns == table.columns;
+    }
+
+    fn write(&self, writer: &mut dyn DBWriter, retries: usize) -> anyhow::Result<()> {
+        return match self.kind {
+            // Inserted rows may be moved already, so they are upserted
+            ChangeKind::Insert => writer.write_batch_with_retry(
+                &self.rows,
+                &self.table,
+                ExponentialRetry::new(retries),
+            ),
+            // Columns missing in the change keep their values
+            ChangeKind::Update => writer.update_rows(&self.rows, &self.table),
+            ChangeKind::Delete => writer.delete_rows(&self.rows, &self.table),
+        }
+        .with_context(|| format!("Failed to apply changes of table \"{}\"", self.table.name));
+    }
+}
+
+/// Applies changes of the input database to output tables until `stop` is set.
+/// Columns are mapped like moved rows, changes of other tables are skipped.
+pub fn follow(
+    args: &Args,
+    config: &Config,
+    tables: &[TableMapping],
+    mut source: Box<dyn ChangeSource>,
+    stop: &AtomicBool,
+) -> anyhow::Result<()> {
+    let tables: HashMap<&str, &TableMapping> = tables
+        .iter()
+        .map(|table| (table.source.as_str(), table))
+        .collect();
+    let mut reader = args.create_reader()?;
+    let mut writer = args.create_writer()?;
+    writer.set_write_mode(WriteMode::Upsert)?;
+    let mut mappings: HashMap<String, ColumnMapping> = HashMap::new();
+    info!("Following changes of the input database");
+    while !stop.load(Ordering::Relaxed) {
+        let changes = source.poll(args.batch_write_size)?;
+        if changes.is_empty() {
+            std::thread::sleep(POLL_INTERVAL);
+            continue;
+        }
+        let mut applied = 0;
+        let mut batch: Option<Batch> = None;
+        for change in changes {
+            let Some(mapping) = tables.get(change.table.as_str()) else {
+                continue;
+            };
+            if !mappings.contains_key(&mapping.source) {
+                let (source, destination) = (&mapping.source, &mapping.destination);
+                let reader_info = reader.get_table_info(source, true).with_context(|| {
+                    format!("Unable to get information about table \"{source}\"")
+                })?;
+                let writer_info = writer.get_table_info(destination, true).with_context(|| {
+                    format!("Unable to get information about table \"{destination}\"")
+                })?;
+                let column_mapping =
+                    ColumnMapping::new(&reader_info, &writer_info, &config.table(source))?;
+                mappings.insert(source.clone(), column_mapping);
+            }
+            let (table, row) =
+                prepare_change(&mappings[&mapping.source], change.kind.clone(), &change)?;
+            match &mut batch {
+                Some(current) if current.accepts(&table, &change.kind) => current.rows.push(row),
+                _ => {
+                    if let Some(previous) = batch.replace(Batch {
+                        table,
+                        kind: change.kind,
+                        rows: vec![row],
+                    }) {
+                        previous.write(writer.as_mut(), args.batch_write_retries)?;
+                    }
+                }
+            }
+            applied += 1;
+        }
+        if let Some(batch) = batch {
+            batch.write(writer.as_mut(), args.batch_write_retries)?;
+        }
+        // Consumed only once applied, so a stopped run repeats them
+        source.confirm()?;
+        debug!("Applied {applied} changes");
+    }
+    info!("Stopped following changes");
+    return Ok(());
+}
+
+// Output table info with columns of the change and the row to write or the key to delete
+fn prepare_change(
+    mapping: &ColumnMapping,
+    kind: ChangeKind,
+    change: &Change,
+) -> anyhow::Result<(TableInfo, Row)> {
+    let info = &mapping.writer_format;
+    let table = &info.name;
+    let (columns, row) = mapping
+        .apply_change(&change.columns, &change.row)
+        .with_context(|| format!("Failed to map change of table \"{table}\""))?;
+    if kind == ChangeKind::Delete {
+        let key = info
+            .primary_key
+            .iter()
+            .map(|name| {
+                let idx = columns
+                    .iter()
+                    .position(|column| &column.name == name)
+                    .with_context(|| {
+                        format!("Key column {name} of table \"{table}\" is missing in change")
+                    })?;
+                return Ok(row[idx].clone());
+            })
+            .collect::<anyhow::Result<Row>>()?;
+        return Ok((info.clone(), key));
+    }
+    if let Some(missing) = info
+        .primary_key
+        .iter()
+        .find(|name| !columns.iter().any(|column| &column.name == *name))
+    {
+        return Err(anyhow::anyhow!(
+            "Key column {missing} of table \"{table}\" is missing in change"
+        ));
+    }
+    let table = TableInfo {
+        columns,
+        ..info.clone()
+    };
+    return Ok((table, row));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TableConfig;
+    use crate::databases::table::{Column, ColumnType, Value};
+
+    fn column(name: &str, column_type: ColumnType) -> Column {
+        return Column {
+            name: name.to_string(),
+            column_type,
+            nullable: true,
+        };
+    }
+
+    fn make_mapping(config: &TableConfig) -> ColumnMapping {
+        let reader_info = TableInfo {
+            name: "src".to_string(),
+            num_rows: None,
+            columns: vec![
+                column("id", ColumnType::I32),
+                column("value", ColumnType::String),
+                column("extra", ColumnType::Bool),
+            ],
+            primary_key: vec!["id".to_string()],
+        };
+        let writer_info = TableInfo {
+            name: "dst".to_string(),
+            num_rows: None,
+            columns: vec![
+                column("id", ColumnType::I64),
+                column("value", ColumnType::String),
+                column("source", ColumnType::String),
+            ],
+            primary_key: vec!["id".to_string()],
+        };
+        return ColumnMapping::new(&reader_info, &writer_info, config).unwrap();
+    }
+
+    #[test]
+    fn test_prepare_change() {
+        let config = TableConfig {
+            columns: HashMap::from([("extra".to_string(), None)]),
+            constants: HashMap::from([("source".to_string(), Some("src".to_string()))]),
+            ..Default::default()
+        };
+        let mapping = make_mapping(&config);
+        let change = Change {
+            table: "src".to_string(),
+            kind: ChangeKind::Insert,
+            columns: vec!["value".to_string(), "id".to_string(), "extra".to_string()],
+            row: vec![
+                Value::String("a".to_string()),
+                Value::I32(1),
+                Value::Bool(true),
+            ],
+        };
+        // Values are widened to types of output columns
+        let (table, row) = prepare_change(&mapping, ChangeKind::Insert, &change).unwrap();
+        assert_eq!(table.column_names(), vec!["id", "value", "source"]);
+        assert_eq!(
+            row,
+            vec![
+                Value::I64(1),
+                Value::String("a".to_string()),
+                Value::String("src".to_string())
+            ]
+        );
+
+        let (table, key) = prepare_change(&mapping, ChangeKind::Delete, &change).unwrap();
+        assert_eq!(table, mapping.writer_format);
+        assert_eq!(key, vec![Value::I64(1)]);
+
+        let change = Change {
+            columns: vec!["value".to_string()],
+            row: vec![Value::String("a".to_string())],
+            ..change
+        };
+        assert!(prepare_change(&mapping, ChangeKind::Insert, &change).is_err());
+        assert!(prepare_change(&mapping, ChangeKind::Delete, &change).is_err());
+    }
+
+    #[test]
+    fn test_prepare_change_renamed() {
+        let config = TableConfig {
+            columns: HashMap::from([
+                ("extra".to_string(), None),
+                ("value".to_string(), Some("source".to_string())),
+            ]),
+            defaults: vec!["value".to_string()],
+            ..Default::default()
+        };
+        let mapping = make_mapping(&config);
+        let change = Change {
+            table: "src".to_string(),
+            kind: ChangeKind::Insert,
+            columns: vec!["id".to_string(), "value".to_string()],
+            row: vec![Value::I32(1), Value::String("a".to_string())],
+        };
+        let (table, row) = prepare_change(&mapping, ChangeKind::Insert, &change).unwrap();
+        assert_eq!(table.column_names(), vec!["id", "source"]);
+        assert_eq!(row, vec![Value::I64(1), Value::String("a".to_string())]);
+    }
+}
@@ -0,0 +1,151 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+
+use crate::checkpoint;
+use crate::databases::table::{ColumnType, Value};
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.f";
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum WatermarkValue {
+    I64(i64),
+    I32(i32),
+    I16(i16),
+    Timestamptz(String),
+    Timestamp(String),
+    Date(String),
+}
+
+impl WatermarkValue {
+    pub fn is_supported(column_type: &ColumnType) -> bool {
+        return matches!(
+            column_type,
+            ColumnType::I64
+                | ColumnType::I32
+                | ColumnType::I16
+                | ColumnType::Timestamptz
+                | ColumnType::Timestamp
+                | ColumnType::Date
+        );
+    }
+}
+
+impl TryFrom<&Value> for WatermarkValue {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        return match value {
+            &Value::I64(val) => Ok(WatermarkValue::I64(val)),
+            &Value::I32(val) => Ok(WatermarkValue::I32(val)),
+            &Value::I16(val) => Ok(WatermarkValue::I16(val)),
+            Value::Timestamptz(val) => Ok(WatermarkValue::Timestamptz(val.to_rfc3339())),
+            Value::Timestamp(val) => Ok(WatermarkValue::Timestamp(
+                val.format(TIMESTAMP_FORMAT).to_string(),
+            )),
+            Value::Date(val) => Ok(WatermarkValue::Date(val.to_string())),
+            _ => Err(anyhow::anyhow!("Unsupported watermark {value:?}")),
+        };
+    }
+}
+
+impl TryFrom<&WatermarkValue> for Value {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &WatermarkValue) -> Result<Self, Self::Error> {
+        return match value {
+            &WatermarkValue::I64(val) => Ok(Value::I64(val)),
+            &WatermarkValue::I32(val) => Ok(Value::I32(val)),
+            &WatermarkValue::I16(val) => Ok(Value::I16(val)),
+            WatermarkValue::Timestamptz(val) => Ok(Value::Timestamptz(
+                DateTime::parse_from_rfc3339(val)
+                    .context("Invalid timestamp in state file")?
+                    .to_utc(),
+            )),
+            WatermarkValue::Timestamp(val) => Ok(Value::Timestamp(
+                NaiveDateTime::parse_from_str(val, TIMESTAMP_FORMAT)
+                    .context("Invalid timestamp in state file")?,
+            )),
+            WatermarkValue::Date(val) => Ok(Value::Date(
+                NaiveDate::parse_from_str(val, "%Y-%m-%d").context("Invalid date in state file")?,
+            )),
+        };
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Watermark {
+    pub column: String,
+    // Largest value of the column moved to the output table
+    pub value: WatermarkValue,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TableWatermark {
+    path: PathBuf,
+    table: String,
+    column: String,
+}
+
+impl TableWatermark {
+    pub fn new(path: &Path, table: &str, column: &str) -> Self {
+        return Self {
+            path: path.to_path_buf(),
+            table: table.to_string(),
+            column: column.to_string(),
+        };
+    }
+
+    // Watermark saved by the previous run, None if the table wasn't moved yet
+    pub fn load(&self) -> anyhow::Result<Option<Value>> {
+        let saved = checkpoint::load_entries::<Watermark>(&self.path)?.remove(&self.table);
+        let Some(saved) = saved else {
+            return Ok(None);
+        };
+        if saved.column != self.column {
+            return Err(anyhow::anyhow!(
+                "Watermark of table \"{}\" was saved for column {}",
+                self.table,
+                saved.column
+            ));
+        }
+        return Value::try_from(&saved.value).map(Some);
+    }
+
+    pub fn save(&self, value: &Value) -> anyhow::Result<()> {
+        let watermark = Watermark {
+            column: self.column.clone(),
+            value: WatermarkValue::try_from(value)?,
+        };
+        return checkpoint::store(&self.path, &self.table, watermark);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watermark_save_and_load() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let path = tmp_dir.path().join("state.json");
+        let watermark = TableWatermark::new(&path, "test", "updated_at");
+        assert_eq!(watermark.load().unwrap(), None);
+
+        let timestamp = NaiveDate::from_ymd_opt(2024, 5, 1)
+            .unwrap()
+            .and_hms_micro_opt(10, 20, 30, 123)
+            .unwrap();
+        watermark.save(&Value::Timestamp(timestamp)).unwrap();
+        let other = TableWatermark::new(&path, "other", "id");
+        other.save(&Value::I32(10)).unwrap();
+
+        assert_eq!(watermark.load().unwrap(), Some(Value::Timestamp(timestamp)));
+        assert_eq!(other.load().unwrap(), Some(Value::I32(10)));
+        let renamed = TableWatermark::new(&path, "test", "id");
+        assert!(renamed.load().is_err());
+        assert!(watermark.save(&Value::Bool(true)).is_err());
+    }
+}
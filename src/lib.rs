@@ -1,38 +1,246 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::AtomicBool;
+
 use anyhow::Context;
-use tracing::info;
+use tracing::{error, info, warn};
+
+use databases::mysql::binlog::BinlogReader;
+use databases::postgres::replication::LogicalSlot;
+use databases::sqlite::JournalModeGuard;
+use databases::table::{ColumnType, TableMapping, split_table_name};
+use databases::traits::ChangeSource;
 
 pub mod args;
 pub mod channel;
+pub mod checkpoint;
+pub mod column_mapping;
+pub mod config;
 pub mod databases;
+pub mod follow;
+pub mod incremental;
+pub mod ordering;
+pub mod parallel;
 pub mod progress;
 pub mod retry;
+pub mod snapshot;
 pub mod table_migrator;
 pub mod uri;
+pub mod verify;
 
 pub fn run(args: args::Args) -> anyhow::Result<()> {
-    let tables = get_tables(&args)?;
-    for table in &tables {
-        let reader = args.create_reader()?;
+    return run_until(args, &AtomicBool::new(false));
+}
+
+/// Same as `run`, but following changes of the input database stops once `stop` is set
+pub fn run_until(args: args::Args, stop: &AtomicBool) -> anyhow::Result<()> {
+    let config = config::Config::from_args(&args)?;
+    if args.command == Some(args::Command::Verify) {
+        let tables = get_input_tables(&args)?;
+        return verify::verify_tables(&args, &config, &tables);
+    }
+    if args.atomic && args.write_mode != databases::write_mode::WriteMode::Insert {
+        return Err(anyhow::anyhow!(
+            "Atomic load replaces output tables, so only insert write mode is supported"
+        ));
+    }
+    // Only new rows are moved incrementally, so replaced tables would lose older rows
+    if args.atomic
+        && config
+            .tables
+            .values()
+            .any(|table| table.incremental.is_some())
+    {
+        return Err(anyhow::anyhow!(
+            "Atomic load replaces output tables, so incremental moves are not supported"
+        ));
+    }
+    // Changes are applied to output tables as they are, so rows outside filters would be written
+    if args.follow && config.tables.values().any(|table| table.filter.is_some()) {
+        return Err(anyhow::anyhow!(
+            "Changes of filtered tables can't be followed, --follow doesn't support --where"
+        ));
+    }
+    if let (uri::URI::Sqlite(uri), Some(journal)) = (&args.output, args.sqlite_fast_write)
+        && !args.dry_run
+    {
+        let journal_mode = JournalModeGuard::new(uri, journal)
+            .context("Failed to set journal mode of the output database")?;
+        let result = move_and_follow(&args, &config, stop);
+        // Writers are closed by now, so the journal mode can be changed back
+        let restored = journal_mode.restore();
+        if let Err(restore_err) = &restored
+            && result.is_err()
+        {
+            error!("{restore_err:#}");
+        }
+        return result.and(restored);
+    }
+    return move_and_follow(&args, &config, stop);
+}
+
+fn move_and_follow(
+    args: &args::Args,
+    config: &config::Config,
+    stop: &AtomicBool,
+) -> anyhow::Result<()> {
+    // Position of changes is taken before tables are read, so changes made while moving are kept
+    let source = match args.dry_run {
+        false => open_change_source(args)?,
+        true => None,
+    };
+    let groups = get_tables(args, config)?;
+    let source = match source {
+        Some((source, true)) => {
+            info!("Changes are followed from the saved position, tables are not moved again");
+            Some(source)
+        }
+        Some((mut source, false)) => {
+            match move_tables(args, config, &groups).and_then(|()| source.confirm()) {
+                Ok(()) => Some(source),
+                // Otherwise the next run would skip tables which weren't moved
+                Err(err) => {
+                    if let Err(discard_err) = source.discard() {
+                        error!("{discard_err:#}");
+                    }
+                    return Err(err);
+                }
+            }
+        }
+        None => {
+            move_tables(args, config, &groups)?;
+            None
+        }
+    };
+    if let Some(source) = source.filter(|_| args.follow) {
+        follow::follow(args, config, &groups.concat(), source, stop)?;
+    }
+    return Ok(());
+}
+
+// Source of changes with true if its position was saved by a previous run
+fn open_change_source(args: &args::Args) -> anyhow::Result<Option<(Box<dyn ChangeSource>, bool)>> {
+    return match &args.input {
+        uri::URI::Postgres(uri) => match &args.slot {
+            Some(name) => {
+                let (slot, created) = LogicalSlot::open(uri, name)
+                    .with_context(|| format!("Failed to open replication slot {name}"))?;
+                Ok(Some((Box::new(slot), !created)))
+            }
+            None if args.follow => Err(anyhow::anyhow!(
+                "Following changes of PostgreSQL requires a replication slot (--slot)"
+            )),
+            None => Ok(None),
+        },
+        uri::URI::Mysql(uri) if args.follow || args.binlog_state_file.is_some() => {
+            let (binlog, resumed) = BinlogReader::open(
+                uri,
+                args.mysql_type_options(),
+                args.binlog_state_file.as_deref(),
+            )
+            .context("Failed to open binlog")?;
+            Ok(Some((Box::new(binlog), resumed)))
+        }
+        _ if args.follow || args.slot.is_some() || args.binlog_state_file.is_some() => Err(
+            anyhow::anyhow!("Changes can be followed only for PostgreSQL and MySQL input"),
+        ),
+        _ => Ok(None),
+    };
+}
+
+fn move_tables(
+    args: &args::Args,
+    config: &config::Config,
+    groups: &[Vec<TableMapping>],
+) -> anyhow::Result<()> {
+    let finished = get_finished_tables(args)?;
+    let parallel_settings = parallel::ParallelSettings {
+        parallel_tables: args.parallel_tables,
+        max_connections: args.max_connections,
+        connections_per_table: args.reader_workers.max(1) + args.writer_workers.max(1),
+    };
+    // Held until all tables are moved, so the snapshot stays open
+    let snapshot = if args.snapshot {
+        Some(snapshot::Snapshot::new(args.create_reader()?)?)
+    } else {
+        None
+    };
+    // Snapshot bound to a single connection can't be read by multiple readers
+    let single_reader = snapshot
+        .as_ref()
+        .is_some_and(|snapshot| !snapshot.is_shared());
+    if single_reader && args.reader_workers > 1 {
+        warn!("Snapshot of the input database is read by a single reader worker");
+    }
+    let move_table = |table: &TableMapping| {
+        let reader = match &snapshot {
+            Some(snapshot) => snapshot.reader()?,
+            None => args.create_reader()?,
+        };
         let writer = args.create_writer()?;
+        let mut settings = table_migrator::TableMigratorSettings::from(args)
+            .with_table_config(config.table(&table.source));
+        if single_reader {
+            settings = settings.with_reader_workers(1);
+        }
+        let migrator = table_migrator::TableMigrator::new(reader, writer, table, settings)?;
+        if !args.dry_run {
+            with_dropped_indexes(args, table, || migrator.run())?;
+        }
+        return Ok(());
+    };
+    let migrate = |table: &TableMapping| {
         info!("Processing table \"{table}\"");
-        let migrator = table_migrator::TableMigrator::new(reader, writer, table, (&args).into())?;
+        if args.atomic && !args.dry_run {
+            with_staging_table(args, config, table, move_table)?;
+        } else {
+            move_table(table)?;
+        }
         if !args.dry_run {
-            migrator.run()?;
+            args.create_writer()?
+                .reset_sequences(&table.destination)
+                .with_context(|| format!("Failed to reset sequences of table \"{table}\""))?;
             info!("Table \"{table}\" moved");
         }
+        return Ok(());
+    };
+    // Tables of a group start only after all referenced tables are moved
+    for group in groups {
+        let pending: Vec<TableMapping> = group
+            .iter()
+            .filter(|table| {
+                let done = finished.contains(&table.source);
+                if done {
+                    info!("Table \"{table}\" was already moved according to the checkpoint");
+                }
+                return !done;
+            })
+            .cloned()
+            .collect();
+        parallel::run_tables(&pending, &parallel_settings, migrate)?;
+    }
+    // Atomic load verifies staging tables before replacing output tables
+    if (args.verify || args.verify_detailed) && !args.dry_run && !args.atomic {
+        verify::verify_tables(args, config, &groups.concat())?;
     }
     return Ok(());
 }
 
-fn get_tables(args: &args::Args) -> anyhow::Result<Vec<String>> {
+fn get_input_tables(args: &args::Args) -> anyhow::Result<Vec<TableMapping>> {
     let tables = match args.table.len() {
         0 => {
             let mut reader = args.create_reader()?;
             reader
                 .get_tables()
                 .context("Failed to get list of tables from input database")?
+                .iter()
+                .map(|table| TableMapping::new(table))
+                .collect()
         }
-        _ => args.table.clone(),
+        _ => args
+            .table
+            .iter()
+            .map(|table| table.parse())
+            .collect::<anyhow::Result<Vec<TableMapping>>>()?,
     };
     info!(
         "Tables to move: {}",
@@ -42,18 +250,195 @@ fn get_tables(args: &args::Args) -> anyhow::Result<Vec<String>> {
             .collect::<Vec<_>>()
             .join(", ")
     );
-    let writer_tables = {
-        let mut writer = args.create_writer()?;
-        writer
-            .get_tables()
-            .context("Failed to get list of tables from output database")?
-    };
-    for table in &tables {
-        if !writer_tables.contains(table) {
+    return Ok(tables);
+}
+
+// Tables to move, grouped so that referenced tables are moved first
+fn get_tables(
+    args: &args::Args,
+    config: &config::Config,
+) -> anyhow::Result<Vec<Vec<TableMapping>>> {
+    let tables = get_input_tables(args)?;
+    let mut writer = args.create_writer()?;
+    // Tables of the current schema and of schemas used in destination names,
+    // other databases treat dotted names as plain table names
+    let mut writer_tables = writer
+        .get_tables()
+        .context("Failed to get list of tables from output database")?;
+    let schemas: HashSet<&str> = match args.output.has_schemas() {
+        true => tables
+            .iter()
+            .filter_map(|table| split_table_name(&table.destination).0)
+            .collect(),
+        false => HashSet::new(),
+    };
+    for schema in schemas {
+        writer_tables.extend(
+            writer
+                .get_schema_tables(schema)
+                .with_context(|| format!("Failed to get list of tables of schema {schema}"))?,
+        );
+    }
+    let mut result = Vec::with_capacity(tables.len());
+    for table in tables {
+        let destination = &table.destination;
+        if writer_tables.contains(destination) {
+            result.push(table);
+            continue;
+        }
+        if !args.create_tables {
             return Err(anyhow::anyhow!(
-                "Table \"{table}\" not found in the output database"
+                "Table \"{destination}\" not found in the output database"
             ));
         }
+        if args.dry_run {
+            info!("Table \"{destination}\" not found in the output database and will be created");
+            continue;
+        }
+        create_table(args, config, &table)?;
+        result.push(table);
     }
-    return Ok(tables);
+    let mut references = HashMap::new();
+    for table in &result {
+        let referenced = writer
+            .get_referenced_tables(&table.destination)
+            .with_context(|| {
+                format!(
+                    "Failed to get foreign keys of table \"{}\"",
+                    table.destination
+                )
+            })?;
+        references.insert(table.destination.clone(), referenced);
+    }
+    let groups = ordering::order_by_references(result, &references);
+    if groups.len() > 1 {
+        info!(
+            "Tables are moved in order of foreign keys: {}",
+            groups
+                .iter()
+                .map(|group| group
+                    .iter()
+                    .map(|table| format!("\"{table}\""))
+                    .collect::<Vec<_>>()
+                    .join(", "))
+                .collect::<Vec<_>>()
+                .join(" -> ")
+        );
+    }
+    return Ok(groups);
+}
+
+fn get_finished_tables(args: &args::Args) -> anyhow::Result<HashSet<String>> {
+    let Some(path) = args.checkpoint_file.as_ref().filter(|_| args.resume) else {
+        return Ok(HashSet::new());
+    };
+    return Ok(checkpoint::load(path)?
+        .into_iter()
+        .filter(|(_, checkpoint)| checkpoint.finished)
+        .map(|(table, _)| table)
+        .collect());
+}
+
+// Runs the migration with secondary indexes of the output table dropped, if requested.
+// Indexes are recreated even if the migration fails.
+fn with_dropped_indexes(
+    args: &args::Args,
+    table: &TableMapping,
+    migrate: impl FnOnce() -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    if !args.drop_indexes {
+        return migrate();
+    }
+    let destination = &table.destination;
+    let indexes = args
+        .create_writer()?
+        .drop_indexes(destination)
+        .with_context(|| format!("Failed to drop indexes of table \"{destination}\""))?;
+    if indexes.is_empty() {
+        return migrate();
+    }
+    // Logged to recreate indexes manually if the process is killed
+    info!(
+        "Indexes of table \"{destination}\" dropped until the table is moved:\n{}",
+        indexes.join("\n")
+    );
+    let result = migrate();
+    info!("Recreating indexes of table \"{destination}\"");
+    // New connection, as the one used to drop may be closed after a long migration
+    let restored = args
+        .create_writer()
+        .and_then(|mut writer| writer.create_indexes(&indexes))
+        .with_context(|| format!("Failed to recreate indexes of table \"{destination}\""));
+    return match (result, restored) {
+        (Err(err), Err(restore_err)) => {
+            error!("{restore_err:#}");
+            Err(err)
+        }
+        (result, restored) => result.and(restored),
+    };
+}
+
+// Moves the table into a staging table, which replaces the output table only if
+// the whole table is moved and verified. Staging table is dropped on failure.
+fn with_staging_table(
+    args: &args::Args,
+    config: &config::Config,
+    table: &TableMapping,
+    move_table: impl FnOnce(&TableMapping) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let staging = table.staging();
+    let (destination, staging_name) = (&table.destination, &staging.destination);
+    args.create_writer()?
+        .create_staging_table(destination, staging_name)
+        .with_context(|| format!("Failed to create staging table \"{staging_name}\""))?;
+    let result = move_table(&staging).and_then(|()| {
+        if args.verify || args.verify_detailed {
+            verify::verify_tables(args, config, std::slice::from_ref(&staging))?;
+        }
+        // New connection, as the one used to create may be closed after a long migration
+        return args
+            .create_writer()?
+            .swap_staging_table(destination, staging_name)
+            .with_context(|| {
+                format!("Failed to replace table \"{destination}\" with staging table")
+            });
+    });
+    if result.is_ok() {
+        info!("Table \"{destination}\" replaced with staging table \"{staging_name}\"");
+        return result;
+    }
+    if let Err(err) = args
+        .create_writer()
+        .and_then(|mut writer| writer.drop_staging_table(staging_name))
+    {
+        error!("Failed to drop staging table \"{staging_name}\": {err:#}");
+    }
+    return result;
+}
+
+fn create_table(
+    args: &args::Args,
+    config: &config::Config,
+    table: &TableMapping,
+) -> anyhow::Result<()> {
+    let mut table_info = args
+        .create_reader()?
+        .get_table_info(&table.source, true)
+        .context("Unable to get information about source table")?;
+    let destination = &table.destination;
+    table_info.name = destination.clone();
+    if config.table(&table.source).arrays_as_json {
+        for column in &mut table_info.columns {
+            if let ColumnType::Array(_) = column.column_type {
+                column.column_type = ColumnType::Json;
+            }
+        }
+    }
+    args.create_writer()?
+        .create_table(&table_info)
+        .with_context(|| {
+            format!("Failed to create table \"{destination}\" in the output database")
+        })?;
+    info!("Table \"{destination}\" created in the output database");
+    return Ok(());
 }
@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+
+use tracing::warn;
+
+use crate::databases::table::TableMapping;
+
+/// Splits tables into groups, tables of a group reference only tables of previous groups.
+/// `references` maps destination names to tables referenced by their foreign keys.
+/// Order of tables is kept within a group, tables of foreign key cycles are put last.
+pub fn order_by_references(
+    tables: Vec<TableMapping>,
+    references: &HashMap<String, Vec<String>>,
+) -> Vec<Vec<TableMapping>> {
+    // Positions of tables each table depends on, references outside of the run are ignored
+    let positions: HashMap<&str, usize> = tables
+        .iter()
+        .enumerate()
+        .map(|(idx, table)| (table.destination.as_str(), idx))
+        .collect();
+    let dependencies: Vec<Vec<usize>> = tables
+        .iter()
+        .enumerate()
+        .map(|(idx, table)| {
+            let mut deps: Vec<usize> = references
+                .get(&table.destination)
+                .into_iter()
+                .flatten()
+                .filter_map(|referenced| positions.get(referenced.as_str()).copied())
+                .filter(|&dep| dep != idx)
+                .collect();
+            deps.sort();
+            deps.dedup();
+            return deps;
+        })
+        .collect();
+
+    let mut done = vec![false; tables.len()];
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    loop {
+        let group: Vec<usize> = (0..tables.len())
+            .filter(|&idx| !done[idx] && dependencies[idx].iter().all(|&dep| done[dep]))
+            .collect();
+        if group.is_empty() {
+            break;
+        }
+        group.iter().for_each(|&idx| done[idx] = true);
+        groups.push(group);
+    }
+    let remaining: Vec<usize> = (0..tables.len()).filter(|&idx| !done[idx]).collect();
+    if !remaining.is_empty() {
+        let cycle = find_cycle(&dependencies, &done, remaining[0])
+            .into_iter()
+            .map(|idx| format!("\"{}\"", tables[idx].destination))
+            .collect::<Vec<_>>()
+            .join(" -> ");
+        warn!(
+            "Foreign keys form a cycle {cycle}, tables of cycles are moved last in the original order"
+        );
+        groups.push(remaining);
+    }
+
+    let mut tables: Vec<Option<TableMapping>> = tables.into_iter().map(Some).collect();
+    return groups
+        .into_iter()
+        .map(|group| {
+            group
+                .into_iter()
+                .map(|idx| tables[idx].take().unwrap())
+                .collect()
+        })
+        .collect();
+}
+
+// Every table left after ordering depends on another left table, so following
+// dependencies from any of them leads into a cycle
+fn find_cycle(dependencies: &[Vec<usize>], done: &[bool], start: usize) -> Vec<usize> {
+    let mut path = vec![start];
+    loop {
+        let current = *path.last().unwrap();
+        let next = dependencies[current]
+            .iter()
+            .copied()
+            .find(|&dep| !done[dep])
+            .expect("Table of a cycle should have a dependency");
+        if let Some(pos) = path.iter().position(|&idx| idx == next) {
+            let mut cycle = path.split_off(pos);
+            cycle.push(next);
+            return cycle;
+        }
+        path.push(next);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(groups: &[Vec<TableMapping>]) -> Vec<Vec<&str>> {
+        return groups
+            .iter()
+            .map(|group| group.iter().map(|t| t.destination.as_str()).collect())
+            .collect();
+    }
+
+    fn make_references(pairs: &[(&str, &str)]) -> HashMap<String, Vec<String>> {
+        let mut references: HashMap<String, Vec<String>> = HashMap::new();
+        for (table, referenced) in pairs {
+            references
+                .entry(table.to_string())
+                .or_default()
+                .push(referenced.to_string());
+        }
+        return references;
+    }
+
+    #[test]
+    fn test_order_by_references() {
+        let tables = ["order_items", "orders", "users", "logs", "products"]
+            .map(TableMapping::new)
+            .to_vec();
+        let references = make_references(&[
+            ("order_items", "orders"),
+            ("order_items", "products"),
+            ("orders", "users"),
+            ("users", "users"),
+            ("logs", "external"),
+        ]);
+        let groups = order_by_references(tables, &references);
+        assert_eq!(
+            names(&groups),
+            vec![
+                vec!["users", "logs", "products"],
+                vec!["orders"],
+                vec!["order_items"]
+            ]
+        );
+    }
+
+    #[test]
+    fn test_order_with_cycle() {
+        let tables = ["a", "b", "c", "d"].map(TableMapping::new).to_vec();
+        let references = make_references(&[("a", "b"), ("b", "c"), ("c", "b"), ("d", "a")]);
+        let groups = order_by_references(tables, &references);
+        assert_eq!(names(&groups), vec![vec!["a", "b", "c", "d"]]);
+
+        let dependencies = vec![vec![1], vec![2], vec![1], vec![0]];
+        assert_eq!(find_cycle(&dependencies, &[false; 4], 3), vec![1, 2, 1]);
+    }
+}
@@ -0,0 +1,169 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex};
+
+use tracing::error;
+
+use crate::databases::table::TableMapping;
+
+// Number of connections shared by tables moved at the same time
+struct ConnectionBudget {
+    total: Option<usize>,
+    available: Mutex<usize>,
+    released: Condvar,
+}
+
+impl ConnectionBudget {
+    fn new(total: Option<usize>) -> Self {
+        return Self {
+            total,
+            available: Mutex::new(total.unwrap_or(usize::MAX)),
+            released: Condvar::new(),
+        };
+    }
+
+    // Blocks until connections are available. Table needing more than the whole budget
+    // waits for all of it, so it runs alone instead of never starting.
+    fn acquire(&self, connections: usize) -> usize {
+        let connections = self
+            .total
+            .map_or(connections, |total| connections.min(total));
+        let mut available = self.available.lock().unwrap();
+        while *available < connections {
+            available = self.released.wait(available).unwrap();
+        }
+        *available -= connections;
+        return connections;
+    }
+
+    fn release(&self, connections: usize) {
+        *self.available.lock().unwrap() += connections;
+        self.released.notify_all();
+    }
+}
+
+pub struct ParallelSettings {
+    pub parallel_tables: usize,
+    pub max_connections: Option<usize>,
+    // Connections opened by a single table, readers and writers
+    pub connections_per_table: usize,
+}
+
+/// Runs `migrate` for tables in order, up to `parallel_tables` at the same time.
+/// After a failure no new tables are started, errors of all failed tables are reported.
+pub fn run_tables(
+    tables: &[TableMapping],
+    settings: &ParallelSettings,
+    migrate: impl Fn(&TableMapping) -> anyhow::Result<()> + Sync,
+) -> anyhow::Result<()> {
+    let budget = ConnectionBudget::new(settings.max_connections);
+    let next = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let errors: Mutex<Vec<(usize, anyhow::Error)>> = Mutex::new(Vec::new());
+    let workers = settings.parallel_tables.clamp(1, tables.len().max(1));
+    std::thread::scope(|s| {
+        for _ in 0..workers {
+            s.spawn(|| {
+                while !failed.load(Ordering::Relaxed) {
+                    let idx = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(table) = tables.get(idx) else {
+                        return;
+                    };
+                    let connections = budget.acquire(settings.connections_per_table);
+                    let result = migrate(table);
+                    budget.release(connections);
+                    if let Err(err) = result {
+                        error!("Failed to move table \"{table}\": {err:#}");
+                        failed.store(true, Ordering::Relaxed);
+                        errors.lock().unwrap().push((idx, err));
+                    }
+                }
+            });
+        }
+    });
+
+    let mut errors = errors.into_inner().unwrap();
+    errors.sort_by_key(|(idx, _)| *idx);
+    // Single error is returned as is, the table is already named in the log
+    if errors.len() == 1 {
+        return Err(errors.remove(0).1);
+    }
+    if !errors.is_empty() {
+        let report = errors
+            .iter()
+            .map(|(idx, err)| format!("\"{}\": {err:#}", tables[*idx]))
+            .collect::<Vec<_>>()
+            .join("\n");
+        return Err(anyhow::anyhow!(
+            "Failed to move {} tables:\n{report}",
+            errors.len()
+        ));
+    }
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_tables(num: usize) -> Vec<TableMapping> {
+        return (0..num)
+            .map(|idx| TableMapping::new(&format!("table{idx}")))
+            .collect();
+    }
+
+    fn settings(parallel_tables: usize, max_connections: Option<usize>) -> ParallelSettings {
+        return ParallelSettings {
+            parallel_tables,
+            max_connections,
+            connections_per_table: 2,
+        };
+    }
+
+    #[test]
+    fn test_run_tables_limits() {
+        let tables = make_tables(10);
+        for (settings, expected) in [
+            (settings(4, None), 4),
+            (settings(4, Some(4)), 2),
+            (settings(4, Some(1)), 1),
+        ] {
+            let running = AtomicUsize::new(0);
+            let max_running = AtomicUsize::new(0);
+            let done = AtomicUsize::new(0);
+            run_tables(&tables, &settings, |_| {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                max_running.fetch_max(now, Ordering::SeqCst);
+                std::thread::sleep(std::time::Duration::from_millis(20));
+                running.fetch_sub(1, Ordering::SeqCst);
+                done.fetch_add(1, Ordering::SeqCst);
+                return Ok(());
+            })
+            .unwrap();
+            assert_eq!(done.load(Ordering::SeqCst), tables.len());
+            assert_eq!(max_running.load(Ordering::SeqCst), expected);
+        }
+    }
+
+    #[test]
+    fn test_run_tables_errors() {
+        let tables = make_tables(3);
+        let result = run_tables(&tables, &settings(1, None), |table| {
+            if table.source == "table1" {
+                return Err(anyhow::anyhow!("broken"));
+            }
+            return Ok(());
+        });
+        let message = format!("{:#}", result.unwrap_err());
+        assert_eq!(message, "broken");
+
+        let result = run_tables(&tables, &settings(3, None), |table| {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            return Err(anyhow::anyhow!("broken {}", table.source));
+        });
+        let message = format!("{:#}", result.unwrap_err());
+        assert_eq!(
+            message,
+            "Failed to move 3 tables:\n\"table0\": broken table0\n\"table1\": broken table1\n\"table2\": broken table2"
+        );
+    }
+}
@@ -113,9 +113,9 @@ impl Display for ProgressTracker {
         if let Some(total) = self.total {
             let mut percent = 100;
             let mut percent_remainder = 0;
-            if total > 0 {
-                percent = current * 100 / total;
-                percent_remainder = (current * 10000 / total) % 100;
+            if let Some(basis_points) = (current * 10000).checked_div(total) {
+                percent = basis_points / 100;
+                percent_remainder = basis_points % 100;
             }
             f.write_fmt(format_args!(
                 "Processed: {percent}.{percent_remainder:02}% ({}/{}) ",
@@ -0,0 +1,150 @@
+use std::sync::{Arc, Condvar, Mutex};
+
+use anyhow::Context;
+
+use crate::databases::table::{Column, ReadFilter, TableInfo, Value};
+use crate::databases::traits::{DBInfoProvider, DBReader, ReaderIterator};
+
+/// Consistent snapshot of the input database, held open for the whole run.
+pub enum Snapshot {
+    // Cloned readers join the snapshot with their own connections
+    Shared(Mutex<Box<dyn DBReader>>),
+    // Snapshot is bound to a single connection, tables take turns reading from it
+    Single(Arc<ReaderSlot>),
+}
+
+impl Snapshot {
+    pub fn new(mut reader: Box<dyn DBReader>) -> anyhow::Result<Self> {
+        reader
+            .start_snapshot()
+            .context("Failed to start snapshot of the input database")?;
+        if reader.is_snapshot_shared() {
+            return Ok(Snapshot::Shared(Mutex::new(reader)));
+        }
+        return Ok(Snapshot::Single(Arc::new(ReaderSlot {
+            reader: Mutex::new(Some(reader)),
+            returned: Condvar::new(),
+        })));
+    }
+
+    pub fn is_shared(&self) -> bool {
+        return matches!(self, Snapshot::Shared(_));
+    }
+
+    // Reader of the snapshot, blocks until the single connection is released by another table
+    pub fn reader(&self) -> anyhow::Result<Box<dyn DBReader>> {
+        return match self {
+            Snapshot::Shared(reader) => reader.lock().unwrap().opt_clone(),
+            Snapshot::Single(slot) => {
+                let mut reader = slot.reader.lock().unwrap();
+                while reader.is_none() {
+                    reader = slot.returned.wait(reader).unwrap();
+                }
+                Ok(Box::new(LeasedReader {
+                    reader: reader.take(),
+                    slot: slot.clone(),
+                }))
+            }
+        };
+    }
+}
+
+pub struct ReaderSlot {
+    reader: Mutex<Option<Box<dyn DBReader>>>,
+    returned: Condvar,
+}
+
+// Reader of the single connection snapshot, returned to the slot on drop
+struct LeasedReader {
+    reader: Option<Box<dyn DBReader>>,
+    slot: Arc<ReaderSlot>,
+}
+
+impl LeasedReader {
+    fn reader(&mut self) -> &mut Box<dyn DBReader> {
+        return self.reader.as_mut().unwrap();
+    }
+}
+
+impl Drop for LeasedReader {
+    fn drop(&mut self) {
+        *self.slot.reader.lock().unwrap() = self.reader.take();
+        self.slot.returned.notify_one();
+    }
+}
+
+impl DBInfoProvider for LeasedReader {
+    fn get_tables(&mut self) -> anyhow::Result<Vec<String>> {
+        return self.reader().get_tables();
+    }
+
+    fn get_schema_tables(&mut self, schema: &str) -> anyhow::Result<Vec<String>> {
+        return self.reader().get_schema_tables(schema);
+    }
+
+    fn get_table_info(&mut self, table: &str, no_count: bool) -> anyhow::Result<TableInfo> {
+        return self.reader().get_table_info(table, no_count);
+    }
+
+    fn get_referenced_tables(&mut self, table: &str) -> anyhow::Result<Vec<String>> {
+        return self.reader().get_referenced_tables(table);
+    }
+}
+
+impl DBReader for LeasedReader {
+    fn opt_clone(&self) -> anyhow::Result<Box<dyn DBReader>> {
+        return Err(anyhow::anyhow!(
+            "Snapshot of this type of databases can't be shared by multiple readers"
+        ));
+    }
+
+    fn read_iter(
+        &mut self,
+        target_format: TableInfo,
+        filter: ReadFilter,
+    ) -> anyhow::Result<ReaderIterator<'_>> {
+        return self.reader().read_iter(target_format, filter);
+    }
+
+    fn count_rows(&mut self, table: &str, condition: &str) -> anyhow::Result<u64> {
+        return self.reader().count_rows(table, condition);
+    }
+
+    fn get_key_bounds(
+        &mut self,
+        table: &str,
+        column: &Column,
+    ) -> anyhow::Result<Option<(Value, Value)>> {
+        return self.reader().get_key_bounds(table, column);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+
+    use crate::databases::sqlite::SqliteDB;
+
+    use super::*;
+
+    #[test]
+    fn test_single_connection_snapshot() {
+        let reader = SqliteDB::new("sqlite://:memory:").unwrap();
+        let snapshot = Snapshot::new(Box::new(reader)).unwrap();
+        assert!(matches!(snapshot, Snapshot::Single(_)));
+
+        let first = snapshot.reader().unwrap();
+        assert!(first.opt_clone().is_err());
+        let released = AtomicBool::new(false);
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                let _second = snapshot.reader().unwrap();
+                assert!(released.load(Ordering::SeqCst));
+            });
+            std::thread::sleep(Duration::from_millis(50));
+            released.store(true, Ordering::SeqCst);
+            drop(first);
+        });
+    }
+}
@@ -1,28 +1,44 @@
+use std::path::PathBuf;
 use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
 
 use anyhow::Context;
 use thiserror::Error;
-use tracing::info;
+use tracing::{info, warn};
 
 use crate::{
     args::Args,
     channel,
+    checkpoint::{self, TableCheckpoint},
+    column_mapping::ColumnMapping,
+    config::TableConfig,
     databases::{
-        table::{Column, ColumnType, Row, TableInfo},
+        table::{Column, KeyRange, ReadFilter, Row, TableInfo, TableMapping, Value},
         traits::{DBReader, DBWriter},
+        write_mode::WriteMode,
     },
+    incremental::{TableWatermark, WatermarkValue},
     progress::TableMigrationProgress,
     retry::ExponentialRetry,
 };
 
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(1);
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct TableMigratorSettings {
     queue_size: usize,
     quiet: bool,
     no_count: bool,
+    reader_workers: usize,
     writer_workers: usize,
     batch_write_size: usize,
     batch_write_retries: usize,
+    write_mode: WriteMode,
+    fast_load: bool,
+    checkpoint_file: Option<PathBuf>,
+    resume: bool,
+    state_file: Option<PathBuf>,
+    table_config: TableConfig,
 }
 
 impl From<&Args> for TableMigratorSettings {
@@ -31,13 +47,32 @@ impl From<&Args> for TableMigratorSettings {
             queue_size: args.queue_size,
             quiet: args.quiet,
             no_count: args.no_count,
+            reader_workers: args.reader_workers,
             writer_workers: args.writer_workers,
             batch_write_size: args.batch_write_size,
             batch_write_retries: args.batch_write_retries,
+            write_mode: args.write_mode,
+            fast_load: args.fast_load,
+            checkpoint_file: args.checkpoint_file.clone(),
+            resume: args.resume,
+            state_file: args.state_file.clone(),
+            table_config: TableConfig::default(),
         };
     }
 }
 
+impl TableMigratorSettings {
+    pub fn with_table_config(mut self, table_config: TableConfig) -> Self {
+        self.table_config = table_config;
+        return self;
+    }
+
+    pub fn with_reader_workers(mut self, reader_workers: usize) -> Self {
+        self.reader_workers = reader_workers;
+        return self;
+    }
+}
+
 #[derive(Error, Debug)]
 enum MigratorError {
     #[error("Stopeed because of the error in an another thread")]
@@ -47,32 +82,122 @@ enum MigratorError {
 }
 
 pub struct TableMigrator {
-    reader: Box<dyn DBReader>,
+    readers: Vec<(Box<dyn DBReader>, ReadFilter)>,
     writers: Vec<Box<dyn DBWriter>>,
     tracker: TableMigrationProgress,
-    target_format: TableInfo,
+    mapping: ColumnMapping,
     sender: channel::Sender,
     reciever: channel::Reciever,
     stopped: std::sync::atomic::AtomicBool,
     settings: TableMigratorSettings,
+    checkpoint: Option<TableCheckpoint>,
+    // Watermark and its new value, saved once the table is moved
+    watermark: Option<(TableWatermark, Value)>,
 }
 
 impl TableMigrator {
     pub fn new(
         mut reader: Box<dyn DBReader>,
         mut writer: Box<dyn DBWriter>,
-        table: &str,
-        settings: TableMigratorSettings,
+        table: &TableMapping,
+        mut settings: TableMigratorSettings,
     ) -> anyhow::Result<TableMigrator> {
         info!("Collecting info about table \"{table}\"");
-        let reader_table_info = reader
-            .get_table_info(table, settings.no_count)
+        let condition = settings.table_config.filter.clone();
+        let mut reader_table_info = reader
+            .get_table_info(&table.source, settings.no_count || condition.is_some())
             .context("Unable to get information about source table")?;
+        if let Some(condition) = &condition
+            && !settings.no_count
+        {
+            reader_table_info.num_rows = Some(
+                reader
+                    .count_rows(&table.source, condition)
+                    .context("Unable to count filtered rows of source table")?,
+            );
+        }
         let writer_table_info = writer
-            .get_table_info(table, false)
+            .get_table_info(&table.destination, false)
             .context("Unable to get information about destination table")?;
-        Self::check_table_compatibility(&reader_table_info, &writer_table_info)?;
+        let mapping = ColumnMapping::new(
+            &reader_table_info,
+            &writer_table_info,
+            &settings.table_config,
+        )?;
         info!("Table \"{table}\" has passed compatability checks");
+        let checkpoint = Self::prepare_checkpoint(&reader_table_info, &mapping, &settings)?;
+        let incremental = Self::prepare_incremental(&mut reader, &reader_table_info, &settings)?;
+        if let Some((_, key_range)) = &incremental {
+            info!(
+                "Table \"{table}\" will be moved incrementally after {:?}",
+                key_range.after
+            );
+            // Moved rows may be changed by later runs
+            settings.write_mode = WriteMode::Upsert;
+            reader_table_info.num_rows = None;
+        }
+        let resumed = checkpoint
+            .as_ref()
+            .is_some_and(|(_, key_range)| key_range.after.is_some());
+        if resumed && settings.write_mode == WriteMode::Insert {
+            // Rows after the saved key may be written before the checkpoint was saved
+            settings.write_mode = WriteMode::Upsert;
+        }
+        if !resumed
+            && settings.write_mode == WriteMode::Insert
+            && writer_table_info.num_rows != Some(0)
+        {
+            return Err(anyhow::anyhow!(
+                "Destination table \"{}\" should be empty",
+                &writer_table_info.name
+            ));
+        }
+        if settings.write_mode == WriteMode::Upsert && writer_table_info.primary_key.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Destination table \"{}\" should have a primary key to upsert rows",
+                &writer_table_info.name
+            ));
+        }
+        let filter = |key_range| ReadFilter {
+            key_range,
+            condition: condition.clone(),
+        };
+        let mut readers = Vec::new();
+        let mut table_checkpoint = None;
+        let mut watermark = None;
+        if let Some((table_watermark, key_range)) = incremental {
+            watermark = key_range
+                .up_to
+                .clone()
+                .map(|value| (table_watermark, value));
+            readers.push((reader, filter(Some(key_range))));
+        } else if let Some((checkpoint, key_range)) = checkpoint {
+            if resumed {
+                info!(
+                    "Table \"{table}\" will be resumed after {:?}",
+                    key_range.after
+                );
+            }
+            readers.push((reader, filter(Some(key_range))));
+            table_checkpoint = Some(checkpoint);
+        } else {
+            let key_ranges = Self::split_key_ranges(&mut reader, &reader_table_info, &settings)?;
+            if key_ranges.len() > 1 {
+                info!(
+                    "Table \"{table}\" will be read in {} key ranges",
+                    key_ranges.len()
+                );
+                for key_range in key_ranges {
+                    readers.push((reader.opt_clone()?, filter(Some(key_range))));
+                }
+            } else {
+                readers.push((reader, filter(None)));
+            }
+        }
+        writer.set_write_mode(settings.write_mode)?;
+        if settings.fast_load {
+            writer.enable_fast_load()?;
+        }
         let mut writers = Vec::new();
         if settings.writer_workers > 1 {
             for _ in 0..settings.writer_workers {
@@ -81,80 +206,168 @@ impl TableMigrator {
         } else {
             writers.push(writer);
         }
-        let tracker = TableMigrationProgress::new(table, reader_table_info.num_rows);
+        let tracker = TableMigrationProgress::new(&table.to_string(), reader_table_info.num_rows);
         let (sender, reciever) = channel::create_channel(settings.queue_size);
         return Ok(TableMigrator {
-            reader,
+            readers,
             writers,
             tracker,
-            target_format: writer_table_info,
+            mapping,
             sender,
             reciever,
             stopped: std::sync::atomic::AtomicBool::new(false),
             settings,
+            checkpoint: table_checkpoint,
+            watermark,
         });
     }
 
-    fn check_table_compatibility(
+    // Single primary key column usable for key ranges
+    fn get_key_column(reader_info: &TableInfo) -> Option<&Column> {
+        return match reader_info.primary_key.as_slice() {
+            [key] => reader_info
+                .column(key)
+                .filter(|column| KeyRange::is_supported_key(&column.column_type)),
+            _ => None,
+        };
+    }
+
+    // Checkpoint of the table and a key range to read, starting after the saved key on resume
+    fn prepare_checkpoint(
         reader_info: &TableInfo,
-        writer_info: &TableInfo,
-    ) -> anyhow::Result<()> {
-        if writer_info.num_rows != Some(0) {
+        mapping: &ColumnMapping,
+        settings: &TableMigratorSettings,
+    ) -> anyhow::Result<Option<(TableCheckpoint, KeyRange)>> {
+        let Some(path) = &settings.checkpoint_file else {
+            return Ok(None);
+        };
+        // Saved key is valid only if all rows before it are written
+        if settings.reader_workers > 1 || settings.writer_workers > 1 {
             return Err(anyhow::anyhow!(
-                "Destination table \"{}\" should be empty",
-                &writer_info.name
+                "Checkpoints require a single reader and a single writer worker"
             ));
         }
-        let mut reader_columns: Vec<&Column> = reader_info.columns.iter().collect();
-        reader_columns.sort_by(|l, r| r.name.cmp(&l.name));
-        let mut writer_columns: Vec<&Column> = writer_info.columns.iter().collect();
-        writer_columns.sort_by(|l, r| r.name.cmp(&l.name));
-        let err = Err(anyhow::anyhow!(
-            "Incompatable set of columns for table \"{}\".\n\
-                In: {reader_columns:?}\n\
-                Out: {writer_columns:?}",
-            &writer_info.name
-        ));
-        if reader_columns.len() != writer_columns.len() {
-            return err;
-        }
-        for (reader_column, writer_column) in std::iter::zip(reader_columns, writer_columns) {
-            if reader_column.name != writer_column.name
-                || (!writer_column.nullable && reader_column.nullable)
-            {
-                return err;
+        let key_column = Self::get_key_column(reader_info);
+        let Some(key_column) = key_column else {
+            return Err(anyhow::anyhow!(
+                "Table \"{}\" has no single integer or uuid primary key required for checkpoints",
+                reader_info.name
+            ));
+        };
+        let key_index = mapping.output_position(&key_column.name).with_context(|| {
+            format!(
+                "Key column {} is not written to destination table \"{}\"",
+                key_column.name, mapping.writer_format.name
+            )
+        })?;
+        let mut after = None;
+        if settings.resume {
+            let saved = checkpoint::load(path)?.remove(&reader_info.name);
+            if let Some(saved) = saved {
+                if saved.column != key_column.name {
+                    return Err(anyhow::anyhow!(
+                        "Checkpoint of table \"{}\" was saved for column {}",
+                        reader_info.name,
+                        saved.column
+                    ));
+                }
+                after = saved.key.as_ref().map(Value::try_from).transpose()?;
             }
-            let allowed = matches!(
-                (reader_column.column_type, writer_column.column_type),
-                (ColumnType::I16, ColumnType::I32 | ColumnType::I64)
-                    | (ColumnType::I32, ColumnType::I64)
-                    | (ColumnType::F32, ColumnType::F64)
-            );
+        }
+        let checkpoint = TableCheckpoint::new(path, &reader_info.name, &key_column.name, key_index);
+        let key_range = KeyRange {
+            column: key_column.name.clone(),
+            after,
+            up_to: None,
+        };
+        return Ok(Some((checkpoint, key_range)));
+    }
 
-            if !allowed && reader_column.column_type != writer_column.column_type {
-                return err;
-            }
+    // Watermark of the table and a key range up to the current largest value of the column
+    fn prepare_incremental(
+        reader: &mut Box<dyn DBReader>,
+        reader_info: &TableInfo,
+        settings: &TableMigratorSettings,
+    ) -> anyhow::Result<Option<(TableWatermark, KeyRange)>> {
+        let Some(column_name) = &settings.table_config.incremental else {
+            return Ok(None);
+        };
+        let Some(path) = &settings.state_file else {
+            return Err(anyhow::anyhow!("Incremental tables require a state file"));
+        };
+        if settings.checkpoint_file.is_some() {
+            return Err(anyhow::anyhow!(
+                "Incremental tables can't be moved with checkpoints"
+            ));
         }
-        return Ok(());
+        let column = reader_info
+            .column(column_name)
+            .filter(|column| WatermarkValue::is_supported(&column.column_type))
+            .with_context(|| {
+                format!(
+                    "Table \"{}\" has no integer, timestamp or date column {column_name} required for incremental moves",
+                    reader_info.name
+                )
+            })?;
+        let watermark = TableWatermark::new(path, &reader_info.name, column_name);
+        let after = watermark.load()?;
+        // Rows added while moving are left for the next run
+        let up_to = reader
+            .get_key_bounds(&reader_info.name, column)
+            .context("Unable to get largest value of incremental column")?
+            .map(|(_, max)| max);
+        let key_range = KeyRange {
+            column: column_name.clone(),
+            after,
+            up_to,
+        };
+        return Ok(Some((watermark, key_range)));
+    }
+
+    fn split_key_ranges(
+        reader: &mut Box<dyn DBReader>,
+        reader_info: &TableInfo,
+        settings: &TableMigratorSettings,
+    ) -> anyhow::Result<Vec<KeyRange>> {
+        if settings.reader_workers <= 1 {
+            return Ok(Vec::new());
+        }
+        let key_column = Self::get_key_column(reader_info);
+        let Some(key_column) = key_column else {
+            warn!(
+                "Table \"{}\" has no single integer or uuid primary key, it will be read by one worker",
+                reader_info.name
+            );
+            return Ok(Vec::new());
+        };
+        let bounds = reader
+            .get_key_bounds(&reader_info.name, key_column)
+            .context("Unable to get key bounds of source table")?;
+        return match bounds {
+            Some((min, max)) => {
+                KeyRange::split(&key_column.name, &min, &max, settings.reader_workers)
+            }
+            None => Ok(Vec::new()),
+        };
     }
 
     fn start_reading(
         mut reader: Box<dyn DBReader>,
         sender: channel::Sender,
         tracker: &TableMigrationProgress,
-        target_format: TableInfo,
+        mapping: &ColumnMapping,
+        filter: ReadFilter,
         stopped: &std::sync::atomic::AtomicBool,
     ) -> Result<(), MigratorError> {
-        let iterator = reader.read_iter(target_format)?;
+        let iterator = reader.read_iter(mapping.reader_format.clone(), filter)?;
         for result in iterator {
             if stopped.load(Ordering::Relaxed) {
                 return Err(MigratorError::Stopped);
             }
-            let row = result?;
+            let row = mapping.apply(result?);
             sender.send(row).map_err(|_| MigratorError::Stopped)?;
             tracker.inc_reader(1);
         }
-        tracker.finish_reader();
         return Ok(());
     }
 
@@ -163,29 +376,51 @@ impl TableMigrator {
         reciever: channel::Reciever,
         tracker: &TableMigrationProgress,
         table: &TableInfo,
-        batch_size: usize,
-        retries: usize,
+        settings: &TableMigratorSettings,
+        checkpoint: Option<&TableCheckpoint>,
         stopped: &std::sync::atomic::AtomicBool,
     ) -> Result<(), MigratorError> {
+        let batch_size = settings.batch_write_size;
         let mut batch: Vec<Row> = Vec::with_capacity(batch_size);
+        // Checkpoint is saved at most once per interval, the last written row is saved at the end
+        let mut saved_at: Option<Instant> = None;
+        let mut unsaved: Option<Row> = None;
+        let mut write = |batch: &[Row]| -> anyhow::Result<()> {
+            let retry = ExponentialRetry::new(settings.batch_write_retries);
+            writer
+                .write_batch_with_retry(batch, table, retry)
+                .context("Writing error")?;
+            if let (Some(checkpoint), Some(last_row)) = (checkpoint, batch.last()) {
+                if saved_at.is_none_or(|at| at.elapsed() >= CHECKPOINT_INTERVAL) {
+                    checkpoint
+                        .save(last_row)
+                        .context("Failed to save checkpoint")?;
+                    saved_at = Some(Instant::now());
+                    unsaved = None;
+                } else {
+                    unsaved = Some(last_row.clone());
+                }
+            }
+            tracker.inc_writer(batch.len().try_into().unwrap());
+            return Ok(());
+        };
         while let Ok(row) = reciever.recv() {
             if stopped.load(Ordering::Relaxed) {
                 return Err(MigratorError::Stopped);
             }
             batch.push(row);
             if batch.len() == batch_size {
-                writer
-                    .write_batch_with_retry(&batch, table, ExponentialRetry::new(retries))
-                    .context("Writing error")?;
-                tracker.inc_writer(batch.len().try_into().unwrap());
+                write(&batch)?;
                 batch.clear();
             }
         }
         if !batch.is_empty() {
-            writer
-                .write_batch_with_retry(&batch, table, ExponentialRetry::new(retries))
-                .context("Writing error")?;
-            tracker.inc_writer(batch.len().try_into().unwrap());
+            write(&batch)?;
+        }
+        if let (Some(checkpoint), Some(last_row)) = (checkpoint, unsaved) {
+            checkpoint
+                .save(&last_row)
+                .context("Failed to save checkpoint")?;
         }
         return Ok(());
     }
@@ -198,27 +433,41 @@ impl TableMigrator {
                 Err(e)
             }
         };
-        info!("Start moving data of table \"{}\"", self.target_format.name);
+        info!(
+            "Start moving data of table \"{}\"",
+            self.mapping.writer_format.name
+        );
+        let readers_left = std::sync::atomic::AtomicUsize::new(self.readers.len());
         return std::thread::scope(|s| {
             let mut handles = Vec::new();
-            handles.push(s.spawn(|| {
-                return process_result(Self::start_reading(
-                    self.reader,
-                    self.sender,
-                    &self.tracker,
-                    self.target_format.clone(),
-                    &self.stopped,
-                ));
-            }));
+            for (reader, filter) in self.readers {
+                let sender = self.sender.clone();
+                handles.push(s.spawn(|| {
+                    let result = Self::start_reading(
+                        reader,
+                        sender,
+                        &self.tracker,
+                        &self.mapping,
+                        filter,
+                        &self.stopped,
+                    );
+                    if result.is_ok() && readers_left.fetch_sub(1, Ordering::Relaxed) == 1 {
+                        self.tracker.finish_reader();
+                    }
+                    return process_result(result);
+                }));
+            }
+            // Channel is closed once all readers drop their senders
+            drop(self.sender);
             for writer in self.writers {
                 handles.push(s.spawn(|| {
                     return process_result(Self::start_writing(
                         writer,
                         self.reciever.clone(),
                         &self.tracker,
-                        &self.target_format,
-                        self.settings.batch_write_size,
-                        self.settings.batch_write_retries,
+                        &self.mapping.writer_format,
+                        &self.settings,
+                        self.checkpoint.as_ref(),
                         &self.stopped,
                     ));
                 }));
@@ -228,6 +477,12 @@ impl TableMigrator {
                 handle.join().unwrap()?;
             }
             self.tracker.finish_writer();
+            if let Some(checkpoint) = &self.checkpoint {
+                checkpoint.finish().context("Failed to save checkpoint")?;
+            }
+            if let Some((watermark, value)) = &self.watermark {
+                watermark.save(value).context("Failed to save watermark")?;
+            }
             return Ok(());
         });
     }
@@ -235,7 +490,7 @@ impl TableMigrator {
 
 #[cfg(test)]
 mod tests {
-    use crate::databases::table::TableInfo;
+    use crate::databases::table::{ColumnType, TableInfo, Value};
     use crate::databases::traits::{DBInfoProvider, ReaderIterator, WriterError};
 
     use super::*;
@@ -260,10 +515,14 @@ mod tests {
             fn get_table_info(&mut self, table: &str, no_count: bool) -> anyhow::Result<TableInfo>;
         }
         impl DBReader for DB {
-            fn read_iter<'a>(&'a mut self, target_format: TableInfo) -> anyhow::Result<ReaderIterator<'a>>;
+            fn opt_clone(&self) -> anyhow::Result<Box<dyn DBReader>>;
+            fn read_iter<'a>(&'a mut self, target_format: TableInfo, filter: ReadFilter) -> anyhow::Result<ReaderIterator<'a>>;
+            fn count_rows(&mut self, table: &str, condition: &str) -> anyhow::Result<u64>;
+            fn get_key_bounds(&mut self, table: &str, column: &Column) -> anyhow::Result<Option<(Value, Value)>>;
         }
         impl DBWriter for DB {
             fn write_batch(&mut self, batch: &[Row], table: &TableInfo) -> Result<(), WriterError>;
+            fn create_table(&mut self, table: &TableInfo) -> anyhow::Result<()>;
             fn recover(&mut self) -> anyhow::Result<()>;
         }
     }
@@ -274,15 +533,31 @@ mod tests {
                 queue_size: 10,
                 quiet: true,
                 no_count: false,
+                reader_workers: 1,
                 writer_workers: 1,
                 batch_write_size: 10,
                 batch_write_retries: 0,
+                write_mode: WriteMode::Insert,
+                fast_load: false,
+                checkpoint_file: None,
+                resume: false,
+                state_file: None,
+                table_config: TableConfig::default(),
             }
         }
     }
 
     const NUM_ROWS: u64 = 5;
 
+    fn default_mapping() -> ColumnMapping {
+        return ColumnMapping::new(
+            &TableInfo::default_in(),
+            &TableInfo::default_out(),
+            &TableConfig::default(),
+        )
+        .unwrap();
+    }
+
     const TABLE_NAME: &str = "test";
 
     impl TableInfo {
@@ -291,6 +566,7 @@ mod tests {
                 name: TABLE_NAME.to_string(),
                 num_rows: None,
                 columns: Vec::new(),
+                primary_key: Vec::new(),
             }
         }
 
@@ -299,6 +575,7 @@ mod tests {
                 name: TABLE_NAME.to_string(),
                 num_rows: Some(0),
                 columns: Vec::new(),
+                primary_key: Vec::new(),
             }
         }
     }
@@ -306,7 +583,7 @@ mod tests {
     #[test]
     fn test_reading() {
         let mut db_mock = MockDB::new();
-        db_mock.expect_read_iter().returning(|_| {
+        db_mock.expect_read_iter().returning(|_, _| {
             let mut rows = MockRowsIter::new();
             let mut count = 0;
             rows.expect_next().returning(move || {
@@ -326,7 +603,8 @@ mod tests {
             Box::new(db_mock),
             sender,
             &tracker,
-            TableInfo::default_out(),
+            &default_mapping(),
+            ReadFilter::default(),
             &stopped,
         );
         assert!(matches!(result, Ok(())));
@@ -337,7 +615,7 @@ mod tests {
     #[test]
     fn test_reading_stops_on_signal() {
         let mut db_mock = MockDB::new();
-        db_mock.expect_read_iter().returning(|_| {
+        db_mock.expect_read_iter().returning(|_, _| {
             let mut rows = MockRowsIter::new();
             rows.expect_next().returning(|| Some(Ok(Row::default())));
             Ok(Box::new(rows))
@@ -350,7 +628,8 @@ mod tests {
             Box::new(db_mock),
             sender,
             &tracker,
-            TableInfo::default_out(),
+            &default_mapping(),
+            ReadFilter::default(),
             &stopped,
         );
         assert!(matches!(result, Err(MigratorError::Stopped)));
@@ -359,7 +638,7 @@ mod tests {
     #[test]
     fn test_reading_stops_on_dropped_writers() {
         let mut db_mock = MockDB::new();
-        db_mock.expect_read_iter().returning(|_| {
+        db_mock.expect_read_iter().returning(|_, _| {
             let mut rows = MockRowsIter::new();
             rows.expect_next().returning(|| Some(Ok(Row::default())));
             Ok(Box::new(rows))
@@ -373,7 +652,8 @@ mod tests {
             Box::new(db_mock),
             sender,
             &tracker,
-            TableInfo::default_out(),
+            &default_mapping(),
+            ReadFilter::default(),
             &stopped,
         );
         assert!(matches!(result, Err(MigratorError::Stopped)));
@@ -400,8 +680,11 @@ mod tests {
             receiver,
             &tracker,
             &TableInfo::default_out(),
-            batch_size,
-            0,
+            &TableMigratorSettings {
+                batch_write_size: batch_size,
+                ..Default::default()
+            },
+            None,
             &stopped,
         );
         assert!(matches!(result, Ok(())));
@@ -432,8 +715,11 @@ mod tests {
             receiver,
             &tracker,
             &TableInfo::default_out(),
-            batch_size,
-            0,
+            &TableMigratorSettings {
+                batch_write_size: batch_size,
+                ..Default::default()
+            },
+            None,
             &stopped,
         );
         assert!(matches!(result, Ok(())));
@@ -452,8 +738,11 @@ mod tests {
             receiver,
             &tracker,
             &TableInfo::default_out(),
-            10,
-            3,
+            &TableMigratorSettings {
+                batch_write_retries: 3,
+                ..Default::default()
+            },
+            None,
             &stopped,
         );
         assert!(matches!(result, Err(MigratorError::Stopped)));
@@ -472,8 +761,11 @@ mod tests {
             receiver,
             &tracker,
             &TableInfo::default_out(),
-            10,
-            3,
+            &TableMigratorSettings {
+                batch_write_retries: 3,
+                ..Default::default()
+            },
+            None,
             &stopped,
         );
         assert!(matches!(result, Ok(())));
@@ -492,7 +784,7 @@ mod tests {
             .expect_get_table_info()
             .returning(|_, _| Ok(TableInfo::default_out()));
 
-        reader_mock.expect_read_iter().returning(|_| {
+        reader_mock.expect_read_iter().returning(|_, _| {
             let mut rows = MockRowsIter::new();
             let mut count = 0;
             rows.expect_next().returning(move || {
@@ -517,13 +809,123 @@ mod tests {
         let migrator = TableMigrator::new(
             Box::new(reader_mock),
             Box::new(writer_mock),
-            TABLE_NAME,
+            &TableMapping::new(TABLE_NAME),
+            settings,
+        )
+        .expect("Failed to create TableMigrator");
+
+        let result = migrator.run();
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_run_with_filter() {
+        let mut reader_mock = MockDB::new();
+        let mut writer_mock = MockDB::new();
+
+        reader_mock
+            .expect_get_table_info()
+            .withf(|_, no_count| *no_count)
+            .returning(|_, _| Ok(TableInfo::default_in()));
+        reader_mock
+            .expect_count_rows()
+            .withf(|_, condition| condition == "id > 1")
+            .times(1)
+            .returning(|_, _| Ok(NUM_ROWS));
+        writer_mock
+            .expect_get_table_info()
+            .returning(|_, _| Ok(TableInfo::default_out()));
+
+        reader_mock.expect_read_iter().returning(|_, filter| {
+            assert_eq!(filter.condition.as_deref(), Some("id > 1"));
+            assert_eq!(filter.key_range, None);
+            let mut rows = MockRowsIter::new();
+            rows.expect_next().returning(|| None);
+            Ok(Box::new(rows))
+        });
+
+        let settings = TableMigratorSettings::default().with_table_config(TableConfig {
+            filter: Some("id > 1".to_string()),
+            ..Default::default()
+        });
+        let migrator = TableMigrator::new(
+            Box::new(reader_mock),
+            Box::new(writer_mock),
+            &TableMapping::new(TABLE_NAME),
+            settings,
+        )
+        .expect("Failed to create TableMigrator");
+
+        assert!(migrator.run().is_ok());
+    }
+
+    #[test]
+    fn test_run_multiple_readers() {
+        let mut reader_mock = MockDB::new();
+        let mut writer_mock = MockDB::new();
+        let table_info = TableInfo {
+            name: TABLE_NAME.to_string(),
+            num_rows: Some(0),
+            columns: vec![Column {
+                name: "id".to_string(),
+                column_type: ColumnType::I64,
+                nullable: false,
+            }],
+            primary_key: vec!["id".to_string()],
+        };
+
+        let reader_info = table_info.clone();
+        reader_mock
+            .expect_get_table_info()
+            .returning(move |_, _| Ok(reader_info.clone()));
+        let writer_info = table_info.clone();
+        writer_mock
+            .expect_get_table_info()
+            .returning(move |_, _| Ok(writer_info.clone()));
+        reader_mock
+            .expect_get_key_bounds()
+            .times(1)
+            .returning(|_, _| Ok(Some((Value::I64(1), Value::I64(100)))));
+        reader_mock.expect_opt_clone().times(2).returning(|| {
+            let mut partition_mock = MockDB::new();
+            partition_mock.expect_read_iter().returning(|_, filter| {
+                assert!(filter.key_range.is_some());
+                let mut rows = MockRowsIter::new();
+                let mut count = 0;
+                rows.expect_next().returning(move || {
+                    if count == NUM_ROWS {
+                        return None;
+                    }
+                    count += 1;
+                    Some(Ok(Row::default()))
+                });
+                Ok(Box::new(rows))
+            });
+            Ok(Box::new(partition_mock))
+        });
+
+        let written = std::sync::Arc::new(std::sync::atomic::AtomicU64::new(0));
+        let written_by_writer = written.clone();
+        writer_mock.expect_write_batch().returning(move |rows, _| {
+            written_by_writer.fetch_add(rows.len() as u64, Ordering::Relaxed);
+            Ok(())
+        });
+
+        let settings = TableMigratorSettings {
+            reader_workers: 2,
+            ..Default::default()
+        };
+        let migrator = TableMigrator::new(
+            Box::new(reader_mock),
+            Box::new(writer_mock),
+            &TableMapping::new(TABLE_NAME),
             settings,
         )
         .expect("Failed to create TableMigrator");
 
         let result = migrator.run();
         assert!(result.is_ok());
+        assert_eq!(written.load(Ordering::Relaxed), NUM_ROWS * 2);
     }
 
     #[test]
@@ -539,7 +941,7 @@ mod tests {
             .expect_get_table_info()
             .returning(|_, _| Ok(TableInfo::default_out()));
 
-        reader_mock.expect_read_iter().returning(|_| {
+        reader_mock.expect_read_iter().returning(|_, _| {
             let mut rows = MockRowsIter::new();
             rows.expect_next()
                 .returning(move || Some(Err(anyhow::anyhow!("Test error"))));
@@ -550,7 +952,7 @@ mod tests {
         let migrator = TableMigrator::new(
             Box::new(reader_mock),
             Box::new(writer_mock),
-            TABLE_NAME,
+            &TableMapping::new(TABLE_NAME),
             settings,
         )
         .expect("Failed to create TableMigrator");
@@ -576,7 +978,7 @@ mod tests {
             .expect_get_table_info()
             .returning(|_, _| Ok(TableInfo::default_out()));
 
-        reader_mock.expect_read_iter().returning(|_| {
+        reader_mock.expect_read_iter().returning(|_, _| {
             let mut rows = MockRowsIter::new();
             let mut count = 0;
             rows.expect_next().returning(move || {
@@ -598,7 +1000,7 @@ mod tests {
         let migrator = TableMigrator::new(
             Box::new(reader_mock),
             Box::new(writer_mock),
-            TABLE_NAME,
+            &TableMapping::new(TABLE_NAME),
             settings,
         )
         .expect("Failed to create TableMigrator");
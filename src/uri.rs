@@ -5,6 +5,7 @@ pub enum URI {
     Sqlite(String),
     Postgres(String),
     Mysql(String),
+    Csv(String),
 }
 
 impl FromStr for URI {
@@ -20,10 +21,20 @@ impl FromStr for URI {
         if s.starts_with("mysql://") {
             return Ok(URI::Mysql(s.to_owned()));
         }
+        if s.starts_with("csv://") {
+            return Ok(URI::Csv(s.to_owned()));
+        }
         return Err("Unknown URI format".to_string());
     }
 }
 
+impl URI {
+    // Table names may be qualified with a schema (PostgreSQL) or a database (MySQL)
+    pub fn has_schemas(&self) -> bool {
+        return matches!(self, URI::Postgres(_) | URI::Mysql(_));
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -46,6 +57,12 @@ mod tests {
         assert!(matches!(uri, Ok(URI::Mysql(_))));
     }
 
+    #[test]
+    fn test_uri_from_str_csv() {
+        let uri = URI::from_str("csv://export/orders.csv");
+        assert!(matches!(uri, Ok(URI::Csv(_))));
+    }
+
     #[test]
     fn test_uri_from_str_invalid() {
         let uri = URI::from_str("invalid://test");
@@ -0,0 +1,266 @@
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use anyhow::Context;
+use tracing::{info, warn};
+
+use crate::args::Args;
+use crate::column_mapping::ColumnMapping;
+use crate::config::{Config, TableConfig};
+use crate::databases::table::{ReadFilter, Row, TableInfo, TableMapping, Value};
+use crate::databases::traits::DBReader;
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RowDifference {
+    MissingInOutput(String),
+    UnexpectedInOutput(String),
+    Changed(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TableReport {
+    pub table: String,
+    pub input_rows: u64,
+    pub output_rows: u64,
+    pub checksums_match: bool,
+    // Filled only in the detailed mode for tables with a primary key
+    pub differences: Vec<RowDifference>,
+}
+
+impl TableReport {
+    pub fn is_match(&self) -> bool {
+        return self.input_rows == self.output_rows
+            && self.checksums_match
+            && self.differences.is_empty();
+    }
+
+    fn log(&self) {
+        let table = &self.table;
+        if self.is_match() {
+            info!("Table \"{table}\" verified, {} rows match", self.input_rows);
+            return;
+        }
+        warn!(
+            "Table \"{table}\" differs: {} rows in input, {} rows in output, checksums {}",
+            self.input_rows,
+            self.output_rows,
+            if self.checksums_match {
+                "match"
+            } else {
+                "differ"
+            }
+        );
+        for difference in &self.differences {
+            match difference {
+                RowDifference::MissingInOutput(key) => {
+                    warn!("Table \"{table}\" row ({key}) is missing in output")
+                }
+                RowDifference::UnexpectedInOutput(key) => {
+                    warn!("Table \"{table}\" row ({key}) is not present in input")
+                }
+                RowDifference::Changed(key) => {
+                    warn!("Table \"{table}\" row ({key}) differs")
+                }
+            }
+        }
+    }
+}
+
+pub(crate) fn hash_value(value: &Value, state: &mut impl Hasher) {
+    std::mem::discriminant(value).hash(state);
+    match value {
+        Value::String(val) => val.hash(state),
+        Value::Bytes(val) => val.hash(state),
+        Value::I64(val) => val.hash(state),
+        Value::I32(val) => val.hash(state),
+        Value::I16(val) => val.hash(state),
+        Value::F64(val) => val.to_bits().hash(state),
+        Value::F32(val) => val.to_bits().hash(state),
+        Value::Decimal(val) => val.hash(state),
+        Value::Bool(val) => val.hash(state),
+        Value::Timestamptz(val) => val.hash(state),
+        Value::Timestamp(val) => val.hash(state),
+        Value::Date(val) => val.hash(state),
+        Value::Time(val) => val.hash(state),
+        // Object keys are sorted, so the same json always gives the same string
+        Value::Json(val) => val.to_string().hash(state),
+        Value::Uuid(val) => val.hash(state),
+        Value::Array(items) => {
+            items.len().hash(state);
+            for item in items {
+                hash_value(item, state);
+            }
+        }
+        Value::Null => {}
+    }
+}
+
+fn hash_row(row: &Row) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for value in row {
+        hash_value(value, &mut hasher);
+    }
+    return hasher.finish();
+}
+
+fn format_key(table: &TableInfo, key_positions: &[usize], row: &Row) -> String {
+    return key_positions
+        .iter()
+        .map(|&idx| format!("{}={:?}", table.columns[idx].name, row[idx]))
+        .collect::<Vec<_>>()
+        .join(", ");
+}
+
+// Number of rows and sum of their hashes, which doesn't depend on the order of rows
+#[derive(Debug, Default, PartialEq)]
+struct TableDigest {
+    num_rows: u64,
+    checksum: u64,
+}
+
+impl TableDigest {
+    fn add(&mut self, row_hash: u64) {
+        self.num_rows += 1;
+        self.checksum = self.checksum.wrapping_add(row_hash);
+    }
+}
+
+pub fn verify_table(
+    input: &mut dyn DBReader,
+    output: &mut dyn DBReader,
+    table: &TableMapping,
+    config: &TableConfig,
+    detailed: bool,
+) -> anyhow::Result<TableReport> {
+    // Input rows are mapped to the output columns, as it happens while moving
+    let reader_info = input
+        .get_table_info(&table.source, true)
+        .context("Unable to get information about source table")?;
+    let writer_info = output
+        .get_table_info(&table.destination, true)
+        .context("Unable to get information about destination table")?;
+    let mapping = ColumnMapping::new(&reader_info, &writer_info, config)?;
+    let target_format = &mapping.writer_format;
+    let mut key_positions = None;
+    if detailed {
+        key_positions = target_format
+            .primary_key
+            .iter()
+            .map(|key| target_format.columns.iter().position(|c| &c.name == key))
+            .collect::<Option<Vec<usize>>>()
+            .filter(|positions| !positions.is_empty());
+        if key_positions.is_none() {
+            warn!("Table \"{table}\" has no primary key, differing rows won't be reported");
+        }
+    }
+
+    let mut input_digest = TableDigest::default();
+    let mut input_hashes: HashMap<String, u64> = HashMap::new();
+    // Only filtered rows of input are expected in output
+    let input_filter = ReadFilter {
+        key_range: None,
+        condition: config.filter.clone(),
+    };
+    for row in input.read_iter(mapping.reader_format.clone(), input_filter)? {
+        let row = mapping.apply(row.context("Failed to read input row")?);
+        let row_hash = hash_row(&row);
+        input_digest.add(row_hash);
+        if let Some(positions) = &key_positions {
+            input_hashes.insert(format_key(target_format, positions, &row), row_hash);
+        }
+    }
+
+    let mut output_digest = TableDigest::default();
+    let mut differences = Vec::new();
+    for row in output.read_iter(target_format.clone(), ReadFilter::default())? {
+        let row = row.context("Failed to read output row")?;
+        let row_hash = hash_row(&row);
+        output_digest.add(row_hash);
+        if let Some(positions) = &key_positions {
+            let key = format_key(target_format, positions, &row);
+            match input_hashes.remove(&key) {
+                None => differences.push(RowDifference::UnexpectedInOutput(key)),
+                Some(input_hash) if input_hash != row_hash => {
+                    differences.push(RowDifference::Changed(key))
+                }
+                Some(_) => {}
+            }
+        }
+    }
+    differences.extend(input_hashes.into_keys().map(RowDifference::MissingInOutput));
+    differences.sort();
+
+    return Ok(TableReport {
+        table: table.to_string(),
+        input_rows: input_digest.num_rows,
+        output_rows: output_digest.num_rows,
+        checksums_match: input_digest.checksum == output_digest.checksum,
+        differences,
+    });
+}
+
+pub fn verify_tables(args: &Args, config: &Config, tables: &[TableMapping]) -> anyhow::Result<()> {
+    let mut mismatched = Vec::new();
+    for table in tables {
+        info!("Verifying table \"{table}\"");
+        let mut input = args.create_reader()?;
+        let mut output = args.create_output_reader()?;
+        let report = verify_table(
+            input.as_mut(),
+            output.as_mut(),
+            table,
+            &config.table(&table.source),
+            args.verify_detailed,
+        )
+        .with_context(|| format!("Failed to verify table \"{table}\""))?;
+        report.log();
+        if !report.is_match() {
+            mismatched.push(format!("\"{table}\""));
+        }
+    }
+    if !mismatched.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Verification failed for tables: {}",
+            mismatched.join(", ")
+        ));
+    }
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_digest_order_independent() {
+        let rows = [
+            vec![Value::I64(1), Value::String("a".to_string())],
+            vec![Value::I64(2), Value::Null],
+            vec![Value::I64(3), Value::F64(0.5)],
+        ];
+        let mut forward = TableDigest::default();
+        rows.iter().for_each(|row| forward.add(hash_row(row)));
+        let mut backward = TableDigest::default();
+        rows.iter()
+            .rev()
+            .for_each(|row| backward.add(hash_row(row)));
+        assert_eq!(forward, backward);
+        assert_eq!(forward.num_rows, 3);
+    }
+
+    #[test]
+    fn test_hash_row_distinguishes_values() {
+        assert_ne!(
+            hash_row(&vec![Value::I64(1), Value::Null]),
+            hash_row(&vec![Value::Null, Value::I64(1)])
+        );
+        assert_ne!(
+            hash_row(&vec![Value::I64(1)]),
+            hash_row(&vec![Value::I32(1)])
+        );
+        assert_ne!(
+            hash_row(&vec![Value::Json(serde_json::json!({"a": 1}))]),
+            hash_row(&vec![Value::Json(serde_json::json!({"a": 2}))])
+        );
+    }
+}
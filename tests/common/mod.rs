@@ -28,6 +28,6 @@ fn rm_container_by_name(name: &str) {
     let _ = std::process::Command::new("docker")
         .stdout(Stdio::null())
         .stderr(Stdio::null())
-        .args(&["rm", "-f", "-v", name])
+        .args(["rm", "-f", "-v", name])
         .status();
 }
@@ -1,6 +1,7 @@
 use itertools::Itertools;
 use std::sync::LazyLock;
 
+use db_mover::databases::identifier::IdentifierQuote;
 use fake::{Fake, Faker};
 use testcontainers::{Container, ImageExt, runners::SyncRunner};
 
@@ -75,9 +76,13 @@ impl TestableDatabase for TestMysqlDatabase {
         self.connection.query_drop(query.as_ref()).unwrap();
     }
 
+    fn quote(&self, name: &str) -> String {
+        return IdentifierQuote::MYSQL.quote(name);
+    }
+
     fn create_test_table(&mut self, name: &str) {
         let query = format!(
-            "CREATE TABLE {name} (id BIGINT PRIMARY KEY, real_field FLOAT, text_field TEXT, blob_field BLOB, timestamp_field DATETIME)"
+            "CREATE TABLE {name} (id BIGINT PRIMARY KEY, real_field FLOAT, text_field TEXT, blob_field BLOB, timestamp_field DATETIME(6), time_field TIME(6))"
         );
         self.connection
             .query_drop(&query)
@@ -98,7 +103,7 @@ impl TestableDatabase for TestMysqlDatabase {
         }
         for chunk in rows.into_iter().chunks(100).into_iter() {
             trx.exec_batch(
-                format!("INSERT INTO {name} VALUES (:id, :real_field, :text_field, :blob_field, :timestamp_field)"),
+                format!("INSERT INTO {name} VALUES (:id, :real_field, :text_field, :blob_field, :timestamp_field, :time_field)"),
                 chunk.map(|row| {
                     params! {
                         "id" => row.id,
@@ -106,6 +111,7 @@ impl TestableDatabase for TestMysqlDatabase {
                         "text_field" => row.text,
                         "blob_field" => row.blob,
                         "timestamp_field" => row.timestamp,
+                        "time_field" => row.time,
                     }
                 }),
             )
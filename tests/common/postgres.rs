@@ -1,5 +1,6 @@
 use std::sync::LazyLock;
 
+use db_mover::databases::identifier::IdentifierQuote;
 use fake::{Fake, Faker};
 use itertools::Itertools;
 use testcontainers::{Container, ImageExt, runners::SyncRunner};
@@ -22,6 +23,8 @@ static POSTGRES_CONTAINER: LazyLock<Container<testcontainers_modules::postgres::
 
         testcontainers_modules::postgres::Postgres::default()
             .with_tag("17-alpine")
+            // Logical replication slots need logical WAL
+            .with_cmd(["postgres", "-c", "wal_level=logical"])
             .with_container_name(name)
             .start()
             .unwrap()
@@ -65,8 +68,8 @@ impl TestPostresDatabase {
 fn generate_placeholders(blocks: usize) -> String {
     (0..blocks)
         .map(|i| {
-            let start = i * 5 + 1;
-            let params = (start..start + 5).map(|n| format!("${}", n)).join(", ");
+            let start = i * 6 + 1;
+            let params = (start..start + 6).map(|n| format!("${}", n)).join(", ");
             format!("({})", params)
         })
         .join(", ")
@@ -81,9 +84,13 @@ impl TestableDatabase for TestPostresDatabase {
         self.client.execute(query.as_ref(), &[]).unwrap();
     }
 
+    fn quote(&self, name: &str) -> String {
+        return IdentifierQuote::ANSI.quote(name);
+    }
+
     fn create_test_table(&mut self, name: &str) {
         let query = format!(
-            "CREATE TABLE {name} (id BIGINT PRIMARY KEY, real_field REAL, text_field TEXT, blob_field BYTEA, timestamp_field TIMESTAMP)"
+            "CREATE TABLE {name} (id BIGINT PRIMARY KEY, real_field REAL, text_field TEXT, blob_field BYTEA, timestamp_field TIMESTAMP, time_field TIME)"
         );
         self.client
             .execute(&query, &[])
@@ -107,6 +114,7 @@ impl TestableDatabase for TestPostresDatabase {
                 params.push(&row.text);
                 params.push(&row.blob);
                 params.push(&row.timestamp);
+                params.push(&row.time);
             }
 
             let placeholders = generate_placeholders(chunk.len());
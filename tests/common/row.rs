@@ -1,4 +1,4 @@
-use chrono::NaiveDateTime;
+use chrono::{NaiveDateTime, NaiveTime};
 use fake::Dummy;
 
 #[derive(Debug, Dummy, Clone)]
@@ -9,9 +9,11 @@ pub struct TestRow {
     #[dummy(expr = "fake::vec![u8; 0..200]")]
     pub blob: Vec<u8>,
     #[dummy(
-        expr = "chrono::NaiveDate::from_ymd_opt(2016, 7, 8).unwrap().and_hms_opt(0, 0, 0).unwrap()"
+        expr = "chrono::NaiveDate::from_ymd_opt(2016, 7, 8).unwrap().and_hms_micro_opt(12, 34, 56, 789012).unwrap()"
     )]
     pub timestamp: NaiveDateTime,
+    #[dummy(expr = "NaiveTime::from_hms_micro_opt(10, 23, 54, 123456).unwrap()")]
+    pub time: NaiveTime,
 }
 
 fn approx_equal_f32(a: f32, b: f32, epsilon: f32) -> bool {
@@ -25,6 +27,7 @@ impl PartialEq for TestRow {
             && self.text == other.text
             && self.blob == other.blob
             && self.timestamp == other.timestamp
+            && self.time == other.time
     }
 }
 
@@ -36,6 +39,7 @@ impl From<postgres::Row> for TestRow {
             text: row.get(2),
             blob: row.get(3),
             timestamp: row.get(4),
+            time: row.get(5),
         };
     }
 }
@@ -48,6 +52,7 @@ impl From<&rusqlite::Row<'_>> for TestRow {
             text: row.get_unwrap(2),
             blob: row.get_unwrap(3),
             timestamp: row.get_unwrap(4),
+            time: row.get_unwrap(5),
         };
     }
 }
@@ -60,6 +65,7 @@ impl From<mysql::Row> for TestRow {
             text: row.get(2).unwrap(),
             blob: row.get(3).unwrap(),
             timestamp: row.get(4).unwrap(),
+            time: row.get(5).unwrap(),
         };
     }
 }
@@ -1,5 +1,6 @@
 use std::path::PathBuf;
 
+use db_mover::databases::identifier::IdentifierQuote;
 use db_mover::uri::URI;
 use fake::{Fake, Faker};
 use rusqlite::{Connection, OpenFlags, params};
@@ -50,9 +51,13 @@ impl TestableDatabase for TestSqliteDatabase {
         self.conn.execute(query.as_ref(), []).unwrap();
     }
 
+    fn quote(&self, name: &str) -> String {
+        return IdentifierQuote::ANSI.quote(name);
+    }
+
     fn create_test_table(&mut self, table_name: &str) {
         let query = format!(
-            "CREATE TABLE {table_name} (id BIGINT PRIMARY KEY NOT NULL, real_field REAL, text_field TEXT, blob_field BLOB, timestamp_field DATETIME)"
+            "CREATE TABLE {table_name} (id BIGINT PRIMARY KEY NOT NULL, real_field REAL, text_field TEXT, blob_field BLOB, timestamp_field DATETIME, time_field TIME)"
         );
         self.conn.execute(&query, []).unwrap();
     }
@@ -60,13 +65,20 @@ impl TestableDatabase for TestSqliteDatabase {
     fn fill_test_table(&mut self, table_name: &str, num_rows: usize) {
         let trx = self.conn.transaction().unwrap();
         {
-            let query = format!("INSERT INTO {table_name} VALUES (?1, ?2, ?3, ?4, ?5)");
+            let query = format!("INSERT INTO {table_name} VALUES (?1, ?2, ?3, ?4, ?5, ?6)");
             let mut stmt = trx.prepare(&query).unwrap();
 
             for i in 1..num_rows + 1 {
                 let row: TestRow = Faker.fake();
-                stmt.execute(params![i, row.real, row.text, row.blob, row.timestamp])
-                    .unwrap();
+                stmt.execute(params![
+                    i,
+                    row.real,
+                    row.text,
+                    row.blob,
+                    row.timestamp,
+                    row.time
+                ])
+                .unwrap();
             }
         }
         trx.commit().unwrap();
@@ -7,6 +7,9 @@ pub trait TestableDatabase {
 
     fn execute(&mut self, query: impl AsRef<str>);
 
+    // Name quoted for the helpers below, which use table names verbatim
+    fn quote(&self, name: &str) -> String;
+
     fn create_test_table(&mut self, table_name: &str);
 
     fn fill_test_table(&mut self, table_name: &str, num_rows: usize);
@@ -14,4 +17,15 @@ pub trait TestableDatabase {
     fn get_all_rows(&mut self, table_name: &str) -> Vec<TestRow>;
 
     fn query_count(&mut self, query: impl AsRef<str>) -> u32;
+
+    // Waits for changes applied by another thread
+    fn wait_for_count(&mut self, query: &str, expected: u32) {
+        for _ in 0..60 {
+            if self.query_count(query) == expected {
+                return;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(500));
+        }
+        panic!("{query} didn't return {expected}");
+    }
 }
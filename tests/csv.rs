@@ -0,0 +1,105 @@
+mod common;
+
+use common::sqlite::TestSqliteDatabase;
+use common::testable_database::TestableDatabase;
+use db_mover::databases::csv::CsvDB;
+use db_mover::databases::traits::DBInfoProvider;
+use db_mover::uri::URI;
+use pretty_assertions::assert_eq;
+
+use rstest::rstest;
+
+#[rstest]
+fn csv_directory_roundtrip() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let csv_uri = URI::Csv(format!("csv://{}", tmp_dir.path().to_str().unwrap()));
+    let mut in_db = TestSqliteDatabase::new();
+    let mut out_db = TestSqliteDatabase::new();
+    in_db.create_test_table("test");
+    in_db.create_test_table("test1");
+    in_db.fill_test_table("test", 10);
+    in_db.fill_test_table("test1", 10);
+
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), csv_uri.clone());
+    args.create_tables = true;
+    db_mover::run(args).unwrap();
+    assert!(tmp_dir.path().join("test.csv").exists());
+    assert!(tmp_dir.path().join("test.schema.json").exists());
+
+    let mut args = db_mover::args::Args::new(csv_uri, out_db.get_uri());
+    args.create_tables = true;
+    db_mover::run(args).unwrap();
+
+    assert_eq!(in_db.get_all_rows("test"), out_db.get_all_rows("test"));
+    assert_eq!(in_db.get_all_rows("test1"), out_db.get_all_rows("test1"));
+}
+
+#[rstest]
+fn csv_dotted_table_name() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let csv_uri = URI::Csv(format!("csv://{}", tmp_dir.path().to_str().unwrap()));
+    let mut in_db = TestSqliteDatabase::new();
+    in_db.create_test_table("test");
+    in_db.fill_test_table("test", 10);
+
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), csv_uri);
+    args.table.push("test:archive.test".to_string());
+    args.create_tables = true;
+    db_mover::run(args).unwrap();
+    assert!(tmp_dir.path().join("archive.test.csv").exists());
+}
+
+#[rstest]
+fn csv_single_file() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let path = tmp_dir.path().join("test.csv");
+    let csv_uri = URI::Csv(format!("csv://{}", path.to_str().unwrap()));
+    let mut in_db = TestSqliteDatabase::new();
+    in_db.create_test_table("test");
+    in_db.fill_test_table("test", 10);
+
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), csv_uri.clone());
+    args.table.push("test".to_string());
+    args.create_tables = true;
+    db_mover::run(args).unwrap();
+
+    let mut db = CsvDB::new(&format!("csv://{}", path.to_str().unwrap())).unwrap();
+    assert_eq!(db.get_tables().unwrap(), vec![String::from("test")]);
+    assert_eq!(db.get_table_info("test", false).unwrap().num_rows, Some(10));
+}
+
+#[rstest]
+fn csv_without_schema() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        tmp_dir.path().join("test.csv"),
+        "name,comment\nfirst,\\N\n\"second, quoted\",\"multi\nline\"\n",
+    )
+    .unwrap();
+    let csv_uri = URI::Csv(format!("csv://{}", tmp_dir.path().to_str().unwrap()));
+    let mut out_db = TestSqliteDatabase::new();
+    out_db.execute("CREATE TABLE test (comment TEXT, name TEXT)");
+
+    let args = db_mover::args::Args::new(csv_uri, out_db.get_uri());
+    db_mover::run(args).unwrap();
+
+    let mut stmt = out_db
+        .conn
+        .prepare("SELECT name, comment FROM test ORDER BY name")
+        .unwrap();
+    let result: Vec<(String, Option<String>)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .map(|row| row.unwrap())
+        .collect();
+    assert_eq!(
+        result,
+        vec![
+            ("first".to_string(), None),
+            (
+                "second, quoted".to_string(),
+                Some("multi\nline".to_string())
+            ),
+        ]
+    );
+}
@@ -1,11 +1,17 @@
 mod common;
 
+use std::collections::HashMap;
 use std::{thread::sleep, time::Duration};
 
 use common::mysql::TestMysqlDatabase;
 use common::postgres::TestPostresDatabase;
 use common::sqlite::TestSqliteDatabase;
 use common::testable_database::TestableDatabase;
+use db_mover::checkpoint::{self, Checkpoint, CheckpointKey};
+use db_mover::config::TableConfig;
+use db_mover::databases::table::TableMapping;
+use db_mover::databases::write_mode::WriteMode;
+use db_mover::verify::{self, RowDifference};
 use pretty_assertions::{assert_eq, assert_ne};
 
 use rstest::rstest;
@@ -90,6 +96,281 @@ fn multiple_tables(mut in_db: impl TestableDatabase, mut out_db: impl TestableDa
     assert_eq!(in_db.get_all_rows("test1"), out_db.get_all_rows("test1"));
 }
 
+#[apply(all_databases_combinations)]
+fn multiple_reader_workers(mut in_db: impl TestableDatabase, mut out_db: impl TestableDatabase) {
+    create_test_tables(&mut in_db, &mut out_db);
+    in_db.fill_test_table("test", 100);
+
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    args.table.push("test".to_string());
+    args.reader_workers = 4;
+    db_mover::run(args).unwrap();
+
+    assert_eq!(in_db.get_all_rows("test"), out_db.get_all_rows("test"));
+}
+
+#[apply(all_databases_combinations)]
+fn resume_from_checkpoint(mut in_db: impl TestableDatabase, mut out_db: impl TestableDatabase) {
+    create_test_tables(&mut in_db, &mut out_db);
+    in_db.fill_test_table("test", 100);
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let checkpoint_file = tmp_dir.path().join("checkpoint.json");
+
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    args.table.push("test".to_string());
+    args.batch_write_size = 10;
+    args.checkpoint_file = Some(checkpoint_file.clone());
+    db_mover::run(args.clone()).unwrap();
+
+    assert_eq!(in_db.get_all_rows("test"), out_db.get_all_rows("test"));
+    let checkpoints = checkpoint::load(&checkpoint_file).unwrap();
+    assert!(checkpoints["test"].finished);
+    assert_eq!(checkpoints["test"].key, Some(CheckpointKey::I64(100)));
+
+    // Simulate interruption after the first 60 rows, with the checkpoint saved after 40 rows
+    out_db.execute("DELETE FROM test WHERE id > 60");
+    let interrupted = HashMap::from([(
+        "test",
+        Checkpoint {
+            column: "id".to_string(),
+            key: Some(CheckpointKey::I64(40)),
+            finished: false,
+        },
+    )]);
+    std::fs::write(
+        &checkpoint_file,
+        serde_json::to_string(&interrupted).unwrap(),
+    )
+    .unwrap();
+
+    args.resume = true;
+    db_mover::run(args.clone()).unwrap();
+    assert_eq!(in_db.get_all_rows("test"), out_db.get_all_rows("test"));
+
+    // Finished table is skipped even though it is not empty
+    db_mover::run(args).unwrap();
+    assert_eq!(in_db.get_all_rows("test"), out_db.get_all_rows("test"));
+}
+
+#[apply(all_databases_combinations)]
+fn verify_after_move(mut in_db: impl TestableDatabase, mut out_db: impl TestableDatabase) {
+    create_test_tables(&mut in_db, &mut out_db);
+    in_db.fill_test_table("test", 10);
+    in_db.fill_test_table("test1", 10);
+
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    args.verify = true;
+    db_mover::run(args.clone()).unwrap();
+
+    args.command = Some(db_mover::args::Command::Verify);
+    args.verify_detailed = true;
+    db_mover::run(args).unwrap();
+}
+
+#[apply(all_databases_combinations)]
+fn verify_finds_differences(mut in_db: impl TestableDatabase, mut out_db: impl TestableDatabase) {
+    create_test_tables(&mut in_db, &mut out_db);
+    in_db.fill_test_table("test", 10);
+    in_db.fill_test_table("test1", 10);
+
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    db_mover::run(args.clone()).unwrap();
+    out_db.execute("UPDATE test SET text_field = 'changed' WHERE id = 5");
+    out_db.execute("DELETE FROM test WHERE id = 7");
+
+    args.command = Some(db_mover::args::Command::Verify);
+    assert!(db_mover::run(args.clone()).is_err());
+
+    let report = verify::verify_table(
+        args.create_reader().unwrap().as_mut(),
+        args.create_output_reader().unwrap().as_mut(),
+        &TableMapping::new("test"),
+        &TableConfig::default(),
+        true,
+    )
+    .unwrap();
+    assert_eq!(report.input_rows, 10);
+    assert_eq!(report.output_rows, 9);
+    assert!(!report.checksums_match);
+    assert_eq!(
+        report.differences,
+        vec![
+            RowDifference::MissingInOutput("id=I64(7)".to_string()),
+            RowDifference::Changed("id=I64(5)".to_string()),
+        ]
+    );
+
+    let report = verify::verify_table(
+        args.create_reader().unwrap().as_mut(),
+        args.create_output_reader().unwrap().as_mut(),
+        &TableMapping::new("test1"),
+        &TableConfig::default(),
+        true,
+    )
+    .unwrap();
+    assert!(report.is_match());
+}
+
+#[apply(all_databases_combinations)]
+fn upsert_into_non_empty(mut in_db: impl TestableDatabase, mut out_db: impl TestableDatabase) {
+    create_test_tables(&mut in_db, &mut out_db);
+    in_db.fill_test_table("test", 10);
+
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    args.table.push("test".to_string());
+    db_mover::run(args.clone()).unwrap();
+    out_db.execute("UPDATE test SET text_field = 'changed' WHERE id = 5");
+    out_db.execute("DELETE FROM test WHERE id = 7");
+
+    assert!(db_mover::run(args.clone()).is_err());
+    args.write_mode = WriteMode::Upsert;
+    db_mover::run(args).unwrap();
+
+    assert_eq!(in_db.get_all_rows("test"), out_db.get_all_rows("test"));
+}
+
+#[apply(all_databases_combinations)]
+fn ignore_duplicates(mut in_db: impl TestableDatabase, mut out_db: impl TestableDatabase) {
+    create_test_tables(&mut in_db, &mut out_db);
+    in_db.fill_test_table("test", 10);
+
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    args.table.push("test".to_string());
+    db_mover::run(args.clone()).unwrap();
+    out_db.execute("UPDATE test SET text_field = 'changed' WHERE id = 5");
+    out_db.execute("DELETE FROM test WHERE id = 7");
+
+    args.write_mode = WriteMode::IgnoreDuplicates;
+    db_mover::run(args.clone()).unwrap();
+
+    let report = verify::verify_table(
+        args.create_reader().unwrap().as_mut(),
+        args.create_output_reader().unwrap().as_mut(),
+        &TableMapping::new("test"),
+        &TableConfig::default(),
+        true,
+    )
+    .unwrap();
+    assert_eq!(
+        report.differences,
+        vec![RowDifference::Changed("id=I64(5)".to_string())]
+    );
+}
+
+#[apply(all_databases_combinations)]
+fn filter_rows(mut in_db: impl TestableDatabase, mut out_db: impl TestableDatabase) {
+    create_test_tables(&mut in_db, &mut out_db);
+    in_db.fill_test_table("test", 10);
+    in_db.fill_test_table("test1", 10);
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let config = tmp_dir.path().join("config.json");
+    std::fs::write(&config, r#"{"tables": {"test1": {"where": "id <= 3"}}}"#).unwrap();
+
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    args.filter.push("test=id > 5 AND id <> 7".to_string());
+    args.config = Some(config);
+    args.verify = true;
+    db_mover::run(args).unwrap();
+
+    let expected: Vec<_> = in_db
+        .get_all_rows("test")
+        .into_iter()
+        .filter(|row| row.id > 5 && row.id != 7)
+        .collect();
+    assert_eq!(expected, out_db.get_all_rows("test"));
+    let expected: Vec<_> = in_db
+        .get_all_rows("test1")
+        .into_iter()
+        .filter(|row| row.id <= 3)
+        .collect();
+    assert_eq!(expected, out_db.get_all_rows("test1"));
+}
+
+#[apply(all_databases_combinations)]
+fn map_columns(mut in_db: impl TestableDatabase, mut out_db: impl TestableDatabase) {
+    create_test_tables(&mut in_db, &mut out_db);
+    in_db.fill_test_table("test", 10);
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let config = tmp_dir.path().join("config.json");
+    std::fs::write(
+        &config,
+        r#"{"tables": {"test": {"columns": {"text_field": null}, "constants": {"text_field": "moved"}}}}"#,
+    )
+    .unwrap();
+
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    args.table.push("test".to_string());
+    args.config = Some(config);
+    args.verify = true;
+    db_mover::run(args).unwrap();
+
+    let expected: Vec<_> = in_db
+        .get_all_rows("test")
+        .into_iter()
+        .map(|mut row| {
+            row.text = "moved".to_string();
+            row
+        })
+        .collect();
+    assert_eq!(expected, out_db.get_all_rows("test"));
+}
+
+#[apply(all_databases_combinations)]
+fn rename_table(mut in_db: impl TestableDatabase, mut out_db: impl TestableDatabase) {
+    in_db.create_test_table("test");
+    in_db.fill_test_table("test", 10);
+    out_db.create_test_table("test_archive");
+
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    args.table.push("test:test_archive".to_string());
+    args.verify = true;
+    db_mover::run(args).unwrap();
+
+    assert_eq!(
+        in_db.get_all_rows("test"),
+        out_db.get_all_rows("test_archive")
+    );
+}
+
+#[apply(all_databases_combinations)]
+fn quoted_identifiers(mut in_db: impl TestableDatabase, mut out_db: impl TestableDatabase) {
+    let tables = ["order", "Mixed Case"];
+    for table in tables {
+        let (in_table, out_table) = (in_db.quote(table), out_db.quote(table));
+        in_db.create_test_table(&in_table);
+        in_db.fill_test_table(&in_table, 10);
+        out_db.create_test_table(&out_table);
+    }
+
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    args.table = tables.iter().map(|table| table.to_string()).collect();
+    args.reader_workers = 2;
+    args.verify = true;
+    db_mover::run(args).unwrap();
+
+    for table in tables {
+        assert_eq!(
+            in_db.get_all_rows(&in_db.quote(table)),
+            out_db.get_all_rows(&out_db.quote(table))
+        );
+    }
+}
+
+#[apply(all_databases_combinations)]
+fn parallel_tables(mut in_db: impl TestableDatabase, mut out_db: impl TestableDatabase) {
+    create_test_tables(&mut in_db, &mut out_db);
+    in_db.fill_test_table("test", 100);
+    in_db.fill_test_table("test1", 100);
+
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    args.parallel_tables = 2;
+    args.max_connections = Some(4);
+    db_mover::run(args).unwrap();
+
+    assert_eq!(in_db.get_all_rows("test"), out_db.get_all_rows("test"));
+    assert_eq!(in_db.get_all_rows("test1"), out_db.get_all_rows("test1"));
+}
+
 #[apply(all_databases_combinations)]
 fn auto_detect_tables(mut in_db: impl TestableDatabase, mut out_db: impl TestableDatabase) {
     create_test_tables(&mut in_db, &mut out_db);
@@ -123,6 +404,38 @@ fn out_table_not_found(mut in_db: impl TestableDatabase, out_db: impl TestableDa
     assert!(db_mover::run(args.clone()).is_err());
 }
 
+#[apply(all_databases_combinations)]
+fn create_missing_tables(mut in_db: impl TestableDatabase, mut out_db: impl TestableDatabase) {
+    in_db.create_test_table("test");
+    in_db.create_test_table("test1");
+    out_db.create_test_table("test1");
+    in_db.fill_test_table("test", 10);
+    in_db.fill_test_table("test1", 10);
+
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    args.create_tables = true;
+    db_mover::run(args).unwrap();
+
+    assert_eq!(in_db.get_all_rows("test"), out_db.get_all_rows("test"));
+    assert_eq!(in_db.get_all_rows("test1"), out_db.get_all_rows("test1"));
+}
+
+#[apply(all_databases_combinations)]
+fn create_missing_tables_dry_run(mut in_db: impl TestableDatabase, out_db: impl TestableDatabase) {
+    in_db.create_test_table("test");
+    in_db.fill_test_table("test", 10);
+
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    args.table.push("test".to_string());
+    args.create_tables = true;
+    args.dry_run = true;
+    db_mover::run(args.clone()).unwrap();
+
+    args.dry_run = false;
+    args.create_tables = false;
+    assert!(db_mover::run(args).is_err());
+}
+
 #[apply(all_databases_combinations)]
 fn out_table_is_not_empty(mut in_db: impl TestableDatabase, mut out_db: impl TestableDatabase) {
     create_test_tables(&mut in_db, &mut out_db);
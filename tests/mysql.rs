@@ -4,7 +4,9 @@ use common::mysql::TestMysqlDatabase;
 use common::testable_database::TestableDatabase;
 use db_mover::databases::{
     mysql::{MysqlDB, MysqlTypeOptions},
-    traits::DBInfoProvider,
+    table::Value,
+    traits::{DBInfoProvider, DBReader, DBWriter},
+    write_mode::WriteMode,
 };
 use mysql::prelude::Queryable;
 use pretty_assertions::assert_eq;
@@ -12,9 +14,10 @@ use pretty_assertions::assert_eq;
 use rstest::rstest;
 
 use crate::common::postgres::TestPostresDatabase;
+use crate::common::sqlite::TestSqliteDatabase;
 
-const MYSQL_BYTES_IN: &'static str = "CAST('Hello World' AS BINARY)";
-const MYSQL_BYTES_EXPECTED: &'static str = "Hello World";
+const MYSQL_BYTES_IN: &str = "CAST('Hello World' AS BINARY)";
+const MYSQL_BYTES_EXPECTED: &str = "Hello World";
 
 #[rstest]
 #[case("bigint", "9223372036854775800", "9223372036854775800")]
@@ -41,6 +44,17 @@ const MYSQL_BYTES_EXPECTED: &'static str = "Hello World";
 #[case("datetime", "'1001-10-19 10:23:54'", "1001-10-19 10:23:54")]
 #[case("date", "'2004-10-19'", "2004-10-19")]
 #[case("time", "'10:23:54'", "10:23:54")]
+#[case(
+    "timestamp(6)",
+    "'2004-10-19 10:23:54.123456'",
+    "2004-10-19 10:23:54.123456"
+)]
+#[case(
+    "datetime(6)",
+    "'2004-10-19 10:23:54.123456'",
+    "2004-10-19 10:23:54.123456"
+)]
+#[case("time(6)", "'10:23:54.123456'", "10:23:54.123456")]
 #[case("json", r#"'{"test":1}'"#, r#"{"test":1}"#)]
 #[case("json", r#"'[{"test":1},{"test":2}]'"#, r#"[{"test":1},{"test":2}]"#)]
 #[case("binary(11)", MYSQL_BYTES_IN, MYSQL_BYTES_EXPECTED)]
@@ -102,9 +116,7 @@ fn mysql_binary_16_uuid(
     in_db.execute("CREATE TABLE test (field binary(16))");
     out_db.execute("CREATE TABLE test (field UUID)");
 
-    in_db.execute(format!(
-        "INSERT INTO test VALUES (X'67e5504410b1426f9247bb680e5fe0c8')"
-    ));
+    in_db.execute("INSERT INTO test VALUES (X'67e5504410b1426f9247bb680e5fe0c8')");
     in_db.execute("INSERT INTO test VALUES (NULL)");
     let mut expected = vec![Some("67e55044-10b1-426f-9247-bb680e5fe0c8".to_string())];
     expected.push(None);
@@ -135,7 +147,7 @@ fn mysql_binary_16_no_uuid(
     in_db.execute("CREATE TABLE test (field binary(16))");
     out_db.execute("CREATE TABLE test (field bytea)");
 
-    in_db.execute(format!("INSERT INTO test VALUES (X'9fad5e9eefdfb449')"));
+    in_db.execute("INSERT INTO test VALUES (X'9fad5e9eefdfb449')");
     in_db.execute("INSERT INTO test VALUES (NULL)");
     let mut expected = vec![Some("\\x9fad5e9eefdfb4490000000000000000".to_string())];
     expected.push(None);
@@ -177,3 +189,359 @@ fn mysql_table_list(
     tables.sort();
     assert_eq!(tables, vec![String::from("test"), String::from("test2")]);
 }
+
+#[rstest]
+fn mysql_cross_database_tables(
+    #[values(TestMysqlDatabase::new_mysql(), TestMysqlDatabase::new_mariadb())]
+    mut in_db: TestMysqlDatabase,
+) {
+    let mut out_db = TestSqliteDatabase::new();
+    in_db.create_test_table("test");
+    in_db.fill_test_table("test", 10);
+    out_db.create_test_table("test_archive");
+    let database = in_db.uri.rsplit('/').next().unwrap().to_string();
+
+    let mut db = MysqlDB::new(&in_db.uri, MysqlTypeOptions::default()).unwrap();
+    assert_eq!(
+        db.get_schema_tables(&database).unwrap(),
+        vec![format!("{database}.test")]
+    );
+
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    args.table.push(format!("{database}.test:test_archive"));
+    db_mover::run(args).unwrap();
+
+    assert_eq!(
+        in_db.get_all_rows("test"),
+        out_db.get_all_rows("test_archive")
+    );
+}
+
+#[rstest]
+fn mysql_foreign_key_order(
+    #[values(TestMysqlDatabase::new_mysql(), TestMysqlDatabase::new_mariadb())]
+    mut out_db: TestMysqlDatabase,
+) {
+    let mut in_db = TestSqliteDatabase::new();
+    in_db.execute("CREATE TABLE child (id BIGINT PRIMARY KEY, parent_id BIGINT)");
+    in_db.execute("CREATE TABLE parent (id BIGINT PRIMARY KEY)");
+    in_db.execute("INSERT INTO parent VALUES (1), (2)");
+    in_db.execute("INSERT INTO child VALUES (1, 1), (2, 2)");
+    out_db.execute("CREATE TABLE parent (id BIGINT PRIMARY KEY)");
+    out_db.execute(
+        "CREATE TABLE child (id BIGINT PRIMARY KEY, parent_id BIGINT, \
+        FOREIGN KEY (parent_id) REFERENCES parent (id))",
+    );
+
+    let mut db = MysqlDB::new(&out_db.uri, MysqlTypeOptions::default()).unwrap();
+    assert_eq!(
+        db.get_referenced_tables("child").unwrap(),
+        vec![String::from("parent")]
+    );
+
+    let args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    db_mover::run(args).unwrap();
+
+    assert_eq!(out_db.query_count("SELECT count(1) FROM child"), 2);
+}
+
+#[rstest]
+fn mysql_drop_indexes(
+    #[values(TestMysqlDatabase::new_mysql(), TestMysqlDatabase::new_mariadb())]
+    mut test_db: TestMysqlDatabase,
+) {
+    test_db.execute(
+        "CREATE TABLE test (id BIGINT PRIMARY KEY, value INTEGER, code VARCHAR(100) UNIQUE, \
+        INDEX test_value (value, code(10)))",
+    );
+    let indexes_query = "SELECT count(DISTINCT INDEX_NAME) FROM INFORMATION_SCHEMA.STATISTICS \
+        WHERE TABLE_SCHEMA = database() AND TABLE_NAME = 'test'";
+    let mut db = MysqlDB::new(&test_db.uri, MysqlTypeOptions::default()).unwrap();
+    db.enable_fast_load().unwrap();
+
+    let indexes = db.drop_indexes("test").unwrap();
+    assert_eq!(
+        indexes,
+        vec![String::from(
+            "CREATE INDEX `test_value` ON `test` (`value`, `code`(10))"
+        )]
+    );
+    assert_eq!(test_db.query_count(indexes_query), 2);
+    db.create_indexes(&indexes).unwrap();
+    assert_eq!(test_db.query_count(indexes_query), 3);
+}
+
+#[rstest]
+fn mysql_reset_auto_increment(
+    #[values(TestMysqlDatabase::new_mysql(), TestMysqlDatabase::new_mariadb())]
+    mut out_db: TestMysqlDatabase,
+) {
+    let mut in_db = TestSqliteDatabase::new();
+    in_db.execute("CREATE TABLE test (id INTEGER PRIMARY KEY, value INTEGER)");
+    in_db.execute("INSERT INTO test VALUES (1, 1), (5, 5), (10, 10)");
+    out_db.execute("CREATE TABLE test (id BIGINT AUTO_INCREMENT PRIMARY KEY, value INTEGER)");
+
+    let args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    db_mover::run(args).unwrap();
+
+    out_db.execute("INSERT INTO test (value) VALUES (11)");
+    assert_eq!(out_db.query_count("SELECT max(id) FROM test"), 11);
+}
+
+#[rstest]
+fn mysql_snapshot(
+    #[values(TestMysqlDatabase::new_mysql(), TestMysqlDatabase::new_mariadb())]
+    mut in_db: TestMysqlDatabase,
+) {
+    in_db.create_test_table("test");
+    in_db.fill_test_table("test", 10);
+
+    let mut reader = MysqlDB::new(&in_db.uri, MysqlTypeOptions::default()).unwrap();
+    reader.start_snapshot().unwrap();
+    in_db.execute("DELETE FROM test");
+    assert_eq!(reader.count_rows("test", "TRUE").unwrap(), 10);
+}
+
+#[rstest]
+fn mysql_timestamp_range(#[values(false, true)] load_data: bool) {
+    let mut in_db = TestPostresDatabase::new();
+    let mut out_db = TestMysqlDatabase::new_mysql();
+    in_db.execute("CREATE TABLE test (id BIGINT PRIMARY KEY, value TIMESTAMPTZ)");
+    in_db.execute("INSERT INTO test VALUES (1, '2004-10-19 10:23:54+00')");
+
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    args.create_tables = true;
+    args.mysql_load_data = load_data;
+    db_mover::run(args.clone()).unwrap();
+    assert_eq!(out_db.query_count("SELECT count(1) FROM test"), 1);
+
+    // Out of range of MySQL timestamp columns
+    in_db.execute("INSERT INTO test VALUES (2, '2050-01-01 00:00:00+00')");
+    args.write_mode = WriteMode::IgnoreDuplicates;
+    assert!(db_mover::run(args).is_err());
+    assert_eq!(out_db.query_count("SELECT count(1) FROM test"), 1);
+}
+
+#[rstest]
+fn mysql_atomic(
+    #[values(TestMysqlDatabase::new_mysql(), TestMysqlDatabase::new_mariadb())]
+    mut out_db: TestMysqlDatabase,
+) {
+    let mut in_db = TestSqliteDatabase::new();
+    in_db.execute("CREATE TABLE test (id INTEGER PRIMARY KEY, value INTEGER)");
+    in_db.execute("INSERT INTO test VALUES (1, 10), (2, 20), (3, 30)");
+    out_db.execute("CREATE TABLE test (id BIGINT PRIMARY KEY, value INTEGER, INDEX (value))");
+    out_db.execute("INSERT INTO test VALUES (100, 100)");
+
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    args.atomic = true;
+    db_mover::run(args).unwrap();
+
+    assert_eq!(out_db.query_count("SELECT count(1) FROM test"), 3);
+    assert_eq!(
+        out_db.query_count(
+            "SELECT count(1) FROM INFORMATION_SCHEMA.TABLES WHERE TABLE_SCHEMA = database()"
+        ),
+        1
+    );
+}
+
+#[rstest]
+fn mysql_atomic_foreign_keys(#[values("parent", "child")] table: &str) {
+    let mut in_db = TestMysqlDatabase::new_mysql();
+    let mut out_db = TestMysqlDatabase::new_mysql();
+    for db in [&mut in_db, &mut out_db] {
+        db.execute("CREATE TABLE parent (id INTEGER PRIMARY KEY)");
+        db.execute(
+            "CREATE TABLE child (id INTEGER PRIMARY KEY, parent_id INTEGER,
+            FOREIGN KEY (parent_id) REFERENCES parent (id))",
+        );
+    }
+    in_db.execute("INSERT INTO parent VALUES (1), (2)");
+    out_db.execute("INSERT INTO parent VALUES (1)");
+    out_db.execute("INSERT INTO child VALUES (1, 1)");
+
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    args.table = vec![table.to_string()];
+    args.atomic = true;
+    // Staging table would lose foreign keys of the table or of tables referencing it
+    assert!(db_mover::run(args).is_err());
+
+    assert_eq!(out_db.query_count("SELECT count(1) FROM parent"), 1);
+    assert_eq!(out_db.query_count("SELECT count(1) FROM child"), 1);
+    assert_eq!(
+        out_db.query_count(
+            "SELECT count(1) FROM INFORMATION_SCHEMA.TABLES WHERE TABLE_SCHEMA = database()"
+        ),
+        2
+    );
+}
+
+#[rstest]
+fn mysql_follow() {
+    let mut in_db = TestMysqlDatabase::new_mysql();
+    let mut out_db = TestSqliteDatabase::new();
+    in_db.execute(
+        "CREATE TABLE test (id BIGINT PRIMARY KEY, value TEXT, updated_at TIMESTAMP(6) NULL)",
+    );
+    out_db.execute("CREATE TABLE test (id BIGINT PRIMARY KEY, value TEXT, updated_at TIMESTAMPTZ)");
+    in_db.execute("INSERT INTO test (id, value) VALUES (1, '1'), (2, '2'), (3, '3')");
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    args.follow = true;
+    args.binlog_state_file = Some(tmp_dir.path().join("binlog.json"));
+    let stop = std::sync::atomic::AtomicBool::new(false);
+    std::thread::scope(|s| {
+        let handle = s.spawn(|| db_mover::run_until(args.clone(), &stop));
+        out_db.wait_for_count("SELECT count(1) FROM test", 3);
+
+        in_db.execute("INSERT INTO test VALUES (4, '4', '2024-01-01 10:00:00.5')");
+        in_db.execute("UPDATE test SET value = 'updated' WHERE id = 1");
+        in_db.execute("UPDATE test SET id = 5 WHERE id = 3");
+        in_db.execute("DELETE FROM test WHERE id = 2");
+        // Changes are applied in order, so the last one is applied after all others
+        out_db.wait_for_count("SELECT count(1) FROM test WHERE id = 2", 0);
+        stop.store(true, std::sync::atomic::Ordering::Relaxed);
+        handle.join().unwrap().unwrap();
+    });
+    assert_eq!(out_db.query_count("SELECT count(1) FROM test"), 3);
+    assert_eq!(
+        out_db.query_count("SELECT count(1) FROM test WHERE value = 'updated' OR id = 5"),
+        2
+    );
+    assert_eq!(
+        out_db.query_count(
+            "SELECT count(1) FROM test WHERE updated_at = '2024-01-01 10:00:00.500+00:00'"
+        ),
+        1
+    );
+
+    // Tables aren't moved again, changes are followed from the saved position
+    in_db.execute("DELETE FROM test WHERE id = 1");
+    stop.store(false, std::sync::atomic::Ordering::Relaxed);
+    std::thread::scope(|s| {
+        let handle = s.spawn(|| db_mover::run_until(args, &stop));
+        out_db.wait_for_count("SELECT count(1) FROM test WHERE id = 1", 0);
+        stop.store(true, std::sync::atomic::Ordering::Relaxed);
+        handle.join().unwrap().unwrap();
+    });
+    assert_eq!(out_db.query_count("SELECT count(1) FROM test"), 2);
+}
+
+#[rstest]
+fn mysql_follow_partial_row_image(#[values("MINIMAL", "NOBLOB")] row_image: &str) {
+    let mut in_db = TestMysqlDatabase::new_mysql();
+    let mut out_db = TestSqliteDatabase::new();
+    in_db.execute("CREATE TABLE test (id BIGINT PRIMARY KEY, value TEXT, note VARCHAR(10))");
+    out_db.execute("CREATE TABLE test (id BIGINT PRIMARY KEY, value TEXT, note VARCHAR(10))");
+    in_db.execute("INSERT INTO test VALUES (1, '1', 'a'), (2, '2', 'b')");
+    // Images of changes keep only some of the columns
+    in_db.execute(format!("SET SESSION binlog_row_image = '{row_image}'"));
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    args.follow = true;
+    let stop = std::sync::atomic::AtomicBool::new(false);
+    std::thread::scope(|s| {
+        let handle = s.spawn(|| db_mover::run_until(args, &stop));
+        out_db.wait_for_count("SELECT count(1) FROM test", 2);
+
+        in_db.execute("UPDATE test SET note = 'updated' WHERE id = 1");
+        in_db.execute("INSERT INTO test VALUES (3, '3', 'c')");
+        in_db.execute("DELETE FROM test WHERE id = 2");
+        out_db.wait_for_count("SELECT count(1) FROM test WHERE id = 2", 0);
+        // Moved row would lose columns missing in the image
+        in_db.execute("UPDATE test SET id = 4 WHERE id = 3");
+        assert!(handle.join().unwrap().is_err());
+    });
+    assert_eq!(
+        out_db.query_count(
+            "SELECT count(1) FROM test WHERE id = 1 AND value = '1' AND note = 'updated'"
+        ),
+        1
+    );
+    assert_eq!(
+        out_db.query_count("SELECT count(1) FROM test WHERE id = 3 AND value = '3' AND note = 'c'"),
+        1
+    );
+}
+
+#[rstest]
+fn mysql_load_data(
+    #[values(TestMysqlDatabase::new_mysql(), TestMysqlDatabase::new_mariadb())]
+    mut out_db: TestMysqlDatabase,
+) {
+    // Disabled by default in MySQL 8
+    out_db.execute("SET GLOBAL local_infile = 1");
+    let mut in_db = TestSqliteDatabase::new();
+    in_db.create_test_table("test");
+    in_db.fill_test_table("test", 100);
+    in_db.execute(
+        "INSERT INTO test VALUES (100, 1.5, 'tab\tnew line\nback\\slash \\N', x'00090a5c',
+        '2016-07-08 00:00:00')",
+    );
+    out_db.create_test_table("test");
+
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    args.mysql_load_data = true;
+    db_mover::run(args.clone()).unwrap();
+    assert_eq!(in_db.get_all_rows("test"), out_db.get_all_rows("test"));
+
+    // Duplicates are errors unless they are ignored, failed batches are rolled back
+    let mut writer = MysqlDB::new(&out_db.uri, MysqlTypeOptions::default()).unwrap();
+    writer.enable_load_data();
+    let table = writer.get_table_info("test", true).unwrap();
+    let row = |id| {
+        let mut row = vec![Value::Null; table.columns.len()];
+        row[0] = Value::I64(id);
+        return row;
+    };
+    let batch = vec![row(1000), row(100)];
+    assert!(writer.write_batch(&batch, &table).is_err());
+    assert_eq!(out_db.query_count("SELECT count(1) FROM test"), 101);
+    writer.set_write_mode(WriteMode::IgnoreDuplicates).unwrap();
+    writer.write_batch(&batch, &table).unwrap();
+    assert_eq!(out_db.query_count("SELECT count(1) FROM test"), 102);
+    assert_eq!(
+        out_db.query_count("SELECT count(1) FROM test WHERE id = 100 AND text_field IS NOT NULL"),
+        1
+    );
+
+    args.write_mode = WriteMode::IgnoreDuplicates;
+    db_mover::run(args).unwrap();
+    assert_eq!(out_db.query_count("SELECT count(1) FROM test"), 102);
+}
+
+#[rstest]
+fn mysql_fetch_size(
+    #[values(TestMysqlDatabase::new_mysql(), TestMysqlDatabase::new_mariadb())]
+    mut in_db: TestMysqlDatabase,
+    #[values(1, 3)] reader_workers: usize,
+) {
+    in_db.create_test_table("test");
+    in_db.fill_test_table("test", 100);
+    in_db.execute("CREATE TABLE no_key (id BIGINT, value TEXT)");
+    in_db.execute("INSERT INTO no_key VALUES (1, 'a'), (1, 'b'), (2, 'c')");
+    let mut out_db = TestSqliteDatabase::new();
+    out_db.create_test_table("test");
+    out_db.execute("CREATE TABLE no_key (id BIGINT, value TEXT)");
+
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    args.mysql_fetch_size = Some(7);
+    args.reader_workers = reader_workers;
+    args.table.push("test".to_string());
+    // Filter is combined with key ranges and pages
+    args.filter.push("test=id % 3 <> 0".to_string());
+    db_mover::run(args.clone()).unwrap();
+    let expected: Vec<_> = in_db
+        .get_all_rows("test")
+        .into_iter()
+        .filter(|row| row.id % 3 != 0)
+        .collect();
+    assert_eq!(expected.len(), 66);
+    assert_eq!(expected, out_db.get_all_rows("test"));
+
+    // Tables without a single column key would be read by a single query
+    args.table = vec!["no_key".to_string()];
+    args.filter.clear();
+    assert!(db_mover::run(args).is_err());
+    assert_eq!(out_db.query_count("SELECT count(1) FROM no_key"), 0);
+}
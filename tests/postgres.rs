@@ -1,14 +1,17 @@
 mod common;
 
 use common::postgres::TestPostresDatabase;
+use common::sqlite::TestSqliteDatabase;
 use common::testable_database::TestableDatabase;
 use db_mover::databases::postgres::PostgresDB;
-use db_mover::databases::traits::DBInfoProvider;
+use db_mover::databases::table::{KeyRange, ReadFilter, Row, Value};
+use db_mover::databases::traits::{DBInfoProvider, DBReader, DBWriter};
+use db_mover::databases::write_mode::WriteMode;
 use pretty_assertions::assert_eq;
 
 use rstest::rstest;
 
-const POSTGRES_NULL_DISABLED_TYPES: [&'static str; 3] = ["smallserial", "serial", "bigserial"];
+const POSTGRES_NULL_DISABLED_TYPES: [&str; 3] = ["smallserial", "serial", "bigserial"];
 
 #[rstest]
 #[case("bigint", "9223372036854775800", "9223372036854775800")]
@@ -44,6 +47,14 @@ const POSTGRES_NULL_DISABLED_TYPES: [&'static str; 3] = ["smallserial", "serial"
     "'67e55044-10b1-426f-9247-bb680e5fe0c8'",
     "67e55044-10b1-426f-9247-bb680e5fe0c8"
 )]
+#[case("integer[]", "'{1,NULL,3}'", "{1,NULL,3}")]
+#[case("text[]", r#"'{"a b",c}'"#, r#"{"a b",c}"#)]
+#[case(
+    "timestamptz[]",
+    "'{2004-10-19 10:23:54+00}'",
+    r#"{"2004-10-19 10:23:54+00"}"#
+)]
+#[case("bigint[]", "'{}'", "{}")]
 fn postgres_types_compatability(
     #[case] type_name: &str,
     #[case] value: &str,
@@ -101,3 +112,514 @@ fn postgres_table_list() {
     tables.sort();
     assert_eq!(tables, vec![String::from("test"), String::from("test2")]);
 }
+
+#[rstest]
+fn postgres_upsert_duplicate_keys() {
+    let mut test_db = TestPostresDatabase::new();
+    test_db.execute("CREATE TABLE test (id BIGINT PRIMARY KEY, value TEXT)");
+    test_db.execute("INSERT INTO test VALUES (1, 'old')");
+    let mut db = PostgresDB::new(&test_db.uri).unwrap();
+    db.set_write_mode(WriteMode::Upsert).unwrap();
+    let table = db.get_table_info("test", true).unwrap();
+    let row = |id, value: &str| vec![Value::I64(id), Value::String(value.to_string())];
+
+    // Last row of the key is written
+    let batch = vec![row(1, "first"), row(2, "new"), row(1, "second")];
+    db.write_batch(&batch, &table).unwrap();
+    assert_eq!(test_db.query_count("SELECT count(1) FROM test"), 2);
+    assert_eq!(
+        test_db.query_count("SELECT count(1) FROM test WHERE id = 1 AND value = 'second'"),
+        1
+    );
+}
+
+#[rstest]
+fn postgres_upsert_defaults() {
+    let mut in_db = TestSqliteDatabase::new();
+    let mut out_db = TestPostresDatabase::new();
+    in_db.execute("CREATE TABLE test (id INTEGER PRIMARY KEY, value INTEGER)");
+    in_db.execute("INSERT INTO test VALUES (1, 10), (2, 20), (3, 30)");
+    out_db.execute(
+        "CREATE TABLE test (id BIGINT PRIMARY KEY, value INTEGER, created_at TIMESTAMPTZ NOT NULL DEFAULT now())",
+    );
+    out_db.execute("INSERT INTO test (id, value) VALUES (1, 100)");
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let config = tmp_dir.path().join("config.json");
+    std::fs::write(
+        &config,
+        r#"{"tables": {"test": {"defaults": ["created_at"]}}}"#,
+    )
+    .unwrap();
+
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    args.config = Some(config);
+    args.write_mode = WriteMode::Upsert;
+    db_mover::run(args).unwrap();
+    assert_eq!(
+        out_db.query_count("SELECT count(1) FROM test WHERE value = id * 10"),
+        3
+    );
+
+    // Temporary tables of similar names don't collide in one writer
+    out_db.execute("CREATE SCHEMA a");
+    out_db.execute("CREATE TABLE a.b (id BIGINT PRIMARY KEY, value INTEGER)");
+    out_db.execute("CREATE TABLE a_b (id BIGINT PRIMARY KEY, name TEXT)");
+    let mut db = PostgresDB::new(&out_db.uri).unwrap();
+    db.set_write_mode(WriteMode::Upsert).unwrap();
+    let table = db.get_table_info("a.b", true).unwrap();
+    db.write_batch(&[vec![Value::I64(1), Value::I32(10)]], &table)
+        .unwrap();
+    let table = db.get_table_info("a_b", true).unwrap();
+    db.write_batch(
+        &[vec![Value::I64(1), Value::String("b".to_string())]],
+        &table,
+    )
+    .unwrap();
+    assert_eq!(out_db.query_count("SELECT count(1) FROM a.b"), 1);
+    assert_eq!(out_db.query_count("SELECT count(1) FROM a_b"), 1);
+}
+
+#[rstest]
+fn postgres_uuid_key_reader_workers() {
+    let mut in_db = TestPostresDatabase::new();
+    let mut out_db = TestPostresDatabase::new();
+    let create_table_query = "CREATE TABLE test (id UUID PRIMARY KEY, value INTEGER)";
+    in_db.execute(create_table_query);
+    out_db.execute(create_table_query);
+    in_db.execute(
+        "INSERT INTO test SELECT lpad(to_hex(i), 32, '0')::uuid, i FROM generate_series(1, 1000) AS i",
+    );
+
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    args.table.push("test".to_string());
+    args.reader_workers = 3;
+    db_mover::run(args).unwrap();
+
+    assert_eq!(
+        out_db.query_count("SELECT count(DISTINCT id) FROM test"),
+        1000
+    );
+}
+
+#[rstest]
+fn postgres_cross_schema_tables() {
+    let mut in_db = TestPostresDatabase::new();
+    let mut out_db = TestPostresDatabase::new();
+    in_db.execute("CREATE SCHEMA src");
+    in_db.execute("CREATE TABLE src.orders (id BIGINT PRIMARY KEY, value INTEGER)");
+    in_db.execute("INSERT INTO src.orders SELECT i, i FROM generate_series(1, 100) AS i");
+    out_db.execute("CREATE SCHEMA dst");
+    out_db.execute("CREATE TABLE dst.orders_archive (id BIGINT PRIMARY KEY, value INTEGER)");
+
+    let mut db = PostgresDB::new(&out_db.uri).unwrap();
+    assert_eq!(db.get_tables().unwrap().len(), 0);
+    assert_eq!(
+        db.get_schema_tables("dst").unwrap(),
+        vec![String::from("dst.orders_archive")]
+    );
+
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    args.table.push("src.orders:dst.orders_archive".to_string());
+    args.verify_detailed = true;
+    db_mover::run(args).unwrap();
+
+    assert_eq!(
+        out_db.query_count("SELECT count(1) FROM dst.orders_archive"),
+        100
+    );
+}
+
+#[rstest]
+fn postgres_foreign_key_order() {
+    let mut in_db = TestPostresDatabase::new();
+    let mut out_db = TestPostresDatabase::new();
+    for db in [&mut in_db, &mut out_db] {
+        db.execute("CREATE TABLE child (id BIGINT PRIMARY KEY, parent_id BIGINT)");
+        db.execute("CREATE TABLE parent (id BIGINT PRIMARY KEY)");
+        db.execute("ALTER TABLE child ADD FOREIGN KEY (parent_id) REFERENCES parent (id)");
+    }
+    in_db.execute("INSERT INTO parent SELECT i FROM generate_series(1, 100) AS i");
+    in_db.execute("INSERT INTO child SELECT i, i FROM generate_series(1, 100) AS i");
+
+    let mut db = PostgresDB::new(&out_db.uri).unwrap();
+    assert_eq!(
+        db.get_referenced_tables("child").unwrap(),
+        vec![String::from("parent")]
+    );
+
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    args.parallel_tables = 2;
+    db_mover::run(args).unwrap();
+
+    assert_eq!(out_db.query_count("SELECT count(1) FROM child"), 100);
+}
+
+#[rstest]
+fn postgres_drop_indexes() {
+    let mut test_db = TestPostresDatabase::new();
+    test_db.execute("CREATE TABLE test (id BIGINT PRIMARY KEY, value INTEGER, code TEXT UNIQUE)");
+    test_db.execute("CREATE INDEX test_value ON test (value)");
+    let indexes_query = "SELECT count(1) FROM pg_indexes WHERE tablename = 'test'";
+    let mut db = PostgresDB::new(&test_db.uri).unwrap();
+    db.enable_fast_load().unwrap();
+
+    let indexes = db.drop_indexes("test").unwrap();
+    assert_eq!(indexes.len(), 1);
+    assert_eq!(test_db.query_count(indexes_query), 2);
+    db.create_indexes(&indexes).unwrap();
+    assert_eq!(test_db.query_count(indexes_query), 3);
+}
+
+#[rstest]
+#[case("SERIAL")]
+#[case("BIGINT GENERATED BY DEFAULT AS IDENTITY")]
+fn postgres_reset_sequences(#[case] id_type: &str) {
+    let mut in_db = TestPostresDatabase::new();
+    let mut out_db = TestPostresDatabase::new();
+    for db in [&mut in_db, &mut out_db] {
+        db.execute(format!(
+            "CREATE TABLE test (id {id_type} PRIMARY KEY, value INTEGER)"
+        ));
+    }
+    in_db.execute("INSERT INTO test SELECT i, i FROM generate_series(1, 10) AS i");
+
+    let args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    db_mover::run(args).unwrap();
+
+    out_db.execute("INSERT INTO test (value) VALUES (11)");
+    assert_eq!(out_db.query_count("SELECT max(id)::bigint FROM test"), 11);
+}
+
+#[rstest]
+fn postgres_snapshot() {
+    let mut in_db = TestPostresDatabase::new();
+    let mut out_db = TestPostresDatabase::new();
+    for db in [&mut in_db, &mut out_db] {
+        db.create_test_table("test");
+    }
+    in_db.fill_test_table("test", 100);
+
+    let mut reader = PostgresDB::new(&in_db.uri).unwrap();
+    reader.start_snapshot().unwrap();
+    in_db.execute("DELETE FROM test");
+    let mut cloned = DBReader::opt_clone(&reader).unwrap();
+    assert_eq!(cloned.count_rows("test", "TRUE").unwrap(), 100);
+
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    args.snapshot = true;
+    args.reader_workers = 2;
+    db_mover::run(args).unwrap();
+    assert_eq!(out_db.query_count("SELECT count(1) FROM test"), 0);
+}
+
+#[rstest]
+fn postgres_filtered_key_ranges() {
+    let mut in_db = TestPostresDatabase::new();
+    in_db.execute(
+        "CREATE TABLE test (id UUID PRIMARY KEY, created TIMESTAMPTZ, amount NUMERIC, name TEXT)",
+    );
+    in_db.execute(
+        "INSERT INTO test SELECT lpad(to_hex(i), 32, '0')::uuid, '2024-01-01'::timestamptz + i * interval '1 hour',
+        i * 1.5, 'it''s ' || i FROM generate_series(1, 9) AS i",
+    );
+    let mut reader = PostgresDB::new(&in_db.uri).unwrap();
+    let table = reader.get_table_info("test", true).unwrap();
+    // Rows read through binary COPY with literals inlined into the query, by names
+    let mut read = |column: &str, after: Value, up_to: Option<Value>| {
+        let filter = ReadFilter {
+            key_range: Some(KeyRange {
+                column: column.to_string(),
+                after: Some(after),
+                up_to,
+            }),
+            condition: Some("name <> 'it''s 5'".to_string()),
+        };
+        let rows: Vec<Row> = reader
+            .read_iter(table.clone(), filter)
+            .unwrap()
+            .collect::<anyhow::Result<_>>()
+            .unwrap();
+        return rows
+            .into_iter()
+            .map(|row| match &row[3] {
+                Value::String(name) => name.clone(),
+                other => panic!("Unexpected name {other:?}"),
+            })
+            .collect::<Vec<_>>();
+    };
+
+    let created = chrono::DateTime::parse_from_rfc3339("2024-01-01T03:00:00+00:00")
+        .unwrap()
+        .to_utc();
+    assert_eq!(
+        read("created", Value::Timestamptz(created), None),
+        vec!["it's 4", "it's 6", "it's 7", "it's 8", "it's 9"]
+    );
+    assert_eq!(
+        read(
+            "amount",
+            Value::Decimal("3.0".parse().unwrap()),
+            Some(Value::Decimal("9".parse().unwrap()))
+        ),
+        vec!["it's 3", "it's 4", "it's 6"]
+    );
+    assert_eq!(
+        read("name", Value::String("it's 6".to_string()), None),
+        vec!["it's 7", "it's 8", "it's 9"]
+    );
+    assert_eq!(
+        read(
+            "id",
+            Value::Uuid(uuid::Uuid::from_u128(3)),
+            Some(Value::Uuid(uuid::Uuid::from_u128(7)))
+        ),
+        vec!["it's 4", "it's 6", "it's 7"]
+    );
+}
+
+#[rstest]
+fn postgres_atomic() {
+    let mut in_db = TestPostresDatabase::new();
+    let mut out_db = TestPostresDatabase::new();
+    for db in [&mut in_db, &mut out_db] {
+        db.execute("CREATE TABLE test (id SERIAL PRIMARY KEY, value INTEGER)");
+        db.execute("CREATE INDEX test_value ON test (value)");
+    }
+    in_db.execute("INSERT INTO test SELECT i, i FROM generate_series(1, 10) AS i");
+    out_db.execute("INSERT INTO test VALUES (100, 100)");
+
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    args.atomic = true;
+    args.verify = true;
+    db_mover::run(args).unwrap();
+
+    assert_eq!(out_db.query_count("SELECT count(1) FROM test"), 10);
+    assert_eq!(
+        out_db.query_count("SELECT count(1) FROM pg_indexes WHERE tablename = 'test'"),
+        2
+    );
+    // Serial sequence is kept by the new table
+    out_db.execute("INSERT INTO test (value) VALUES (11)");
+    assert_eq!(out_db.query_count("SELECT max(id)::bigint FROM test"), 11);
+}
+
+#[rstest]
+fn postgres_atomic_foreign_keys(#[values("parent", "child")] table: &str) {
+    let mut in_db = TestPostresDatabase::new();
+    let mut out_db = TestPostresDatabase::new();
+    for db in [&mut in_db, &mut out_db] {
+        db.execute("CREATE TABLE parent (id INTEGER PRIMARY KEY)");
+        db.execute(
+            "CREATE TABLE child (id INTEGER PRIMARY KEY, parent_id INTEGER REFERENCES parent)",
+        );
+    }
+    in_db.execute("INSERT INTO parent VALUES (1), (2)");
+    out_db.execute("INSERT INTO parent VALUES (1)");
+    out_db.execute("INSERT INTO child VALUES (1, 1)");
+
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    args.table = vec![table.to_string()];
+    args.atomic = true;
+    // Staging table would lose foreign keys of the table or of tables referencing it
+    assert!(db_mover::run(args).is_err());
+
+    assert_eq!(out_db.query_count("SELECT count(1) FROM parent"), 1);
+    assert_eq!(out_db.query_count("SELECT count(1) FROM child"), 1);
+    assert_eq!(
+        out_db.query_count("SELECT count(1) FROM pg_tables WHERE schemaname = current_schema"),
+        2
+    );
+}
+
+#[rstest]
+fn postgres_incremental() {
+    let mut in_db = TestPostresDatabase::new();
+    let mut out_db = TestPostresDatabase::new();
+    for db in [&mut in_db, &mut out_db] {
+        db.execute(
+            "CREATE TABLE test (id BIGINT PRIMARY KEY, value INTEGER, updated_at TIMESTAMPTZ)",
+        );
+    }
+    in_db.execute(
+        "INSERT INTO test SELECT i, i, '2024-01-01'::timestamptz + i * interval '1 second'
+        FROM generate_series(1, 10) AS i",
+    );
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    args.incremental = vec![String::from("test=updated_at")];
+    args.state_file = Some(tmp_dir.path().join("state.json"));
+    db_mover::run(args.clone()).unwrap();
+    assert_eq!(out_db.query_count("SELECT count(1) FROM test"), 10);
+
+    in_db.execute("UPDATE test SET value = 0, updated_at = now() WHERE id = 1");
+    db_mover::run(args).unwrap();
+    assert_eq!(
+        out_db.query_count("SELECT count(1) FROM test WHERE value = 0"),
+        1
+    );
+}
+
+#[rstest]
+fn postgres_follow() {
+    let mut in_db = TestPostresDatabase::new();
+    let mut out_db = TestPostresDatabase::new();
+    for db in [&mut in_db, &mut out_db] {
+        db.execute(
+            "CREATE TABLE test (id BIGINT PRIMARY KEY, value TEXT, note TEXT NOT NULL DEFAULT '')",
+        );
+    }
+    // Uncompressed large values are TOASTed
+    in_db.execute("ALTER TABLE test ALTER COLUMN note SET STORAGE EXTERNAL");
+    in_db.execute("INSERT INTO test SELECT i, i::text FROM generate_series(1, 10) AS i");
+    in_db.execute("UPDATE test SET note = repeat('x', 10000) WHERE id = 4");
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    args.slot = Some(String::from("db_mover_follow_test"));
+    args.follow = true;
+    let stop = std::sync::atomic::AtomicBool::new(false);
+    std::thread::scope(|s| {
+        let handle = s.spawn(|| db_mover::run_until(args, &stop));
+        out_db.wait_for_count("SELECT count(1) FROM test", 10);
+
+        in_db.execute("INSERT INTO test VALUES (11, '11')");
+        in_db.execute("UPDATE test SET value = 'updated' WHERE id = 1");
+        in_db.execute("UPDATE test SET id = 12 WHERE id = 3");
+        // Unchanged TOASTed value isn't logged, so the update doesn't set it
+        in_db.execute("UPDATE test SET value = 'toasted' WHERE id = 4");
+        in_db.execute("DELETE FROM test WHERE id = 2");
+        // Changes are applied in order, so the last one is applied after all others
+        out_db.wait_for_count("SELECT count(1) FROM test WHERE id = 2", 0);
+        stop.store(true, std::sync::atomic::Ordering::Relaxed);
+        handle.join().unwrap().unwrap();
+    });
+    assert_eq!(out_db.query_count("SELECT count(1) FROM test"), 10);
+    assert_eq!(
+        out_db.query_count("SELECT count(1) FROM test WHERE value = 'updated'"),
+        1
+    );
+    assert_eq!(
+        out_db.query_count("SELECT count(1) FROM test WHERE id IN (2, 3)"),
+        0
+    );
+    assert_eq!(
+        out_db.query_count(
+            "SELECT count(1) FROM test WHERE value = 'toasted' AND length(note) = 10000"
+        ),
+        1
+    );
+    in_db.execute("SELECT pg_drop_replication_slot('db_mover_follow_test')");
+}
+
+#[rstest]
+fn postgres_follow_mapping() {
+    let mut in_db = TestPostresDatabase::new();
+    let mut out_db = TestPostresDatabase::new();
+    in_db.execute("CREATE TABLE test (id INTEGER PRIMARY KEY, value REAL, name TEXT)");
+    // Widened columns, a renamed column and a column filled by its default
+    out_db.execute(
+        "CREATE TABLE test (id BIGINT PRIMARY KEY, value DOUBLE PRECISION, title TEXT,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT now())",
+    );
+    in_db.execute("INSERT INTO test VALUES (1, 1.5, 'a')");
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let config = tmp_dir.path().join("config.json");
+    std::fs::write(
+        &config,
+        r#"{"tables": {"test": {"columns": {"name": "title"}, "defaults": ["created_at"]}}}"#,
+    )
+    .unwrap();
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    args.slot = Some(String::from("db_mover_follow_mapping_test"));
+    args.follow = true;
+    args.config = Some(config);
+    let stop = std::sync::atomic::AtomicBool::new(false);
+    std::thread::scope(|s| {
+        let handle = s.spawn(|| db_mover::run_until(args.clone(), &stop));
+        out_db.wait_for_count("SELECT count(1) FROM test", 1);
+
+        in_db.execute("INSERT INTO test VALUES (2, 2.5, 'b')");
+        in_db.execute("UPDATE test SET name = 'updated' WHERE id = 1");
+        out_db.wait_for_count("SELECT count(1) FROM test WHERE title = 'updated'", 1);
+        stop.store(true, std::sync::atomic::Ordering::Relaxed);
+        handle.join().unwrap().unwrap();
+    });
+    assert_eq!(
+        out_db
+            .query_count("SELECT count(1) FROM test WHERE id = 2 AND value = 2.5 AND title = 'b'"),
+        1
+    );
+    in_db.execute("SELECT pg_drop_replication_slot('db_mover_follow_mapping_test')");
+
+    // Rows outside of the filter would be inserted by followed changes
+    args.filter.push("test=id > 1".to_string());
+    assert!(db_mover::run(args).is_err());
+}
+
+#[rstest]
+fn postgres_decimal_to_sqlite() {
+    let mut in_db = TestPostresDatabase::new();
+    let out_db = TestSqliteDatabase::new();
+    in_db.execute("CREATE TABLE test (id BIGINT PRIMARY KEY, value NUMERIC)");
+    in_db.execute("INSERT INTO test VALUES (1, 1234567890123456789.123456789), (2, 1.10)");
+
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    args.create_tables = true;
+    db_mover::run(args).unwrap();
+
+    // Stored as text without losing digits
+    let mut stmt = out_db
+        .conn
+        .prepare("SELECT value FROM test ORDER BY id")
+        .unwrap();
+    let result: Vec<String> = stmt
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .map(|res| res.unwrap())
+        .collect();
+    assert_eq!(result, vec!["1234567890123456789.123456789", "1.10"]);
+}
+
+#[rstest]
+fn postgres_arrays_to_sqlite(
+    #[values(
+        None,
+        Some(r#"{"arrays_as_json": true}"#),
+        Some(r#"{"tables": {"test": {"arrays_as_json": true}}}"#)
+    )]
+    config: Option<&str>,
+) {
+    let mut in_db = TestPostresDatabase::new();
+    let out_db = TestSqliteDatabase::new();
+    in_db.execute("CREATE TABLE test (id BIGINT PRIMARY KEY, tags TEXT[], scores INTEGER[])");
+    in_db.execute("INSERT INTO test VALUES (1, '{a,b}', '{1,NULL,3}'), (2, '{}', NULL)");
+
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    args.create_tables = true;
+    assert!(db_mover::run(args.clone()).is_err());
+    let tmp_dir = tempfile::tempdir().unwrap();
+    match config {
+        Some(config) => {
+            let path = tmp_dir.path().join("config.json");
+            std::fs::write(&path, config).unwrap();
+            args.config = Some(path);
+        }
+        None => args.arrays_as_json = true,
+    }
+    db_mover::run(args).unwrap();
+
+    let mut stmt = out_db
+        .conn
+        .prepare("SELECT tags, scores FROM test ORDER BY id")
+        .unwrap();
+    let result: Vec<(String, Option<String>)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .map(|res| res.unwrap())
+        .collect();
+    assert_eq!(
+        result,
+        vec![
+            (r#"["a","b"]"#.to_string(), Some("[1,null,3]".to_string())),
+            ("[]".to_string(), None),
+        ]
+    );
+}
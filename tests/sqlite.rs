@@ -2,8 +2,9 @@ mod common;
 use common::sqlite::TestSqliteDatabase;
 
 use common::testable_database::TestableDatabase;
-use db_mover::databases::sqlite::SqliteDB;
-use db_mover::databases::traits::DBInfoProvider;
+use db_mover::databases::sqlite::{FastWriteJournal, SqliteDB};
+use db_mover::databases::table::Value;
+use db_mover::databases::traits::{DBInfoProvider, DBReader, DBWriter};
 use pretty_assertions::assert_eq;
 
 use rstest::rstest;
@@ -19,6 +20,8 @@ use rstest::rstest;
 // while writing to the sqlite
 #[case("real", "123.12345", "123.12345123291")]
 #[case("double precision", "123.12345678", "123.12345678")]
+#[case("numeric(12, 8)", "123.12345678", "123.12345678")]
+#[case("decimal(12, 8)", "123.12345678", "123.12345678")]
 #[case("bool", "true", "1")]
 #[case("varchar(10)", "'test'", "test")]
 #[case("char(10)", "'test'", "test")]
@@ -110,3 +113,328 @@ fn sqlite_table_list() {
     tables.sort();
     assert_eq!(tables, vec![String::from("test"), String::from("test2")]);
 }
+
+#[rstest]
+fn sqlite_quoted_columns() {
+    let mut in_db = TestSqliteDatabase::new();
+    let mut out_db = TestSqliteDatabase::new();
+    in_db.execute(r#"CREATE TABLE "Order" ("Select" INTEGER PRIMARY KEY, "Mixed Case" TEXT)"#);
+    in_db.execute(r#"INSERT INTO "Order" VALUES (1, 'a'), (2, 'b'), (3, NULL)"#);
+
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    args.table.push("Order".to_string());
+    args.create_tables = true;
+    args.reader_workers = 2;
+    args.verify_detailed = true;
+    db_mover::run(args).unwrap();
+
+    assert_eq!(
+        out_db.query_count(r#"SELECT count(1) FROM "Order" WHERE "Mixed Case" IS NOT NULL"#),
+        2
+    );
+}
+
+#[rstest]
+fn sqlite_dotted_table_name() {
+    let mut in_db = TestSqliteDatabase::new();
+    let mut out_db = TestSqliteDatabase::new();
+    in_db.create_test_table("test");
+    in_db.fill_test_table("test", 10);
+
+    // SQLite has no schemas, so the whole name is the table name
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    args.table.push("test:archive.test".to_string());
+    args.create_tables = true;
+    args.verify = true;
+    db_mover::run(args).unwrap();
+
+    assert_eq!(
+        in_db.get_all_rows("test"),
+        out_db.get_all_rows(r#""archive.test""#)
+    );
+}
+
+#[rstest]
+fn sqlite_update_rows() {
+    let mut test_db = TestSqliteDatabase::new();
+    test_db.execute("CREATE TABLE test (id INTEGER PRIMARY KEY, value TEXT, note TEXT NOT NULL)");
+    test_db.execute("INSERT INTO test VALUES (1, 'a', 'x'), (2, 'b', 'y')");
+    let mut db = SqliteDB::new(test_db.get_uri_raw().as_str()).unwrap();
+    let mut table = db.get_table_info("test", true).unwrap();
+    // Columns missing in the updated rows keep their values
+    table.columns.retain(|column| column.name != "note");
+    let rows = vec![vec![Value::I32(2), Value::String("updated".to_string())]];
+    db.update_rows(&rows, &table).unwrap();
+    assert_eq!(
+        test_db.query_count(
+            "SELECT count(1) FROM test WHERE id = 2 AND value = 'updated' AND note = 'y'"
+        ),
+        1
+    );
+    assert_eq!(
+        test_db.query_count("SELECT count(1) FROM test WHERE id = 1 AND value = 'a'"),
+        1
+    );
+}
+
+#[rstest]
+fn sqlite_referenced_tables() {
+    let mut test_db = TestSqliteDatabase::new();
+    test_db.execute("CREATE TABLE parent (id INTEGER PRIMARY KEY)");
+    test_db.execute(
+        "CREATE TABLE child (id INTEGER PRIMARY KEY, parent_id INTEGER REFERENCES parent(id), \
+        other_id INTEGER REFERENCES parent(id))",
+    );
+    let mut db = SqliteDB::new(test_db.get_uri_raw().as_str()).unwrap();
+
+    assert_eq!(
+        db.get_referenced_tables("child").unwrap(),
+        vec![String::from("parent")]
+    );
+    assert_eq!(db.get_referenced_tables("parent").unwrap().len(), 0);
+}
+
+#[rstest]
+fn sqlite_drop_indexes(#[values(false, true)] fail: bool) {
+    let mut in_db = TestSqliteDatabase::new();
+    let mut out_db = TestSqliteDatabase::new();
+    let create_table_query = "CREATE TABLE test (id INTEGER PRIMARY KEY, value INTEGER)";
+    in_db.execute(create_table_query);
+    in_db.execute("INSERT INTO test VALUES (1, 10), (2, 20), (3, 20)");
+    out_db.execute(create_table_query);
+    out_db.execute("CREATE INDEX test_value ON test (value)");
+    if fail {
+        // Unique indexes are kept, so duplicated values break the migration
+        out_db.execute("CREATE UNIQUE INDEX test_value_unique ON test (value)");
+    }
+
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    args.table.push("test".to_string());
+    args.fast_load = true;
+    args.drop_indexes = true;
+    assert_eq!(db_mover::run(args).is_err(), fail);
+
+    let num_indexes = out_db.query_count(
+        "SELECT count(1) FROM sqlite_master WHERE type = 'index' AND tbl_name = 'test'",
+    );
+    assert_eq!(num_indexes, if fail { 2 } else { 1 });
+    if !fail {
+        assert_eq!(out_db.query_count("SELECT count(1) FROM test"), 3);
+    }
+}
+
+#[rstest]
+fn sqlite_snapshot() {
+    let mut in_db = TestSqliteDatabase::new();
+    let mut out_db = TestSqliteDatabase::new();
+    // Writers don't wait for readers in WAL mode
+    in_db
+        .conn
+        .pragma_update(None, "journal_mode", "WAL")
+        .unwrap();
+    for db in [&mut in_db, &mut out_db] {
+        db.create_test_table("first");
+        db.create_test_table("second");
+    }
+    in_db.fill_test_table("first", 10);
+    in_db.fill_test_table("second", 10);
+
+    let mut reader = SqliteDB::new(&in_db.get_uri_raw()).unwrap();
+    reader.start_snapshot().unwrap();
+    in_db.execute("DELETE FROM first");
+    assert_eq!(
+        reader.get_table_info("first", false).unwrap().num_rows,
+        Some(10)
+    );
+
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    args.snapshot = true;
+    args.parallel_tables = 2;
+    // Falls back to a single reader worker
+    args.reader_workers = 2;
+    db_mover::run(args).unwrap();
+    assert_eq!(out_db.query_count("SELECT count(1) FROM first"), 0);
+    assert_eq!(out_db.query_count("SELECT count(1) FROM second"), 10);
+}
+
+#[rstest]
+fn sqlite_atomic(#[values(false, true)] fail: bool) {
+    let mut in_db = TestSqliteDatabase::new();
+    let mut out_db = TestSqliteDatabase::new();
+    in_db.execute("CREATE TABLE test (id INTEGER PRIMARY KEY, value INTEGER)");
+    in_db.execute("INSERT INTO test VALUES (1, 10), (2, 20), (3, 30)");
+    if fail {
+        // Unique constraint of the output table is kept by the staging table
+        in_db.execute("INSERT INTO test VALUES (4, 30)");
+    }
+    out_db.execute("CREATE TABLE test (id INTEGER PRIMARY KEY, value INTEGER UNIQUE)");
+    out_db.execute("CREATE INDEX test_id_value ON test (id, value)");
+    out_db.execute("INSERT INTO test VALUES (100, 100)");
+
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    args.atomic = true;
+    args.verify = true;
+    assert_eq!(db_mover::run(args).is_err(), fail);
+
+    assert_eq!(
+        out_db.query_count("SELECT count(1) FROM test"),
+        if fail { 1 } else { 3 }
+    );
+    assert_eq!(
+        out_db.query_count("SELECT count(1) FROM sqlite_master WHERE type = 'table'"),
+        1
+    );
+    assert_eq!(
+        out_db.query_count(
+            "SELECT count(1) FROM sqlite_master WHERE type = 'index' AND tbl_name = 'test'"
+        ),
+        2
+    );
+}
+
+#[rstest]
+fn sqlite_atomic_quoted_name() {
+    let mut in_db = TestSqliteDatabase::new();
+    let mut out_db = TestSqliteDatabase::new();
+    let create_table_query = r#"CREATE TABLE "te""st (1)" (id INTEGER PRIMARY KEY, value TEXT)"#;
+    in_db.execute(create_table_query);
+    out_db.execute(create_table_query);
+    in_db.execute(r#"INSERT INTO "te""st (1)" VALUES (1, 'a'), (2, 'b')"#);
+    out_db.execute(r#"INSERT INTO "te""st (1)" VALUES (100, 'c')"#);
+
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    args.atomic = true;
+    db_mover::run(args).unwrap();
+
+    assert_eq!(
+        out_db.query_count(r#"SELECT count(1) FROM "te""st (1)" WHERE id < 100"#),
+        2
+    );
+    assert_eq!(
+        out_db.query_count("SELECT count(1) FROM sqlite_master WHERE type = 'table'"),
+        1
+    );
+}
+
+#[rstest]
+fn sqlite_incremental(#[values("id", "updated_at")] column: &str) {
+    let mut in_db = TestSqliteDatabase::new();
+    let mut out_db = TestSqliteDatabase::new();
+    let create_table_query =
+        "CREATE TABLE test (id INTEGER PRIMARY KEY, value TEXT, updated_at DATETIME)";
+    in_db.execute(create_table_query);
+    out_db.execute(create_table_query);
+    in_db.execute(
+        "INSERT INTO test VALUES (1, 'a', '2024-01-01 00:00:00'), (2, 'b', '2024-01-02 00:00:00')",
+    );
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    args.incremental = vec![format!("test={column}")];
+    args.state_file = Some(tmp_dir.path().join("state.json"));
+    db_mover::run(args.clone()).unwrap();
+    assert_eq!(out_db.query_count("SELECT count(1) FROM test"), 2);
+
+    in_db.execute("UPDATE test SET value = 'c', updated_at = '2024-01-03 00:00:00' WHERE id = 1");
+    in_db.execute("INSERT INTO test VALUES (3, 'd', '2024-01-04 00:00:00')");
+    db_mover::run(args.clone()).unwrap();
+    assert_eq!(out_db.query_count("SELECT count(1) FROM test"), 3);
+    let value: String = out_db
+        .conn
+        .query_row("SELECT value FROM test WHERE id = 1", [], |row| row.get(0))
+        .unwrap();
+    // Only changes of the incremental column move updated rows
+    assert_eq!(value, if column == "id" { "a" } else { "c" });
+
+    // Replacing the table with only new rows would lose older rows
+    in_db.execute("INSERT INTO test VALUES (4, 'e', '2024-01-05 00:00:00')");
+    args.atomic = true;
+    assert!(db_mover::run(args).is_err());
+    assert_eq!(out_db.query_count("SELECT count(1) FROM test"), 3);
+    assert_eq!(
+        out_db.query_count("SELECT count(1) FROM sqlite_master WHERE type = 'table'"),
+        1
+    );
+}
+
+#[rstest]
+fn sqlite_fast_write(
+    #[values(FastWriteJournal::Wal, FastWriteJournal::Off)] journal: FastWriteJournal,
+) {
+    let mut in_db = TestSqliteDatabase::new();
+    let mut out_db = TestSqliteDatabase::new();
+    in_db.create_test_table("test");
+    // More rows than fit into a single insert
+    in_db.fill_test_table("test", 20_000);
+    in_db.create_test_table("test1");
+    in_db.fill_test_table("test1", 100);
+    out_db.create_test_table("test");
+    out_db.create_test_table("test1");
+
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    args.sqlite_fast_write = Some(journal);
+    // Writers of parallel tables share the journal mode of the database
+    args.parallel_tables = 2;
+    db_mover::run(args).unwrap();
+    assert_eq!(in_db.get_all_rows("test"), out_db.get_all_rows("test"));
+    assert_eq!(in_db.get_all_rows("test1"), out_db.get_all_rows("test1"));
+    let journal_mode: String = out_db
+        .conn
+        .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(journal_mode, "delete");
+}
+
+#[rstest]
+fn sqlite_fast_write_dry_run(
+    #[values(FastWriteJournal::Wal, FastWriteJournal::Off)] journal: FastWriteJournal,
+) {
+    let mut in_db = TestSqliteDatabase::new();
+    let mut out_db = TestSqliteDatabase::new();
+    in_db.create_test_table("test");
+    in_db.fill_test_table("test", 10);
+    out_db.create_test_table("test");
+
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    args.sqlite_fast_write = Some(journal);
+    args.dry_run = true;
+    db_mover::run(args).unwrap();
+    assert_eq!(out_db.query_count("SELECT count(1) FROM test"), 0);
+    let journal_mode: String = out_db
+        .conn
+        .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(journal_mode, "delete");
+}
+
+#[rstest]
+fn sqlite_decimal(#[values(false, true)] decimal_as_real: bool) {
+    let mut in_db = TestSqliteDatabase::new();
+    let out_db = TestSqliteDatabase::new();
+    // Numeric affinity stores decimals as integers and reals
+    in_db.execute("CREATE TABLE test (id INTEGER PRIMARY KEY, value decimal(12, 2))");
+    in_db.execute("INSERT INTO test VALUES (1, 10), (2, 1.5), (3, NULL)");
+
+    let mut args = db_mover::args::Args::new(in_db.get_uri(), out_db.get_uri());
+    args.create_tables = true;
+    args.sqlite_decimal_as_real = decimal_as_real;
+    args.verify = true;
+    db_mover::run(args).unwrap();
+
+    let mut stmt = out_db
+        .conn
+        .prepare(
+            "SELECT typeof(value) || ':' || value FROM test WHERE value IS NOT NULL ORDER BY id",
+        )
+        .unwrap();
+    let result: Vec<String> = stmt
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .map(|res| res.unwrap())
+        .collect();
+    let expected = if decimal_as_real {
+        vec!["integer:10", "real:1.5"]
+    } else {
+        vec!["text:10", "text:1.5"]
+    };
+    assert_eq!(result, expected);
+}